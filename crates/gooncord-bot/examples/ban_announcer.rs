@@ -0,0 +1,60 @@
+//! Announces every ban in the channel and answers `!ping`.
+//!
+//! ```sh
+//! GOONCORD_URL=https://... GOONCORD_KEY=... GOONCORD_BOT_TOKEN=... \
+//!     cargo run -p gooncord-bot --example ban_announcer
+//! ```
+
+use gooncord_bot::{Bot, Event};
+use std::env;
+use std::time::Duration;
+
+fn var(name: &str) -> String {
+    env::var(name).unwrap_or_else(|_| panic!("{} is not set", name))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    let mut bot = Bot::connect(
+        &var("GOONCORD_URL"),
+        &var("GOONCORD_KEY"),
+        &var("GOONCORD_BOT_TOKEN"),
+    )
+    .await?;
+    println!("Connected as {}", bot.username());
+
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let events = match bot.poll().await {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+
+        for event in events {
+            let result = match event {
+                Event::UserBanned(ban) => {
+                    let seconds = (ban.expires_at - ban.created_at).num_seconds();
+                    bot.send(&format!(
+                        "📢 {} got banned for {}s: {}",
+                        ban.username, seconds, ban.reason
+                    ))
+                    .await
+                }
+                Event::MessageCreated(message) if message.content.trim() == "!ping" => {
+                    match bot.react(&message.id, "👍").await {
+                        Ok(()) => bot.send("pong").await,
+                        Err(e) => Err(e),
+                    }
+                }
+                Event::MessageCreated(_) => Ok(()),
+            };
+            if let Err(e) = result {
+                eprintln!("{}", e);
+            }
+        }
+    }
+}
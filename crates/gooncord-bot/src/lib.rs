@@ -0,0 +1,248 @@
+//! Bots for Gooncord.
+//!
+//! A bot is a user account without a password that signs in with an API
+//! token (`/bot create <name>` in the app, moderators only). It sees what
+//! happens in the channel by polling for [`Event`]s and talks back with
+//! [`Bot::send`], [`Bot::react`] and [`Bot::ban`].
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), String> {
+//! use gooncord_bot::{Bot, Event};
+//!
+//! let mut bot = Bot::connect("https://example.supabase.co", "anon key", "token").await?;
+//! loop {
+//!     for event in bot.poll().await? {
+//!         if let Event::MessageCreated(message) = event {
+//!             if message.content == "!ping" {
+//!                 bot.send("pong").await?;
+//!             }
+//!         }
+//!     }
+//!     # break;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{DateTime, Duration, Utc};
+use gooncord_core::postgrest::{Order, RestQuery};
+use gooncord_core::sequencer::Sequencer;
+use gooncord_core::{ApiClient, Backend};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+pub use gooncord_core::ReceivedMessage;
+
+/// Something that happened in the channel.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// Someone else posted a message. The bot's own messages are skipped.
+    MessageCreated(ReceivedMessage),
+    UserBanned(Ban),
+}
+
+#[derive(Debug, Clone)]
+pub struct Ban {
+    pub username: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub struct Bot {
+    api: ApiClient,
+    user_id: Uuid,
+    username: String,
+    sequencer: Sequencer,
+    /// Bans past this id have not been reported yet. Ids only go up, so
+    /// unlike timestamps they can't tie or arrive out of order.
+    last_ban_id: i64,
+}
+
+impl Bot {
+    /// Signs in with a bot token. Only events from now on are reported.
+    pub async fn connect(url: &str, key: &str, token: &str) -> Result<Bot, String> {
+        #[derive(Deserialize)]
+        struct BotUser {
+            user_id: Uuid,
+            username: String,
+        }
+
+        let api = ApiClient::new(url.to_string(), key.to_string());
+        let request = api.rpc("gooncord_bot").json(&json!({ "p_token": token }));
+        let user: Option<BotUser> = api
+            .send(request, "Failed to check bot token")
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse bot token: {}", e))?;
+        let user = user.ok_or("Unknown bot token")?;
+        // Everything the bot does from here on is on its own behalf.
+        api.set_session(Some(token));
+
+        let mut bot = Bot {
+            api,
+            user_id: user.user_id,
+            username: user.username,
+            sequencer: Sequencer::default(),
+            last_ban_id: 0,
+        };
+        // The backlog is history, not news.
+        let api = bot.api.clone();
+        bot.sequencer
+            .poll(Utc::now(), |query| api.get_messages(query))
+            .await?;
+        bot.last_ban_id = bot.latest_ban_id().await?;
+        Ok(bot)
+    }
+
+    pub fn user_id(&self) -> Uuid {
+        self.user_id
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// The backend connection, for anything the SDK doesn't wrap.
+    pub fn api(&self) -> &ApiClient {
+        &self.api
+    }
+
+    /// Everything that happened since the last poll, oldest first within
+    /// each kind. Call it every second or so.
+    pub async fn poll(&mut self) -> Result<Vec<Event>, String> {
+        let api = self.api.clone();
+        let messages = self
+            .sequencer
+            .poll(Utc::now(), |query| api.get_messages(query))
+            .await?;
+        let mut events: Vec<Event> = messages
+            .into_iter()
+            .filter(|m| m.user_id != self.user_id.to_string())
+            .map(Event::MessageCreated)
+            .collect();
+        events.extend(self.new_bans().await?.into_iter().map(Event::UserBanned));
+        Ok(events)
+    }
+
+    async fn latest_ban_id(&self) -> Result<i64, String> {
+        #[derive(Deserialize)]
+        struct BanId {
+            id: i64,
+        }
+
+        let query = RestQuery::table("bans")
+            .select("id")
+            .order("id", Order::Desc)
+            .limit(1);
+        let latest: Vec<BanId> = self
+            .api
+            .send(self.api.get(&query), "Failed to fetch bans")
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse bans: {}", e))?;
+        Ok(latest.first().map_or(0, |ban| ban.id))
+    }
+
+    async fn new_bans(&mut self) -> Result<Vec<Ban>, String> {
+        #[derive(Deserialize)]
+        struct BanRow {
+            id: i64,
+            reason: String,
+            created_at: DateTime<Utc>,
+            expires_at: DateTime<Utc>,
+            users: User,
+        }
+
+        #[derive(Deserialize)]
+        struct User {
+            username: String,
+        }
+
+        let query = RestQuery::table("bans")
+            .gt("id", self.last_ban_id)
+            .select("id,reason,created_at,expires_at,users(username)")
+            .order("id", Order::Asc);
+        let rows: Vec<BanRow> = self
+            .api
+            .send(self.api.get(&query), "Failed to fetch bans")
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse bans: {}", e))?;
+
+        if let Some(last) = rows.last() {
+            self.last_ban_id = last.id;
+        }
+        Ok(rows
+            .into_iter()
+            .map(|row| Ban {
+                username: row.users.username,
+                reason: row.reason,
+                created_at: row.created_at,
+                expires_at: row.expires_at,
+            })
+            .collect())
+    }
+
+    /// Posts to the channel like any other user, message cap included.
+    pub async fn send(&self, content: &str) -> Result<(), String> {
+        self.api
+            .send_message(Uuid::new_v4(), self.user_id, content)
+            .await
+    }
+
+    pub async fn react(&self, message_id: &str, emoji: &str) -> Result<(), String> {
+        let request = self
+            .api
+            .post(&RestQuery::table("reactions").on_conflict("message_id,user_id,emoji"))
+            .header("Prefer", "resolution=ignore-duplicates,return=minimal")
+            .json(&json!({
+                "message_id": message_id,
+                "user_id": self.user_id,
+                "emoji": emoji
+            }));
+        self.api.send(request, "Failed to react").await?;
+        Ok(())
+    }
+
+    /// Bans `username` for `duration`, the same way a ban roll would. The
+    /// database allows an hour at most.
+    pub async fn ban(
+        &self,
+        username: &str,
+        reason: &str,
+        duration: Duration,
+    ) -> Result<(), String> {
+        #[derive(Deserialize)]
+        struct UserRow {
+            id: Uuid,
+        }
+
+        let query = RestQuery::table("users")
+            .eq("username", username)
+            .select("id");
+        let users: Vec<UserRow> = self
+            .api
+            .send(self.api.get(&query), "Failed to look up user")
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse user: {}", e))?;
+        let user = users
+            .first()
+            .ok_or_else(|| format!("No user called {}", username))?;
+
+        let request = self.api.post(&RestQuery::table("bans")).json(&json!({
+            "user_id": user.id,
+            "reason": reason,
+            "expires_at": (Utc::now() + duration).to_rfc3339(),
+            "is_active": true
+        }));
+        self.api.send(request, "Failed to ban").await?;
+        Ok(())
+    }
+}
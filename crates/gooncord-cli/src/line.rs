@@ -0,0 +1,127 @@
+//! One line per record, for line mode and the TUI alike.
+
+use chrono::{DateTime, Local, Utc};
+use gooncord_core::{Ban, ReceivedMessage};
+use serde_json::json;
+
+fn local_time(rfc3339: &str) -> String {
+    DateTime::parse_from_rfc3339(rfc3339)
+        .map(|at| at.with_timezone(&Local).format("%H:%M:%S").to_string())
+        .unwrap_or_else(|_| rfc3339.to_string())
+}
+
+/// `[12:34:56] alice: hi`, with newlines flattened so one message stays one
+/// line.
+pub fn message(message: &ReceivedMessage, json: bool) -> String {
+    if json {
+        return json!(message).to_string();
+    }
+    format!(
+        "[{}] {}: {}",
+        local_time(&message.created_at),
+        message.username,
+        message.content.replace(['\r', '\n'], " ")
+    )
+}
+
+pub fn ban_status(ban: Option<&Ban>, now: DateTime<Utc>) -> String {
+    match ban.filter(|ban| ban.in_effect(now)) {
+        Some(ban) => format!(
+            "Banned for {}s more: {}",
+            (ban.expires_at - now).num_seconds().max(1),
+            ban.reason
+        ),
+        None => "Not banned".to_string(),
+    }
+}
+
+pub fn status(ban: Option<&Ban>, now: DateTime<Utc>, json: bool) -> String {
+    if !json {
+        return ban_status(ban, now);
+    }
+    match ban.filter(|ban| ban.in_effect(now)) {
+        Some(ban) => json!({ "banned": true, "ban": ban }).to_string(),
+        None => json!({ "banned": false }).to_string(),
+    }
+}
+
+pub fn ban(ban: &Ban, now: DateTime<Utc>, json: bool) -> String {
+    if json {
+        return json!(ban).to_string();
+    }
+    let state = if ban.in_effect(now) {
+        "active"
+    } else if ban.is_active {
+        "expired"
+    } else {
+        "lifted"
+    };
+    format!(
+        "{}\t{}s\t{}\t{}",
+        ban.created_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S"),
+        (ban.expires_at - ban.created_at).num_seconds(),
+        state,
+        ban.reason
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn ban_until(expires_at: DateTime<Utc>) -> Ban {
+        Ban {
+            reason: "too many vowels".to_string(),
+            created_at: expires_at - Duration::seconds(20),
+            expires_at,
+            is_active: true,
+        }
+    }
+
+    #[test]
+    fn status_counts_down_until_the_ban_expires() {
+        let now = Utc::now();
+        let ban = ban_until(now + Duration::seconds(12));
+        assert_eq!(
+            ban_status(Some(&ban), now),
+            "Banned for 12s more: too many vowels"
+        );
+        assert_eq!(
+            ban_status(Some(&ban), now + Duration::seconds(12)),
+            "Not banned"
+        );
+        assert_eq!(ban_status(None, now), "Not banned");
+        assert_eq!(status(None, now, true), r#"{"banned":false}"#);
+    }
+
+    #[test]
+    fn history_lines_are_tab_separated() {
+        let now = Utc::now();
+        let line = ban(&ban_until(now - Duration::seconds(1)), now, false);
+        let fields: Vec<&str> = line.split('\t').collect();
+        assert_eq!(fields[1..], ["20s", "expired", "too many vowels"]);
+    }
+
+    #[test]
+    fn messages_stay_on_one_line() {
+        let received = ReceivedMessage {
+            id: "1".to_string(),
+            seq: 1,
+            content: "roses are red\nbans are too".to_string(),
+            username: "alice".to_string(),
+            created_at: "2024-05-01T12:00:00+00:00".to_string(),
+            user_id: "u".to_string(),
+            client_id: None,
+        };
+        let line = message(&received, false);
+        assert!(
+            line.ends_with("] alice: roses are red bans are too"),
+            "{}",
+            line
+        );
+        assert_eq!(message(&received, true).lines().count(), 1);
+    }
+}
@@ -0,0 +1,180 @@
+//! Gooncord from a terminal: chat, tail the channel and check bans without
+//! a webview. Every command prints plain lines (or JSON lines with
+//! `--json`) so it can be piped; `tui` is the full-screen chat.
+
+mod line;
+mod session;
+mod tui;
+
+use chrono::Utc;
+use clap::{Parser, Subcommand};
+use gooncord_core::sequencer::Sequencer;
+use gooncord_core::{backend, ApiClient, Backend, Session};
+use std::env;
+use std::io::{self, BufRead, IsTerminal};
+use std::time::Duration;
+use uuid::Uuid;
+
+#[derive(Parser)]
+#[command(name = "gooncord-cli", about = "Chat and watch bans from a terminal")]
+struct Cli {
+    /// Print JSON lines instead of text.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create an account and sign in.
+    Register {
+        username: String,
+    },
+    /// Sign in. The session is kept until `logout`.
+    Login {
+        username: String,
+    },
+    Logout,
+    /// Send a message, or one message per line of stdin if none is given.
+    Send {
+        message: Vec<String>,
+    },
+    /// Print the latest messages.
+    Tail {
+        /// Keep printing new messages as they arrive.
+        #[arg(short, long)]
+        follow: bool,
+    },
+    Ban {
+        #[command(subcommand)]
+        command: BanCommand,
+    },
+    /// Full-screen chat.
+    Tui,
+}
+
+#[derive(Subcommand)]
+enum BanCommand {
+    /// Whether you are banned right now.
+    Status,
+    /// Every ban you ever got, newest first.
+    History,
+}
+
+fn var(name: &str) -> Result<String, String> {
+    env::var(name).map_err(|_| format!("{} is not set", name))
+}
+
+/// `GOONCORD_PASSWORD` if set, else a prompt, else the first line of stdin.
+fn password() -> Result<String, String> {
+    if let Ok(password) = env::var("GOONCORD_PASSWORD") {
+        return Ok(password);
+    }
+    if io::stdin().is_terminal() {
+        return rpassword::prompt_password("Password: ").map_err(|e| e.to_string());
+    }
+    let mut password = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut password)
+        .map_err(|e| e.to_string())?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+async fn send(backend: &dyn Backend, session: &Session, content: &str) -> Result<(), String> {
+    let now = Utc::now();
+    if let Some(ban) = backend.current_ban(session.user_id, now).await? {
+        return Err(line::ban_status(Some(&ban), now));
+    }
+    backend
+        .send_message(Uuid::new_v4(), session.user_id, content)
+        .await
+}
+
+async fn tail(backend: &dyn Backend, follow: bool, json: bool) -> Result<(), String> {
+    let mut sequencer = Sequencer::default();
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let messages = sequencer
+            .poll(Utc::now(), |query| backend.get_messages(query))
+            .await;
+        match messages {
+            Ok(messages) => {
+                for message in &messages {
+                    println!("{}", line::message(message, json));
+                }
+            }
+            Err(e) if follow => eprintln!("{}", e),
+            Err(e) => return Err(e),
+        }
+        if !follow {
+            return Ok(());
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    let cli = Cli::parse();
+    let backend =
+        backend::from_env(|| Ok(ApiClient::new(var("GOONCORD_URL")?, var("GOONCORD_KEY")?)))?;
+    backend.check_schema().await?;
+
+    match cli.command {
+        Command::Register { username } => {
+            let session = backend.register(&username, &password()?).await?;
+            session::save(&session)?;
+            println!("Registered and logged in as {}", session.username);
+        }
+        Command::Login { username } => {
+            let session = backend.login(&username, &password()?).await?;
+            session::save(&session)?;
+            println!("Logged in as {}", session.username);
+        }
+        Command::Logout => {
+            if let Some(session) = session::load()? {
+                backend.end_session(&session.token).await?;
+            }
+            session::forget()?;
+        }
+        Command::Send { message } => {
+            let session = session::require()?;
+            backend.resume(&session);
+            if message.is_empty() {
+                for content in io::stdin().lock().lines() {
+                    let content = content.map_err(|e| e.to_string())?;
+                    if !content.trim().is_empty() {
+                        send(&*backend, &session, &content).await?;
+                    }
+                }
+            } else {
+                send(&*backend, &session, &message.join(" ")).await?;
+            }
+        }
+        Command::Tail { follow } => tail(&*backend, follow, cli.json).await?,
+        Command::Ban { command } => {
+            let session = session::require()?;
+            backend.resume(&session);
+            let now = Utc::now();
+            match command {
+                BanCommand::Status => {
+                    let ban = backend.current_ban(session.user_id, now).await?;
+                    println!("{}", line::status(ban.as_ref(), now, cli.json));
+                }
+                BanCommand::History => {
+                    for ban in backend.ban_history(session.user_id).await? {
+                        println!("{}", line::ban(&ban, now, cli.json));
+                    }
+                }
+            }
+        }
+        Command::Tui => {
+            let session = session::require()?;
+            backend.resume(&session);
+            tui::run(backend, session).await?
+        }
+    }
+    Ok(())
+}
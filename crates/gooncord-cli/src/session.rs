@@ -0,0 +1,43 @@
+use gooncord_core::Session;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+/// Where the signed-in session is kept between commands.
+fn path() -> Result<PathBuf, String> {
+    dirs::data_local_dir()
+        .map(|dir| dir.join("gooncord").join("cli-session.json"))
+        .ok_or_else(|| "No data directory".to_string())
+}
+
+pub fn save(session: &Session) -> Result<(), String> {
+    let path = path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to save session: {}", e))?;
+    }
+    let json = serde_json::to_string(session).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("Failed to save session: {}", e))
+}
+
+pub fn load() -> Result<Option<Session>, String> {
+    match fs::read_to_string(path()?) {
+        Ok(json) => serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| format!("Failed to read session: {}", e)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read session: {}", e)),
+    }
+}
+
+pub fn require() -> Result<Session, String> {
+    load()?.ok_or_else(|| "Not logged in; run `gooncord-cli login <username>`".to_string())
+}
+
+pub fn forget() -> Result<(), String> {
+    match fs::remove_file(path()?) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            Err(format!("Failed to forget session: {}", e))
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,184 @@
+use chrono::Utc;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use gooncord_core::sequencer::Sequencer;
+use gooncord_core::{Backend, Ban, ReceivedMessage, Session, DEFAULT_CHANNEL};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::Frame;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::line;
+
+/// Messages kept on screen.
+const SCROLLBACK: usize = 500;
+/// How often the ban status is looked up again.
+const BAN_CHECK_EVERY_POLLS: u32 = 5;
+
+struct App {
+    session: Session,
+    messages: Vec<ReceivedMessage>,
+    input: String,
+    ban: Option<Ban>,
+    /// The last error, shown until something succeeds.
+    error: Option<String>,
+}
+
+impl App {
+    fn draw(&self, frame: &mut Frame) {
+        let [messages, input, status] = Layout::vertical([
+            Constraint::Min(3),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let visible = messages.height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = self.messages[self.messages.len().saturating_sub(visible)..]
+            .iter()
+            .map(|m| Line::raw(line::message(m, false)))
+            .collect();
+        let title = format!("#{}", DEFAULT_CHANNEL);
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            messages,
+        );
+
+        let now = Utc::now();
+        let (title, style) = match self.ban.as_ref().filter(|ban| ban.in_effect(now)) {
+            Some(ban) => (
+                line::ban_status(Some(ban), now),
+                Style::default().fg(Color::Red),
+            ),
+            None => ("Message".to_string(), Style::default()),
+        };
+        frame.render_widget(
+            Paragraph::new(self.input.as_str())
+                .block(Block::bordered().title(title).border_style(style)),
+            input,
+        );
+        frame.set_cursor_position((input.x + 1 + self.input.chars().count() as u16, input.y + 1));
+
+        let status_line = match &self.error {
+            Some(error) => Paragraph::new(error.as_str()).style(Style::default().fg(Color::Red)),
+            None => Paragraph::new(format!(
+                "{} · Enter to send · Esc to quit",
+                self.session.username
+            )),
+        };
+        frame.render_widget(status_line, status);
+    }
+
+    async fn submit(&mut self, backend: &dyn Backend) {
+        let content = self.input.trim().to_string();
+        if content.is_empty() {
+            return;
+        }
+        if let Some(ban) = self.ban.as_ref().filter(|ban| ban.in_effect(Utc::now())) {
+            self.error = Some(line::ban_status(Some(ban), Utc::now()));
+            return;
+        }
+        match backend
+            .send_message(Uuid::new_v4(), self.session.user_id, &content)
+            .await
+        {
+            Ok(()) => {
+                self.input.clear();
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+}
+
+/// Reads terminal events on a thread of its own, since crossterm blocks.
+fn key_events() -> mpsc::UnboundedReceiver<Event> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+pub async fn run(backend: Arc<dyn Backend>, session: Session) -> Result<(), String> {
+    let mut terminal = ratatui::init();
+    let result = run_app(&mut terminal, &*backend, session).await;
+    ratatui::restore();
+    result
+}
+
+async fn run_app(
+    terminal: &mut ratatui::DefaultTerminal,
+    backend: &dyn Backend,
+    session: Session,
+) -> Result<(), String> {
+    let mut app = App {
+        session,
+        messages: Vec::new(),
+        input: String::new(),
+        ban: None,
+        error: None,
+    };
+    let mut sequencer = Sequencer::default();
+    let mut events = key_events();
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    let mut polls = 0u32;
+
+    loop {
+        terminal
+            .draw(|frame| app.draw(frame))
+            .map_err(|e| e.to_string())?;
+
+        tokio::select! {
+            _ = interval.tick() => {
+                match sequencer.poll(Utc::now(), |query| backend.get_messages(query)).await {
+                    Ok(messages) => {
+                        app.messages.extend(messages);
+                        let excess = app.messages.len().saturating_sub(SCROLLBACK);
+                        app.messages.drain(..excess);
+                    }
+                    Err(e) => app.error = Some(e),
+                }
+                if polls.is_multiple_of(BAN_CHECK_EVERY_POLLS) {
+                    match backend.current_ban(app.session.user_id, Utc::now()).await {
+                        Ok(ban) => app.ban = ban,
+                        Err(e) => app.error = Some(e),
+                    }
+                }
+                polls = polls.wrapping_add(1);
+            }
+            event = events.recv() => {
+                let Some(event) = event else {
+                    return Err("Lost the terminal".to_string());
+                };
+                let Event::Key(key) = event else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(())
+                    }
+                    KeyCode::Char(c) => app.input.push(c),
+                    KeyCode::Backspace => {
+                        app.input.pop();
+                    }
+                    KeyCode::Enter => app.submit(backend).await,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
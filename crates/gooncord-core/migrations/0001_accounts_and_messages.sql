@@ -0,0 +1,45 @@
+-- Accounts, sessions, the message feed and bans: everything the
+-- `Backend` trait touches.
+
+CREATE TABLE IF NOT EXISTS users (
+    id uuid PRIMARY KEY,
+    username text NOT NULL UNIQUE,
+    password_hash text NOT NULL,
+    is_moderator boolean NOT NULL DEFAULT false,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS sessions (
+    token text PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    expires_at timestamptz NOT NULL
+);
+CREATE INDEX IF NOT EXISTS sessions_by_user ON sessions (user_id);
+
+CREATE TABLE IF NOT EXISTS messages (
+    id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- The order messages are shown in; see `sequencer.rs`.
+    seq bigint GENERATED ALWAYS AS IDENTITY UNIQUE,
+    -- Lets a resent message be recognised; see `outbox.rs`.
+    client_id uuid UNIQUE,
+    channel text NOT NULL DEFAULT 'general',
+    content text NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS messages_by_channel ON messages (channel, seq);
+CREATE INDEX IF NOT EXISTS messages_by_user ON messages (user_id);
+-- Backs the `wfts(english)` filter used by search.
+CREATE INDEX IF NOT EXISTS messages_content_fts
+    ON messages USING gin (to_tsvector('english', content));
+
+CREATE TABLE IF NOT EXISTS bans (
+    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    reason text NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    expires_at timestamptz NOT NULL,
+    is_active boolean NOT NULL DEFAULT true
+);
+CREATE INDEX IF NOT EXISTS bans_by_user ON bans (user_id, created_at);
+CREATE INDEX IF NOT EXISTS bans_by_created_at ON bans (created_at);
@@ -0,0 +1,71 @@
+-- Achievements, coins, ban events, reactions, pins, bookmarks and read
+-- cursors.
+
+CREATE TABLE IF NOT EXISTS achievements (
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    achievement_id text NOT NULL,
+    unlocked_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, achievement_id)
+);
+
+CREATE TABLE IF NOT EXISTS wallets (
+    user_id uuid PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    balance bigint NOT NULL DEFAULT 0 CHECK (balance >= 0)
+);
+
+CREATE TABLE IF NOT EXISTS inventory (
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    item text NOT NULL,
+    quantity integer NOT NULL DEFAULT 0 CHECK (quantity >= 0),
+    UNIQUE (user_id, item)
+);
+
+-- Scheduled ban storms, amnesties and name hunts; see `ban_events.rs`.
+CREATE TABLE IF NOT EXISTS ban_events (
+    id text PRIMARY KEY,
+    kind text NOT NULL CHECK (kind IN ('ban_storm', 'amnesty', 'name_prefix')),
+    chance double precision,
+    prefix text,
+    starts_at timestamptz NOT NULL,
+    ends_at timestamptz NOT NULL CHECK (ends_at > starts_at)
+);
+CREATE INDEX IF NOT EXISTS ban_events_by_end ON ban_events (ends_at);
+
+CREATE TABLE IF NOT EXISTS reactions (
+    id bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    message_id uuid NOT NULL REFERENCES messages (id) ON DELETE CASCADE,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    emoji text NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    UNIQUE (message_id, user_id, emoji)
+);
+
+CREATE TABLE IF NOT EXISTS pins (
+    message_id uuid PRIMARY KEY REFERENCES messages (id) ON DELETE CASCADE,
+    channel text NOT NULL,
+    pinned_by uuid REFERENCES users (id) ON DELETE SET NULL,
+    pinned_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS pins_by_channel ON pins (channel, pinned_at);
+
+-- Bookmarks keep a copy of the message, so no foreign key to `messages`:
+-- they outlive the message being trimmed.
+CREATE TABLE IF NOT EXISTS bookmarks (
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    message_id uuid NOT NULL,
+    username text NOT NULL,
+    content text NOT NULL,
+    created_at timestamptz NOT NULL,
+    note text,
+    bookmarked_at timestamptz NOT NULL DEFAULT now(),
+    UNIQUE (user_id, message_id)
+);
+
+CREATE TABLE IF NOT EXISTS read_cursors (
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    channel text NOT NULL,
+    last_read_seq bigint NOT NULL,
+    updated_at timestamptz NOT NULL DEFAULT now(),
+    UNIQUE (user_id, channel)
+);
+CREATE INDEX IF NOT EXISTS read_cursors_by_channel ON read_cursors (channel, last_read_seq);
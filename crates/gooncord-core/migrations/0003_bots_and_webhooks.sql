@@ -0,0 +1,32 @@
+-- Bot accounts and their tokens, and incoming and outgoing webhooks.
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS is_bot boolean NOT NULL DEFAULT false;
+
+CREATE TABLE IF NOT EXISTS bot_tokens (
+    token text PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_by uuid REFERENCES users (id) ON DELETE SET NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS bot_tokens_by_user ON bot_tokens (user_id);
+
+CREATE TABLE IF NOT EXISTS incoming_webhooks (
+    id uuid PRIMARY KEY,
+    name text NOT NULL,
+    channel text NOT NULL DEFAULT 'general',
+    secret text NOT NULL UNIQUE,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_by uuid REFERENCES users (id) ON DELETE SET NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS outgoing_webhooks (
+    id uuid PRIMARY KEY,
+    url text NOT NULL,
+    secret text NOT NULL,
+    events text[] NOT NULL DEFAULT '{}',
+    -- The bot the delivering relay signs in as; no other gets the secret.
+    relay_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_by uuid REFERENCES users (id) ON DELETE SET NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,337 @@
+-- Row-level security for Supabase.
+--
+-- Gooncord issues its own sessions rather than Supabase JWTs, so every
+-- client reaches PostgREST with the same anon key. To tell users apart,
+-- clients send their session token (a bot sends its bot token) in an
+-- `X-Gooncord-Session` header, and `gooncord_current_user()` turns that into
+-- the user id the policies below check ownership against.
+--
+-- Password hashes, session tokens, bot tokens and webhook secrets are never
+-- granted to anon at all. Registering, signing in, checking sessions and the
+-- webhook relay's lookups go through the `SECURITY DEFINER` functions here,
+-- which only ever hand out the caller's own session.
+--
+-- What the anon key can't express, and clients are trusted with instead:
+-- bans are rolled on the client, so signed-in users may ban themselves for
+-- up to an hour at a time (moderators and their bots may ban anyone); coin
+-- balances and inventory are worked out on the client, so users can set
+-- their own (but only their own); and retention runs on clients, so any
+-- signed-in user may delete unpinned messages. A deployment that can't live
+-- with that runs `gooncord-server`, which rolls bans and caps the channel
+-- itself. The table owner (the migration runner, `gooncord-server`)
+-- bypasses all of this.
+
+-- Supabase has these roles already; a plain Postgres gets them so the
+-- grants below apply the same way.
+DO $$
+BEGIN
+    CREATE ROLE anon NOLOGIN;
+EXCEPTION WHEN duplicate_object THEN NULL;
+END
+$$;
+DO $$
+BEGIN
+    CREATE ROLE authenticated NOLOGIN;
+EXCEPTION WHEN duplicate_object THEN NULL;
+END
+$$;
+
+-- `crypt`, `gen_salt` and `gen_random_bytes`. Supabase keeps it in the
+-- `extensions` schema, hence the functions' search paths.
+CREATE EXTENSION IF NOT EXISTS pgcrypto;
+
+-- Who the request's `X-Gooncord-Session` header signs in as, if anyone.
+CREATE OR REPLACE FUNCTION gooncord_current_user() RETURNS uuid
+LANGUAGE sql STABLE SECURITY DEFINER SET search_path = public AS $$
+    WITH header AS (
+        SELECT nullif(current_setting('request.headers', true), '')::json
+            ->> 'x-gooncord-session' AS token
+    )
+    SELECT user_id FROM sessions, header
+    WHERE sessions.token = header.token AND sessions.expires_at > now()
+    UNION ALL
+    SELECT user_id FROM bot_tokens, header
+    WHERE bot_tokens.token = header.token
+    LIMIT 1
+$$;
+
+CREATE OR REPLACE FUNCTION gooncord_is_moderator() RETURNS boolean
+LANGUAGE sql STABLE SECURITY DEFINER SET search_path = public AS $$
+    SELECT coalesce(
+        (SELECT is_moderator FROM users WHERE id = gooncord_current_user()),
+        false
+    )
+$$;
+
+-- The rules `auth::validate_username` enforces.
+CREATE OR REPLACE FUNCTION gooncord_valid_username(p_username text) RETURNS boolean
+LANGUAGE sql IMMUTABLE AS $$
+    SELECT p_username ~ '^[A-Za-z0-9_-]{3,24}$'
+        AND lower(p_username) <> ALL (ARRAY[
+            'admin', 'administrator', 'bot', 'everyone', 'gooncord', 'here',
+            'mod', 'moderator', 'null', 'root', 'server', 'system'
+        ])
+$$;
+
+-- The unexpired session `p_token` is, shaped like `auth::Session`.
+CREATE OR REPLACE FUNCTION gooncord_session(p_token text) RETURNS json
+LANGUAGE sql STABLE SECURITY DEFINER SET search_path = public AS $$
+    SELECT json_build_object(
+        'token', sessions.token,
+        'user_id', users.id,
+        'username', users.username,
+        'is_moderator', users.is_moderator
+    )
+    FROM sessions JOIN users ON users.id = sessions.user_id
+    WHERE sessions.token = p_token AND sessions.expires_at > now()
+$$;
+
+CREATE OR REPLACE FUNCTION gooncord_start_session(p_user_id uuid) RETURNS json
+LANGUAGE plpgsql SECURITY DEFINER SET search_path = public, extensions AS $$
+DECLARE
+    -- 32 random bytes, URL-safe base64, like the clients' own tokens.
+    v_token text := translate(encode(gen_random_bytes(32), 'base64'), '+/=', '-_');
+BEGIN
+    INSERT INTO sessions (token, user_id, expires_at)
+    VALUES (v_token, p_user_id, now() + interval '30 days');
+    RETURN gooncord_session(v_token);
+END
+$$;
+-- Only for the functions below: called directly it would sign anyone in.
+REVOKE EXECUTE ON FUNCTION gooncord_start_session(uuid) FROM PUBLIC, anon, authenticated;
+
+CREATE OR REPLACE FUNCTION gooncord_register(p_username text, p_password text) RETURNS json
+LANGUAGE plpgsql SECURITY DEFINER SET search_path = public, extensions AS $$
+DECLARE
+    v_id uuid := gen_random_uuid();
+BEGIN
+    IF NOT gooncord_valid_username(p_username) THEN
+        RAISE EXCEPTION 'Invalid username' USING ERRCODE = '22023';
+    END IF;
+    BEGIN
+        INSERT INTO users (id, username, password_hash)
+        VALUES (v_id, p_username, crypt(p_password, gen_salt('bf')));
+    EXCEPTION WHEN unique_violation THEN
+        RAISE EXCEPTION 'Username already exists' USING ERRCODE = '23505';
+    END;
+    RETURN gooncord_start_session(v_id);
+END
+$$;
+
+-- A new session, or null if the username or password is wrong.
+CREATE OR REPLACE FUNCTION gooncord_login(p_username text, p_password text) RETURNS json
+LANGUAGE plpgsql SECURITY DEFINER SET search_path = public, extensions AS $$
+DECLARE
+    v_id uuid;
+    v_hash text;
+BEGIN
+    SELECT id, password_hash INTO v_id, v_hash
+    FROM users WHERE username = p_username AND NOT is_bot;
+    IF v_id IS NULL THEN
+        RETURN NULL;
+    END IF;
+    -- Accounts made before this migration were hashed by the client's
+    -- bcrypt as `$2b$`, which pgcrypto reads as the identical `$2a$`.
+    v_hash := overlay(v_hash PLACING '$2a$' FROM 1 FOR 4);
+    IF crypt(p_password, v_hash) <> v_hash THEN
+        RETURN NULL;
+    END IF;
+    RETURN gooncord_start_session(v_id);
+END
+$$;
+
+CREATE OR REPLACE FUNCTION gooncord_end_session(p_token text) RETURNS void
+LANGUAGE sql SECURITY DEFINER SET search_path = public AS $$
+    DELETE FROM sessions WHERE token = p_token
+$$;
+
+-- The bot `p_token` signs in as, if it's a bot token.
+CREATE OR REPLACE FUNCTION gooncord_bot(p_token text) RETURNS json
+LANGUAGE sql STABLE SECURITY DEFINER SET search_path = public AS $$
+    SELECT json_build_object('user_id', users.id, 'username', users.username)
+    FROM bot_tokens JOIN users ON users.id = bot_tokens.user_id
+    WHERE bot_tokens.token = p_token
+$$;
+
+-- Posts `p_content` as the incoming webhook whose secret is `p_secret`.
+-- False if there is no such webhook.
+CREATE OR REPLACE FUNCTION gooncord_post_webhook(p_secret text, p_content text)
+RETURNS boolean
+LANGUAGE plpgsql SECURITY DEFINER SET search_path = public AS $$
+BEGIN
+    IF length(p_content) NOT BETWEEN 1 AND 2000 THEN
+        RAISE EXCEPTION 'Content must be 1 to 2000 characters' USING ERRCODE = '22023';
+    END IF;
+    INSERT INTO messages (client_id, channel, content, user_id)
+    SELECT gen_random_uuid(), channel, p_content, user_id
+    FROM incoming_webhooks WHERE secret = p_secret;
+    RETURN FOUND;
+END
+$$;
+
+-- The outgoing webhooks the calling bot relays, with the secrets to sign
+-- deliveries with. Every other caller, bot or not, gets none.
+CREATE OR REPLACE FUNCTION gooncord_outgoing_webhooks()
+RETURNS TABLE (id uuid, url text, secret text, events text[])
+LANGUAGE sql STABLE SECURITY DEFINER SET search_path = public AS $$
+    SELECT hooks.id, hooks.url, hooks.secret, hooks.events
+    FROM outgoing_webhooks hooks
+    WHERE hooks.relay_id = gooncord_current_user()
+$$;
+
+-- Nothing is reachable unless granted below.
+REVOKE ALL ON ALL TABLES IN SCHEMA public FROM anon, authenticated;
+
+ALTER TABLE users ENABLE ROW LEVEL SECURITY;
+GRANT SELECT (id, username, is_moderator, is_bot, created_at),
+    INSERT (id, username, password_hash, is_bot, created_at),
+    UPDATE (username)
+    ON users TO anon, authenticated;
+CREATE POLICY users_read ON users FOR SELECT USING (true);
+-- People sign up through `gooncord_register`; moderators add bots, which
+-- have no password.
+CREATE POLICY users_add_bot ON users FOR INSERT WITH CHECK (
+    is_bot AND password_hash = '!' AND gooncord_is_moderator()
+    AND gooncord_valid_username(username)
+);
+CREATE POLICY users_rename ON users FOR UPDATE
+    USING (id = gooncord_current_user())
+    WITH CHECK (id = gooncord_current_user() AND gooncord_valid_username(username));
+
+-- No grants: sessions are only reached through the functions above.
+ALTER TABLE sessions ENABLE ROW LEVEL SECURITY;
+
+ALTER TABLE messages ENABLE ROW LEVEL SECURITY;
+GRANT SELECT, INSERT, DELETE ON messages TO anon, authenticated;
+CREATE POLICY messages_read ON messages FOR SELECT USING (true);
+CREATE POLICY messages_send ON messages FOR INSERT WITH CHECK (
+    user_id = gooncord_current_user()
+    AND length(content) BETWEEN 1 AND 2000
+    AND NOT EXISTS (
+        SELECT 1 FROM bans
+        WHERE bans.user_id = messages.user_id
+          AND bans.is_active AND bans.expires_at > now()
+    )
+);
+-- Trimming the channel and retention, which clients run; pinned messages
+-- stay.
+CREATE POLICY messages_delete ON messages FOR DELETE USING (
+    gooncord_current_user() IS NOT NULL
+    AND NOT EXISTS (SELECT 1 FROM pins WHERE pins.message_id = messages.id)
+);
+
+-- Rolled on the client; lifting one by deleting it isn't allowed. People
+-- only ban themselves (a redirected ban goes through
+-- `gooncord_redirect_ban`), `created_at` is always the database's `now()`,
+-- and no ban runs longer than an hour.
+ALTER TABLE bans ENABLE ROW LEVEL SECURITY;
+GRANT SELECT, INSERT (user_id, reason, expires_at, is_active)
+    ON bans TO anon, authenticated;
+CREATE POLICY bans_read ON bans FOR SELECT USING (true);
+CREATE POLICY bans_create ON bans FOR INSERT WITH CHECK (
+    (
+        user_id = gooncord_current_user()
+        OR gooncord_is_moderator()
+        OR EXISTS (SELECT 1 FROM users WHERE id = gooncord_current_user() AND is_bot)
+    )
+    AND expires_at > now() AND expires_at <= now() + interval '1 hour'
+    AND length(reason) BETWEEN 1 AND 200
+);
+
+ALTER TABLE achievements ENABLE ROW LEVEL SECURITY;
+GRANT SELECT, INSERT ON achievements TO anon, authenticated;
+CREATE POLICY achievements_read ON achievements FOR SELECT USING (true);
+CREATE POLICY achievements_unlock ON achievements FOR INSERT
+    WITH CHECK (user_id = gooncord_current_user());
+
+ALTER TABLE wallets ENABLE ROW LEVEL SECURITY;
+GRANT SELECT, INSERT, UPDATE ON wallets TO anon, authenticated;
+CREATE POLICY wallets_read ON wallets FOR SELECT USING (true);
+CREATE POLICY wallets_create ON wallets FOR INSERT
+    WITH CHECK (user_id = gooncord_current_user());
+CREATE POLICY wallets_update ON wallets FOR UPDATE
+    USING (user_id = gooncord_current_user())
+    WITH CHECK (user_id = gooncord_current_user());
+
+ALTER TABLE inventory ENABLE ROW LEVEL SECURITY;
+GRANT SELECT, INSERT, UPDATE ON inventory TO anon, authenticated;
+CREATE POLICY inventory_read ON inventory FOR SELECT USING (true);
+CREATE POLICY inventory_create ON inventory FOR INSERT
+    WITH CHECK (user_id = gooncord_current_user());
+CREATE POLICY inventory_update ON inventory FOR UPDATE
+    USING (user_id = gooncord_current_user())
+    WITH CHECK (user_id = gooncord_current_user());
+
+-- Scheduled from the dashboard only.
+ALTER TABLE ban_events ENABLE ROW LEVEL SECURITY;
+GRANT SELECT ON ban_events TO anon, authenticated;
+CREATE POLICY ban_events_read ON ban_events FOR SELECT USING (true);
+
+ALTER TABLE reactions ENABLE ROW LEVEL SECURITY;
+GRANT SELECT, INSERT ON reactions TO anon, authenticated;
+CREATE POLICY reactions_read ON reactions FOR SELECT USING (true);
+CREATE POLICY reactions_add ON reactions FOR INSERT
+    WITH CHECK (user_id = gooncord_current_user());
+
+ALTER TABLE pins ENABLE ROW LEVEL SECURITY;
+GRANT SELECT, INSERT, DELETE ON pins TO anon, authenticated;
+CREATE POLICY pins_read ON pins FOR SELECT USING (true);
+CREATE POLICY pins_add ON pins FOR INSERT WITH CHECK (gooncord_is_moderator());
+CREATE POLICY pins_remove ON pins FOR DELETE USING (gooncord_is_moderator());
+
+ALTER TABLE bookmarks ENABLE ROW LEVEL SECURITY;
+GRANT SELECT, INSERT, UPDATE, DELETE ON bookmarks TO anon, authenticated;
+CREATE POLICY bookmarks_read ON bookmarks FOR SELECT
+    USING (user_id = gooncord_current_user());
+CREATE POLICY bookmarks_add ON bookmarks FOR INSERT
+    WITH CHECK (user_id = gooncord_current_user());
+CREATE POLICY bookmarks_update ON bookmarks FOR UPDATE
+    USING (user_id = gooncord_current_user())
+    WITH CHECK (user_id = gooncord_current_user());
+CREATE POLICY bookmarks_remove ON bookmarks FOR DELETE
+    USING (user_id = gooncord_current_user());
+
+-- Everyone's cursors are readable: they're the read receipts.
+ALTER TABLE read_cursors ENABLE ROW LEVEL SECURITY;
+GRANT SELECT, INSERT, UPDATE ON read_cursors TO anon, authenticated;
+CREATE POLICY read_cursors_read ON read_cursors FOR SELECT USING (true);
+CREATE POLICY read_cursors_create ON read_cursors FOR INSERT
+    WITH CHECK (user_id = gooncord_current_user());
+CREATE POLICY read_cursors_update ON read_cursors FOR UPDATE
+    USING (user_id = gooncord_current_user())
+    WITH CHECK (user_id = gooncord_current_user());
+
+-- Bots and webhooks are for moderators to manage. Tokens and secrets are
+-- shown once, when created, and never read back.
+ALTER TABLE bot_tokens ENABLE ROW LEVEL SECURITY;
+GRANT SELECT (user_id, created_by, created_at), INSERT, DELETE
+    ON bot_tokens TO anon, authenticated;
+CREATE POLICY bot_tokens_read ON bot_tokens FOR SELECT USING (gooncord_is_moderator());
+CREATE POLICY bot_tokens_create ON bot_tokens FOR INSERT WITH CHECK (gooncord_is_moderator());
+CREATE POLICY bot_tokens_revoke ON bot_tokens FOR DELETE USING (gooncord_is_moderator());
+
+ALTER TABLE incoming_webhooks ENABLE ROW LEVEL SECURITY;
+GRANT SELECT (id, name, channel, user_id, created_by, created_at), INSERT, DELETE
+    ON incoming_webhooks TO anon, authenticated;
+CREATE POLICY incoming_webhooks_read ON incoming_webhooks FOR SELECT
+    USING (gooncord_is_moderator());
+CREATE POLICY incoming_webhooks_create ON incoming_webhooks FOR INSERT
+    WITH CHECK (gooncord_is_moderator());
+CREATE POLICY incoming_webhooks_delete ON incoming_webhooks FOR DELETE
+    USING (gooncord_is_moderator());
+
+ALTER TABLE outgoing_webhooks ENABLE ROW LEVEL SECURITY;
+GRANT SELECT (id, url, events, relay_id, created_by, created_at), INSERT, DELETE
+    ON outgoing_webhooks TO anon, authenticated;
+CREATE POLICY outgoing_webhooks_read ON outgoing_webhooks FOR SELECT
+    USING (gooncord_is_moderator());
+CREATE POLICY outgoing_webhooks_create ON outgoing_webhooks FOR INSERT WITH CHECK (
+    gooncord_is_moderator()
+    AND EXISTS (SELECT 1 FROM users WHERE users.id = relay_id AND users.is_bot)
+);
+CREATE POLICY outgoing_webhooks_delete ON outgoing_webhooks FOR DELETE
+    USING (gooncord_is_moderator());
+
+ALTER TABLE schema_migrations ENABLE ROW LEVEL SECURITY;
+GRANT SELECT ON schema_migrations TO anon, authenticated;
+CREATE POLICY schema_migrations_read ON schema_migrations FOR SELECT USING (true);
@@ -0,0 +1,48 @@
+-- Items in use: shields until they run out, halvings and redirects until a
+-- ban uses them up. Stored so they outlast signing out and back in.
+
+CREATE TABLE IF NOT EXISTS active_effects (
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    item text NOT NULL,
+    -- Null for effects that last until a ban consumes them.
+    expires_at timestamptz,
+    UNIQUE (user_id, item)
+);
+
+ALTER TABLE active_effects ENABLE ROW LEVEL SECURITY;
+GRANT SELECT, INSERT, DELETE ON active_effects TO anon, authenticated;
+CREATE POLICY active_effects_read ON active_effects FOR SELECT USING (true);
+CREATE POLICY active_effects_add ON active_effects FOR INSERT
+    WITH CHECK (user_id = gooncord_current_user());
+CREATE POLICY active_effects_remove ON active_effects FOR DELETE
+    USING (user_id = gooncord_current_user());
+
+-- A `ban_redirect` in effect sends the caller's next ban to someone else,
+-- picked here. Using one up and handing on the ban happen together, so a
+-- redirect can't be replayed; returns who got the ban.
+CREATE OR REPLACE FUNCTION gooncord_redirect_ban(p_reason text, p_expires_at timestamptz)
+RETURNS text
+LANGUAGE plpgsql SECURITY DEFINER SET search_path = public AS $$
+DECLARE
+    target users%ROWTYPE;
+BEGIN
+    IF p_expires_at <= now() OR p_expires_at > now() + interval '1 hour'
+        OR length(p_reason) NOT BETWEEN 1 AND 200 THEN
+        RAISE EXCEPTION 'Bans last up to an hour' USING ERRCODE = '22023';
+    END IF;
+    DELETE FROM active_effects
+    WHERE user_id = gooncord_current_user() AND item = 'ban_redirect';
+    IF NOT FOUND THEN
+        RAISE EXCEPTION 'No ban redirect in effect' USING ERRCODE = '42501';
+    END IF;
+    SELECT * INTO target FROM users
+    WHERE id <> gooncord_current_user() AND NOT is_bot
+    ORDER BY random()
+    LIMIT 1;
+    IF NOT FOUND THEN
+        RAISE EXCEPTION 'Nobody else to redirect the ban to' USING ERRCODE = 'P0002';
+    END IF;
+    INSERT INTO bans (user_id, reason, expires_at) VALUES (target.id, p_reason, p_expires_at);
+    RETURN target.username;
+END
+$$;
@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+
+use crate::BAN_REASONS;
+
+/// Something that happened to the current user that achievements can react to.
+#[derive(Debug, Clone, Copy)]
+pub enum AchievementEvent<'a> {
+    BanReceived,
+    ReasonCollected(&'a str),
+    MessageSent,
+    Survived { minutes: u64 },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Requirement {
+    Bans(u32),
+    Reasons(usize),
+    AllReasons,
+    MessagesInSession(u32),
+    SurvivedMinutes(u64),
+}
+
+#[derive(Debug)]
+pub struct Achievement {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub badge: &'static str,
+    requirement: Requirement,
+}
+
+pub const CATALOG: &[Achievement] = &[
+    Achievement {
+        id: "first_ban",
+        name: "Welcome to Gooncord",
+        description: "Get banned for the first time",
+        badge: "🔨",
+        requirement: Requirement::Bans(1),
+    },
+    Achievement {
+        id: "frequent_flyer",
+        name: "Frequent Flyer",
+        description: "Get banned 10 times",
+        badge: "✈️",
+        requirement: Requirement::Bans(10),
+    },
+    Achievement {
+        id: "hall_of_shame",
+        name: "Hall of Shame",
+        description: "Get banned 100 times",
+        badge: "🏚️",
+        requirement: Requirement::Bans(100),
+    },
+    Achievement {
+        id: "collector",
+        name: "Collector",
+        description: "Collect 10 different ban reasons",
+        badge: "📜",
+        requirement: Requirement::Reasons(10),
+    },
+    Achievement {
+        id: "completionist",
+        name: "Completionist",
+        description: "Collect every single ban reason",
+        badge: "👑",
+        requirement: Requirement::AllReasons,
+    },
+    Achievement {
+        id: "chatterbox",
+        name: "Chatterbox",
+        description: "Send 50 messages in one session",
+        badge: "💬",
+        requirement: Requirement::MessagesInSession(50),
+    },
+    Achievement {
+        id: "untouchable",
+        name: "Untouchable",
+        description: "Survive 5 minutes without a ban",
+        badge: "🛡️",
+        requirement: Requirement::SurvivedMinutes(5),
+    },
+    Achievement {
+        id: "ban_proof",
+        name: "Ban-proof",
+        description: "Survive 15 minutes without a ban",
+        badge: "🦾",
+        requirement: Requirement::SurvivedMinutes(15),
+    },
+];
+
+pub fn find(id: &str) -> Option<&'static Achievement> {
+    CATALOG.iter().find(|a| a.id == id)
+}
+
+/// Progress towards the catalog for the logged in user.
+///
+/// Ban count and collected reasons are seeded from the user's ban history on
+/// login, so they survive restarts; message counts are per session. Only
+/// reasons from [`BAN_REASONS`] count as collected, so a `/banme` with a
+/// reason of your own is still a ban but not a new reason.
+#[derive(Debug, Clone, Default)]
+pub struct AchievementTracker {
+    bans: u32,
+    reasons: HashSet<String>,
+    messages: u32,
+    survived_minutes: u64,
+    unlocked: HashSet<&'static str>,
+}
+
+impl AchievementTracker {
+    pub fn new(unlocked_ids: &[String], past_reasons: &[String]) -> Self {
+        AchievementTracker {
+            bans: past_reasons.len() as u32,
+            reasons: past_reasons
+                .iter()
+                .filter(|r| BAN_REASONS.contains(&r.as_str()))
+                .cloned()
+                .collect(),
+            messages: 0,
+            survived_minutes: 0,
+            unlocked: unlocked_ids
+                .iter()
+                .filter_map(|id| find(id))
+                .map(|a| a.id)
+                .collect(),
+        }
+    }
+
+    pub fn bans(&self) -> u32 {
+        self.bans
+    }
+
+    pub fn reasons_collected(&self) -> usize {
+        self.reasons.len()
+    }
+
+    pub fn messages(&self) -> u32 {
+        self.messages
+    }
+
+    pub fn unlocked(&self) -> impl Iterator<Item = &'static Achievement> + '_ {
+        CATALOG.iter().filter(|a| self.unlocked.contains(a.id))
+    }
+
+    /// Applies an event and returns the achievements it newly unlocked.
+    pub fn record(&mut self, event: AchievementEvent) -> Vec<&'static Achievement> {
+        match event {
+            AchievementEvent::BanReceived => self.bans += 1,
+            AchievementEvent::ReasonCollected(reason) => {
+                if BAN_REASONS.contains(&reason) {
+                    self.reasons.insert(reason.to_string());
+                }
+            }
+            AchievementEvent::MessageSent => self.messages += 1,
+            AchievementEvent::Survived { minutes } => {
+                self.survived_minutes = self.survived_minutes.max(minutes);
+            }
+        }
+
+        let newly_unlocked: Vec<&'static Achievement> = CATALOG
+            .iter()
+            .filter(|a| !self.unlocked.contains(a.id) && self.is_met(a.requirement))
+            .collect();
+        self.unlocked.extend(newly_unlocked.iter().map(|a| a.id));
+        newly_unlocked
+    }
+
+    fn is_met(&self, requirement: Requirement) -> bool {
+        match requirement {
+            Requirement::Bans(n) => self.bans >= n,
+            Requirement::Reasons(n) => self.reasons.len() >= n,
+            Requirement::AllReasons => BAN_REASONS.iter().all(|r| self.reasons.contains(*r)),
+            Requirement::MessagesInSession(n) => self.messages >= n,
+            Requirement::SurvivedMinutes(n) => self.survived_minutes >= n,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(unlocked: &[&'static Achievement]) -> Vec<&'static str> {
+        unlocked.iter().map(|a| a.id).collect()
+    }
+
+    #[test]
+    fn unlocks_at_the_thresholds() {
+        let mut tracker = AchievementTracker::default();
+        assert_eq!(
+            ids(&tracker.record(AchievementEvent::BanReceived)),
+            vec!["first_ban"]
+        );
+        for _ in 2..10 {
+            assert!(tracker.record(AchievementEvent::BanReceived).is_empty());
+        }
+        let unlocked = tracker.record(AchievementEvent::BanReceived);
+        assert_eq!(ids(&unlocked), vec!["frequent_flyer"]);
+
+        for _ in 1..50 {
+            assert!(tracker.record(AchievementEvent::MessageSent).is_empty());
+        }
+        assert_eq!(
+            ids(&tracker.record(AchievementEvent::MessageSent)),
+            vec!["chatterbox"]
+        );
+
+        let survived = |minutes| AchievementEvent::Survived { minutes };
+        assert!(tracker.record(survived(4)).is_empty());
+        assert_eq!(
+            ids(&tracker.record(survived(15))),
+            vec!["untouchable", "ban_proof"]
+        );
+    }
+
+    #[test]
+    fn collecting_reasons_unlocks_collector_then_completionist() {
+        let mut tracker = AchievementTracker::default();
+        for reason in &BAN_REASONS[..9] {
+            assert!(tracker
+                .record(AchievementEvent::ReasonCollected(reason))
+                .is_empty());
+        }
+        let unlocked = tracker.record(AchievementEvent::ReasonCollected(BAN_REASONS[9]));
+        assert_eq!(ids(&unlocked), vec!["collector"]);
+
+        for reason in &BAN_REASONS[10..BAN_REASONS.len() - 1] {
+            assert!(tracker
+                .record(AchievementEvent::ReasonCollected(reason))
+                .is_empty());
+        }
+        let last = BAN_REASONS[BAN_REASONS.len() - 1];
+        let unlocked = tracker.record(AchievementEvent::ReasonCollected(last));
+        assert_eq!(ids(&unlocked), vec!["completionist"]);
+        assert_eq!(tracker.reasons_collected(), BAN_REASONS.len());
+    }
+
+    #[test]
+    fn unlocked_achievements_are_not_reported_again() {
+        let mut tracker = AchievementTracker::new(&["first_ban".to_string()], &[]);
+        assert!(tracker.record(AchievementEvent::BanReceived).is_empty());
+
+        let survived = AchievementEvent::Survived { minutes: 5 };
+        assert_eq!(ids(&tracker.record(survived)), vec!["untouchable"]);
+        assert!(tracker.record(survived).is_empty());
+        assert_eq!(
+            ids(&tracker.unlocked().collect::<Vec<_>>()),
+            vec!["first_ban", "untouchable"]
+        );
+    }
+
+    #[test]
+    fn only_known_reasons_are_collected() {
+        let past = vec!["asking for it".to_string(), BAN_REASONS[0].to_string()];
+        let mut tracker = AchievementTracker::new(&[], &past);
+        assert_eq!(tracker.bans(), 2);
+        assert_eq!(tracker.reasons_collected(), 1);
+
+        for i in 0..20 {
+            tracker.record(AchievementEvent::ReasonCollected(&format!("made up {}", i)));
+        }
+        assert_eq!(tracker.reasons_collected(), 1);
+        assert!(tracker.unlocked().all(|a| a.id != "collector"));
+    }
+}
@@ -0,0 +1,2435 @@
+use crate::achievements::{Achievement, AchievementEvent, AchievementTracker};
+use crate::ban_events::{BanEvent, BanPolicy};
+use crate::cache::{Cache, CacheConfig};
+use crate::chance::{self, Chance, Clock};
+use crate::commands::{Args, Command, CommandRegistry, Input, Outcome};
+use crate::economy::{ActiveEffect, BanIntercept, Item, Wallet};
+use crate::events::UiEvent;
+use crate::outbox::{Outbox, OutboxEntry, OutboxStatus};
+use crate::postgrest::{Order, RestQuery};
+use crate::receipts::ReadCursor;
+use crate::search::{SearchQuery, SearchResults};
+use crate::shared::{AuthState, SharedState};
+use crate::spectator::{Action, SpectatorPolicy};
+use crate::{achievements, auth, backend, commands, economy, receipts};
+use crate::{ApiClient, Backend, ReceivedMessage, Session, BAN_REASONS, DEFAULT_CHANNEL};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLockReadGuard};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum WebMessage {
+    Message {
+        content: String,
+    },
+    RetryMessage {
+        client_id: Uuid,
+    },
+    Register {
+        username: String,
+        password: String,
+    },
+    Login {
+        username: String,
+        password: String,
+    },
+    CheckAuth,
+    Logout,
+    RequestMessages,
+    Wallet,
+    BuyItem {
+        item: Item,
+    },
+    UseItem {
+        item: Item,
+    },
+    React {
+        message_id: String,
+        emoji: String,
+    },
+    ViewProfile {
+        username: String,
+    },
+    Search(SearchQuery),
+    PinMessage {
+        message_id: String,
+    },
+    UnpinMessage {
+        message_id: String,
+    },
+    ListPins,
+    Bookmark {
+        message_id: String,
+        note: Option<String>,
+    },
+    RemoveBookmark {
+        message_id: String,
+    },
+    ListBookmarks,
+    MarkRead {
+        seq: i64,
+    },
+    SeenBy {
+        seq: i64,
+    },
+    Autocomplete {
+        prefix: String,
+    },
+    CreateIncomingWebhook {
+        name: String,
+    },
+    CreateOutgoingWebhook {
+        url: String,
+        events: Vec<String>,
+        relay: String,
+    },
+    ListWebhooks,
+    DeleteWebhook {
+        kind: WebhookKind,
+        id: Uuid,
+    },
+}
+
+impl WebMessage {
+    fn action(&self) -> Action {
+        match self {
+            WebMessage::Message { .. }
+            | WebMessage::RetryMessage { .. }
+            | WebMessage::PinMessage { .. }
+            | WebMessage::UnpinMessage { .. } => Action::Chat,
+            WebMessage::React { .. } => Action::React,
+            WebMessage::ViewProfile { .. } => Action::ViewProfile,
+            WebMessage::BuyItem { .. } | WebMessage::UseItem { .. } => Action::Shop,
+            WebMessage::Register { .. }
+            | WebMessage::Login { .. }
+            | WebMessage::CheckAuth
+            | WebMessage::Logout
+            | WebMessage::RequestMessages
+            | WebMessage::Wallet
+            | WebMessage::Search(_)
+            | WebMessage::ListPins
+            | WebMessage::Bookmark { .. }
+            | WebMessage::RemoveBookmark { .. }
+            | WebMessage::ListBookmarks
+            | WebMessage::MarkRead { .. }
+            | WebMessage::SeenBy { .. }
+            | WebMessage::Autocomplete { .. }
+            | WebMessage::CreateIncomingWebhook { .. }
+            | WebMessage::CreateOutgoingWebhook { .. }
+            | WebMessage::ListWebhooks
+            | WebMessage::DeleteWebhook { .. } => Action::Passive,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Profile {
+    pub username: String,
+    pub created_at: String,
+    pub ban_count: usize,
+    pub is_banned: bool,
+    pub badges: Vec<String>,
+}
+
+/// What outgoing webhooks can subscribe to.
+pub const WEBHOOK_EVENTS: &[&str] = &["message", "ban"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum WebhookKind {
+    Incoming,
+    Outgoing,
+}
+
+/// Listed without its secret, which is only shown when it's created.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IncomingWebhook {
+    pub id: Uuid,
+    pub name: String,
+    pub channel: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutgoingWebhook {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<String>,
+    pub created_at: String,
+}
+
+/// A message a moderator pinned to the channel.
+#[derive(Serialize, Debug, Clone)]
+pub struct Pin {
+    pub message_id: String,
+    pub username: String,
+    pub content: String,
+    pub created_at: String,
+    pub pinned_at: String,
+}
+
+/// A private bookmark. Keeps its own copy of the message, since the message
+/// itself is gone after the next cleanup.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Bookmark {
+    pub message_id: String,
+    pub username: String,
+    pub content: String,
+    pub created_at: String,
+    pub note: Option<String>,
+    pub bookmarked_at: String,
+}
+
+/// Outcome of a single random ban roll.
+pub enum BanRoll {
+    Spared,
+    Banned,
+    Shielded,
+    Redirected { username: String },
+}
+
+/// Everything one signed-in client knows. Owned by a single task that feeds
+/// it [`WebMessage`]s and [`tick`]s; anything background workers need to see
+/// lives in `shared`.
+pub struct AppState {
+    pub shared: Arc<SharedState>,
+    /// What the ban system reads the time from; see [`chance`].
+    clock: Arc<dyn Clock>,
+    /// What the ban system rolls with.
+    rng: chance::Rng,
+    last_ban_check: DateTime<Utc>,
+    achievements: AchievementTracker,
+    ban_free_since: DateTime<Utc>,
+    wallet: Wallet,
+    ban_policy: BanPolicy,
+    announced_events: HashSet<String>,
+    last_event_refresh: Option<DateTime<Utc>>,
+    spectator_policy: SpectatorPolicy,
+    outbox: Outbox,
+    /// Highest sequence number read in the default channel, if ever.
+    read_cursor: Option<i64>,
+    commands: CommandRegistry,
+}
+
+/// Where Gooncord keeps its local files, if the platform has such a place.
+fn data_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("gooncord"))
+}
+
+impl AppState {
+    pub fn new() -> Self {
+        let supabase_url = "Not giving you my url either".to_string();
+        let supabase_key = "I am not giving you my key".to_string();
+
+        let config = CacheConfig::from_env();
+        let cache = data_dir()
+            .ok_or_else(|| "No data directory".to_string())
+            .and_then(|dir| Cache::open(&dir.join("cache.sqlite3"), config))
+            .or_else(|e| {
+                eprintln!("{}, caching in memory only", e);
+                Cache::in_memory(config)
+            })
+            .expect("in-memory cache");
+
+        let api = ApiClient::new(supabase_url, supabase_key);
+        let backend = backend::from_env(|| Ok(api.clone())).unwrap_or_else(|e| {
+            eprintln!("{}, using Supabase", e);
+            Arc::new(api.clone())
+        });
+        let outbox = Outbox::load(data_dir().map(|dir| dir.join("outbox.json")));
+        let chance = Chance::from_env().unwrap_or_else(|e| {
+            eprintln!("{}, leaving bans to chance", e);
+            Chance::system()
+        });
+        AppState::with_backend(api, backend, cache, outbox).with_chance(chance)
+    }
+
+    /// An app state on the given connections instead of the ones `new`
+    /// picks from the environment, e.g. a mock server in tests.
+    pub fn with_backend(
+        api: ApiClient,
+        backend: Arc<dyn Backend>,
+        cache: Cache,
+        outbox: Outbox,
+    ) -> Self {
+        let Chance { clock, rng } = Chance::system();
+        let now = clock.now();
+        AppState {
+            shared: SharedState::new(api, backend, cache),
+            clock,
+            rng,
+            last_ban_check: now,
+            achievements: AchievementTracker::default(),
+            ban_free_since: now,
+            wallet: Wallet::default(),
+            ban_policy: BanPolicy::default(),
+            announced_events: HashSet::new(),
+            last_event_refresh: None,
+            spectator_policy: SpectatorPolicy::default(),
+            outbox,
+            read_cursor: None,
+            commands: CommandRegistry::default(),
+        }
+    }
+
+    /// The same state on another clock and RNG: seeded in tests, recorded
+    /// or replayed when debugging a session's bans.
+    pub fn with_chance(mut self, chance: Chance) -> Self {
+        let now = chance.clock.now();
+        self.clock = chance.clock;
+        self.rng = chance.rng;
+        self.last_ban_check = now;
+        self.ban_free_since = now;
+        self
+    }
+
+    pub fn auth(&self) -> RwLockReadGuard<'_, AuthState> {
+        self.shared.auth()
+    }
+
+    pub async fn register_user(
+        &mut self,
+        username: &str,
+        password: &str,
+    ) -> Result<String, String> {
+        let session = self.shared.backend.register(username, password).await?;
+        self.start_session(session);
+        self.load_extras().await;
+        Ok("Registration successful! You are now logged in.".to_string())
+    }
+
+    pub async fn logout(&mut self) {
+        if let Err(e) = self.end_session().await {
+            eprintln!("Error ending session: {}", e);
+        }
+        self.shared.set_logged_out();
+        self.achievements = AchievementTracker::default();
+        self.wallet = Wallet::default();
+        self.read_cursor = None;
+    }
+
+    pub async fn end_session(&self) -> Result<(), String> {
+        let Some(token) = self.auth().session_token.clone() else {
+            return Ok(());
+        };
+        self.shared.backend.end_session(&token).await
+    }
+
+    pub fn start_session(&mut self, session: Session) {
+        self.shared.set_logged_in(
+            session.token,
+            session.user_id,
+            &session.username,
+            session.is_moderator,
+        );
+        if let Err(e) = self
+            .shared
+            .cache()
+            .set_last_owner(&session.user_id.to_string())
+        {
+            eprintln!("{}", e);
+        }
+    }
+
+    pub async fn login_user(&mut self, username: &str, password: &str) -> Result<String, String> {
+        let session = self.shared.backend.login(username, password).await?;
+        self.start_session(session);
+        self.check_ban_status().await?;
+        self.load_extras().await;
+        Ok("Login successful!".to_string())
+    }
+
+    /// Loads achievements, wallet and read cursor for a fresh session, if
+    /// the backend keeps them.
+    async fn load_extras(&mut self) {
+        if !self.shared.backend.has_extras() {
+            return;
+        }
+        if let Err(e) = self.load_achievements().await {
+            eprintln!("Failed to load achievements: {}", e);
+        }
+        if let Err(e) = self.load_wallet().await {
+            eprintln!("Failed to load wallet: {}", e);
+        }
+        if let Err(e) = self.load_read_cursor().await {
+            eprintln!("Failed to load read cursor: {}", e);
+        }
+    }
+
+    pub async fn load_achievements(&mut self) -> Result<(), String> {
+        let Some(user_id) = self.auth().current_user_id else {
+            return Err("User not logged in".to_string());
+        };
+
+        #[derive(Deserialize)]
+        struct UnlockedAchievement {
+            achievement_id: String,
+        }
+
+        let query = RestQuery::table("achievements")
+            .eq("user_id", user_id)
+            .select("achievement_id");
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch achievements: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        let unlocked: Vec<String> = response
+            .json::<Vec<UnlockedAchievement>>()
+            .await
+            .map_err(|e| format!("Failed to parse achievements: {}", e))?
+            .into_iter()
+            .map(|a| a.achievement_id)
+            .collect();
+
+        #[derive(Deserialize)]
+        struct PastBan {
+            reason: String,
+        }
+
+        let query = RestQuery::table("bans")
+            .eq("user_id", user_id)
+            .select("reason");
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch ban history: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        let past_reasons: Vec<String> = response
+            .json::<Vec<PastBan>>()
+            .await
+            .map_err(|e| format!("Failed to parse ban history: {}", e))?
+            .into_iter()
+            .map(|b| b.reason)
+            .collect();
+
+        self.achievements = AchievementTracker::new(&unlocked, &past_reasons);
+        self.ban_free_since = self.clock.now();
+        Ok(())
+    }
+
+    /// Feeds an event to the tracker and persists anything it unlocks, if
+    /// the backend keeps achievements.
+    pub async fn record_achievement_event(
+        &mut self,
+        event: AchievementEvent<'_>,
+    ) -> Vec<&'static Achievement> {
+        let unlocked = self.achievements.record(event);
+        if !self.shared.backend.has_extras() {
+            return unlocked;
+        }
+        for achievement in &unlocked {
+            if let Err(e) = self.save_achievement(achievement).await {
+                eprintln!("Failed to save achievement {}: {}", achievement.id, e);
+            }
+        }
+        unlocked
+    }
+
+    pub async fn save_achievement(&self, achievement: &Achievement) -> Result<(), String> {
+        let user_id = self.auth().current_user_id;
+        let response = self
+            .shared
+            .extras()?
+            .post(&RestQuery::table("achievements"))
+            .header("Content-Type", "application/json")
+            .header("Prefer", "return=minimal")
+            .json(&json!({
+                "user_id": user_id,
+                "achievement_id": achievement.id,
+                "unlocked_at": self.clock.now().to_rfc3339()
+            }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Seconds left on the current ban, or `None` when not banned or the ban
+    /// has run out.
+    pub fn ban_remaining_secs(&self) -> Option<i64> {
+        let auth = self.auth();
+        if !auth.is_banned {
+            return None;
+        }
+        let remaining = (auth.ban_expires? - self.clock.now()).num_seconds();
+        (remaining > 0).then_some(remaining)
+    }
+
+    /// Adds a plugin's slash command; see [`CommandRegistry::register`].
+    pub fn register_command(&mut self, command: Command) -> Result<(), String> {
+        self.commands.register(command)
+    }
+
+    pub fn run_command(&mut self, name: &str, args: Args<'_>) -> Result<Outcome, String> {
+        let username = self.auth().current_username.clone().unwrap_or_default();
+        let mut ctx = commands::Context {
+            username: &username,
+            stats: commands::Stats {
+                bans: self.achievements.bans(),
+                reasons_collected: self.achievements.reasons_collected(),
+                messages: self.achievements.messages(),
+                balance: self.wallet.balance,
+            },
+            rng: &mut self.rng,
+        };
+        self.commands.run(name, args, &mut ctx)
+    }
+
+    pub async fn rename(&self, username: &str) -> Result<(), String> {
+        let Some(user_id) = self.auth().current_user_id else {
+            return Err("User not logged in".to_string());
+        };
+        auth::validate_username(username)?;
+
+        let response = self
+            .shared
+            .extras()?
+            .patch(&RestQuery::table("users").eq("id", user_id))
+            .header("Content-Type", "application/json")
+            .header("Prefer", "return=minimal")
+            .json(&json!({ "username": username }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to change username: {}", e))?;
+
+        if response.status() == reqwest::StatusCode::CONFLICT {
+            return Err(format!("{} is already taken", username));
+        }
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to change username: {}", error));
+        }
+
+        self.shared.set_username(username);
+        Ok(())
+    }
+
+    /// Makes a bot account and returns its API token. Bots have no password,
+    /// so they can't log in to the app; they sign in with the token through
+    /// the `gooncord-bot` crate.
+    pub async fn create_bot(&self, name: &str) -> Result<String, String> {
+        let auth = self.auth().clone();
+        if !auth.is_moderator {
+            return Err("Only moderators can create bots".to_string());
+        }
+
+        let bot_id = self.create_bot_user(name).await?;
+        let token = Self::generate_secret();
+        let response = self
+            .shared
+            .extras()?
+            .post(&RestQuery::table("bot_tokens"))
+            .header("Content-Type", "application/json")
+            .header("Prefer", "return=minimal")
+            .json(&json!({
+                "token": token,
+                "user_id": bot_id,
+                "created_by": auth.current_user_id,
+                "created_at": self.clock.now().to_rfc3339()
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Bot token creation failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Bot token creation failed: {}", error));
+        }
+        Ok(token)
+    }
+
+    pub async fn revoke_bot(&self, name: &str) -> Result<(), String> {
+        if !self.auth().is_moderator {
+            return Err("Only moderators can revoke bots".to_string());
+        }
+
+        let query = RestQuery::table("users")
+            .eq("username", name)
+            .is("is_bot", Some(true))
+            .select("id");
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to look up bot: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to look up bot: {}", error));
+        }
+
+        let users: Vec<serde_json::Value> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse bot: {}", e))?;
+        let bot_id = users
+            .first()
+            .and_then(|user| user["id"].as_str())
+            .ok_or_else(|| format!("No bot called {}", name))?
+            .to_string();
+
+        let response = self
+            .shared
+            .extras()?
+            .delete(&RestQuery::table("bot_tokens").eq("user_id", bot_id))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to revoke bot: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to revoke bot: {}", error));
+        }
+        Ok(())
+    }
+
+    /// A user without a password for bots and integrations to post as.
+    /// Bots are named like everyone else.
+    pub async fn create_bot_user(&self, name: &str) -> Result<Uuid, String> {
+        auth::validate_username(name)?;
+        let bot_id = Uuid::new_v4();
+        let response = self
+            .shared
+            .extras()?
+            .post(&RestQuery::table("users"))
+            .header("Content-Type", "application/json")
+            .header("Prefer", "return=minimal")
+            .json(&json!({
+                "id": bot_id,
+                "username": name,
+                "password_hash": "!",
+                "is_bot": true,
+                "created_at": self.clock.now().to_rfc3339()
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Bot creation failed: {}", e))?;
+
+        if response.status() == reqwest::StatusCode::CONFLICT {
+            return Err(format!("{} is already taken", name));
+        }
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Bot creation failed: {}", error));
+        }
+        Ok(bot_id)
+    }
+
+    /// An incoming webhook posting into the default channel as a new bot
+    /// user called `name`. Returns the secret for its URL.
+    pub async fn create_incoming_webhook(&self, name: &str) -> Result<String, String> {
+        let auth = self.auth().clone();
+        if !auth.is_moderator {
+            return Err("Only moderators can manage webhooks".to_string());
+        }
+
+        let user_id = self.create_bot_user(name).await?;
+        let secret = Self::generate_secret();
+        let response = self
+            .shared
+            .extras()?
+            .post(&RestQuery::table("incoming_webhooks"))
+            .header("Content-Type", "application/json")
+            .header("Prefer", "return=minimal")
+            .json(&json!({
+                "id": Uuid::new_v4(),
+                "name": name,
+                "channel": DEFAULT_CHANNEL,
+                "secret": secret,
+                "user_id": user_id,
+                "created_by": auth.current_user_id,
+                "created_at": self.clock.now().to_rfc3339()
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Webhook creation failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Webhook creation failed: {}", error));
+        }
+        Ok(secret)
+    }
+
+    /// An outgoing webhook POSTing `events` to `target`. Returns the secret
+    /// its payloads are signed with.
+    /// An outgoing webhook delivered by the relay signed in as the bot
+    /// called `relay`, which alone gets to see its secret. Returns the
+    /// secret.
+    pub async fn create_outgoing_webhook(
+        &self,
+        target: &str,
+        events: &[String],
+        relay: &str,
+    ) -> Result<String, String> {
+        let auth = self.auth().clone();
+        if !auth.is_moderator {
+            return Err("Only moderators can manage webhooks".to_string());
+        }
+        if !target.starts_with("https://") && !target.starts_with("http://") {
+            return Err("Webhook URLs must start with http:// or https://".to_string());
+        }
+        if events.is_empty() || events.iter().any(|e| !WEBHOOK_EVENTS.contains(&e.as_str())) {
+            return Err(format!(
+                "Pick at least one of: {}",
+                WEBHOOK_EVENTS.join(", ")
+            ));
+        }
+
+        #[derive(Deserialize)]
+        struct Relay {
+            id: Uuid,
+        }
+
+        let query = RestQuery::table("users")
+            .eq("username", relay)
+            .eq("is_bot", true)
+            .select("id");
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to look up relay bot: {}", e))?;
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to look up relay bot: {}", error));
+        }
+        let relays: Vec<Relay> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse relay bot: {}", e))?;
+        let Some(relay) = relays.first() else {
+            return Err(format!("No bot called {} to relay the webhook", relay));
+        };
+
+        let secret = Self::generate_secret();
+        let response = self
+            .shared
+            .extras()?
+            .post(&RestQuery::table("outgoing_webhooks"))
+            .header("Content-Type", "application/json")
+            .header("Prefer", "return=minimal")
+            .json(&json!({
+                "id": Uuid::new_v4(),
+                "url": target,
+                "secret": secret,
+                "events": events,
+                "relay_id": relay.id,
+                "created_by": auth.current_user_id,
+                "created_at": self.clock.now().to_rfc3339()
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Webhook creation failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Webhook creation failed: {}", error));
+        }
+        Ok(secret)
+    }
+
+    pub async fn list_webhooks(
+        &self,
+    ) -> Result<(Vec<IncomingWebhook>, Vec<OutgoingWebhook>), String> {
+        if !self.auth().is_moderator {
+            return Err("Only moderators can manage webhooks".to_string());
+        }
+
+        let mut lists = Vec::new();
+        for query in [
+            RestQuery::table("incoming_webhooks").select("id,name,channel,created_at"),
+            RestQuery::table("outgoing_webhooks").select("id,url,events,created_at"),
+        ] {
+            let response = self
+                .shared
+                .extras()?
+                .get(&query.order("created_at", Order::Asc))
+                .send()
+                .await
+                .map_err(|e| format!("Failed to fetch webhooks: {}", e))?;
+
+            if !response.status().is_success() {
+                let error = response.text().await.map_err(|e| e.to_string())?;
+                return Err(format!("API error: {}", error));
+            }
+            lists.push(
+                response
+                    .json::<serde_json::Value>()
+                    .await
+                    .map_err(|e| format!("Failed to parse webhooks: {}", e))?,
+            );
+        }
+
+        let outgoing = serde_json::from_value(lists.pop().unwrap_or_default())
+            .map_err(|e| format!("Failed to parse webhooks: {}", e))?;
+        let incoming = serde_json::from_value(lists.pop().unwrap_or_default())
+            .map_err(|e| format!("Failed to parse webhooks: {}", e))?;
+        Ok((incoming, outgoing))
+    }
+
+    pub async fn delete_webhook(&self, kind: WebhookKind, id: Uuid) -> Result<(), String> {
+        if !self.auth().is_moderator {
+            return Err("Only moderators can manage webhooks".to_string());
+        }
+
+        let table = match kind {
+            WebhookKind::Incoming => "incoming_webhooks",
+            WebhookKind::Outgoing => "outgoing_webhooks",
+        };
+        let response = self
+            .shared
+            .extras()?
+            .delete(&RestQuery::table(table).eq("id", id))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to delete webhook: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to delete webhook: {}", error));
+        }
+        Ok(())
+    }
+
+    pub fn generate_secret() -> String {
+        let bytes: [u8; 32] = rand::thread_rng().gen();
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    /// `/banme`: a ban like any other, just not left to chance.
+    pub async fn ban_self(&mut self, reason: &str) -> Result<(), String> {
+        let Some(user_id) = self.auth().current_user_id else {
+            return Err("User not logged in".to_string());
+        };
+        let duration_secs = self.rng.gen_range(10..30);
+        let expires_at = self.clock.now() + chrono::Duration::seconds(duration_secs);
+        self.create_ban(user_id, reason, expires_at).await?;
+        self.shared.set_banned(reason, expires_at);
+        Ok(())
+    }
+
+    pub fn lift_ban(&mut self) {
+        self.shared.set_unbanned();
+        self.ban_free_since = self.clock.now();
+    }
+
+    pub async fn check_ban_status(&mut self) -> Result<(), String> {
+        let Some(user_id) = self.auth().current_user_id else {
+            return Err("User not logged in".to_string());
+        };
+
+        match self
+            .shared
+            .backend
+            .current_ban(user_id, self.clock.now())
+            .await?
+        {
+            Some(ban) => self.shared.set_banned(&ban.reason, ban.expires_at),
+            None => self.lift_ban(),
+        }
+        Ok(())
+    }
+
+    pub async fn react_to_message(&self, message_id: &str, emoji: &str) -> Result<(), String> {
+        let Some(user_id) = self.auth().current_user_id else {
+            return Err("User not identified".to_string());
+        };
+
+        let query = RestQuery::table("reactions").on_conflict("message_id,user_id,emoji");
+        let response = self
+            .shared
+            .extras()?
+            .post(&query)
+            .header("Content-Type", "application/json")
+            .header("Prefer", "resolution=ignore-duplicates,return=minimal")
+            .json(&json!({
+                "message_id": message_id,
+                "user_id": user_id,
+                "emoji": emoji
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to react: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to react: {}", error));
+        }
+        Ok(())
+    }
+
+    pub async fn load_read_cursor(&mut self) -> Result<(), String> {
+        let Some(user_id) = self.auth().current_user_id else {
+            return Err("User not logged in".to_string());
+        };
+
+        #[derive(Deserialize)]
+        struct CursorRow {
+            last_read_seq: i64,
+        }
+
+        let query = RestQuery::table("read_cursors")
+            .eq("user_id", user_id)
+            .eq("channel", DEFAULT_CHANNEL)
+            .select("last_read_seq");
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch read cursor: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        let rows: Vec<CursorRow> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse read cursor: {}", e))?;
+        self.read_cursor = rows.first().map(|row| row.last_read_seq);
+        Ok(())
+    }
+
+    /// Messages in the default channel past the read cursor, counted by the
+    /// server. Nothing is unread for someone who has never read the channel.
+    pub async fn unread_count(&self) -> Result<u64, String> {
+        let Some(cursor) = self.read_cursor else {
+            return Ok(0);
+        };
+
+        let query = RestQuery::table("messages")
+            .eq("channel", DEFAULT_CHANNEL)
+            .gt("seq", cursor)
+            .select("id")
+            .limit(1);
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .header("Prefer", "count=exact")
+            .send()
+            .await
+            .map_err(|e| format!("Failed to count unread messages: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        response
+            .headers()
+            .get("Content-Range")
+            .and_then(|value| value.to_str().ok())
+            .and_then(receipts::parse_total)
+            .ok_or_else(|| "Server didn't count unread messages".to_string())
+    }
+
+    pub async fn unread_marker(&self) -> UiEvent {
+        let unread = if self.shared.backend.has_extras() {
+            self.unread_count().await.unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                0
+            })
+        } else {
+            0
+        };
+        UiEvent::UnreadMarker {
+            last_read_seq: self.read_cursor,
+            unread,
+        }
+    }
+
+    /// Moves the read cursor up to `seq`. Returns whether it moved; it never
+    /// goes backwards.
+    pub async fn mark_read(&mut self, seq: i64) -> Result<bool, String> {
+        let Some(user_id) = self.auth().current_user_id else {
+            return Err("User not logged in".to_string());
+        };
+        if self.read_cursor.is_some_and(|cursor| cursor >= seq) {
+            return Ok(false);
+        }
+
+        let query = RestQuery::table("read_cursors").on_conflict("user_id,channel");
+        let response = self
+            .shared
+            .extras()?
+            .post(&query)
+            .header("Content-Type", "application/json")
+            .header("Prefer", "resolution=merge-duplicates,return=minimal")
+            .json(&json!({
+                "user_id": user_id,
+                "channel": DEFAULT_CHANNEL,
+                "last_read_seq": seq,
+                "updated_at": self.clock.now().to_rfc3339()
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to save read cursor: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to save read cursor: {}", error));
+        }
+
+        self.read_cursor = Some(seq);
+        Ok(true)
+    }
+
+    /// Everyone's read cursor in the default channel, one more than
+    /// `receipts::seen_by` looks at so it can tell when to give up.
+    pub async fn read_cursors(&self) -> Result<Vec<ReadCursor>, String> {
+        #[derive(Deserialize)]
+        struct CursorRow {
+            last_read_seq: i64,
+            users: User,
+        }
+
+        #[derive(Deserialize)]
+        struct User {
+            username: String,
+        }
+
+        let query = RestQuery::table("read_cursors")
+            .eq("channel", DEFAULT_CHANNEL)
+            .select("last_read_seq,users(username)")
+            .limit(receipts::SEEN_BY_MAX_READERS + 1);
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch read cursors: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        let rows: Vec<CursorRow> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse read cursors: {}", e))?;
+        Ok(rows
+            .into_iter()
+            .map(|row| ReadCursor {
+                username: row.users.username,
+                last_read_seq: row.last_read_seq,
+            })
+            .collect())
+    }
+
+    pub async fn get_profile(&self, username: &str) -> Result<Profile, String> {
+        #[derive(Deserialize)]
+        struct UserRow {
+            id: Uuid,
+            username: String,
+            created_at: String,
+        }
+
+        #[derive(Deserialize)]
+        struct BanRow {
+            expires_at: String,
+            is_active: bool,
+        }
+
+        #[derive(Deserialize)]
+        struct AchievementRow {
+            achievement_id: String,
+        }
+
+        let query = RestQuery::table("users")
+            .eq("username", username)
+            .select("id,username,created_at");
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch profile: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        let users: Vec<UserRow> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse profile: {}", e))?;
+        let user = users.into_iter().next().ok_or("User not found")?;
+
+        let query = RestQuery::table("bans")
+            .eq("user_id", user.id)
+            .select("expires_at,is_active");
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch bans: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        let bans: Vec<BanRow> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse bans: {}", e))?;
+        let now = self.clock.now();
+        let is_banned = bans.iter().any(|ban| {
+            ban.is_active
+                && DateTime::parse_from_rfc3339(&ban.expires_at)
+                    .is_ok_and(|expires| expires.with_timezone(&Utc) > now)
+        });
+
+        let query = RestQuery::table("achievements")
+            .eq("user_id", user.id)
+            .select("achievement_id");
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch achievements: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        let achievements: Vec<AchievementRow> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse achievements: {}", e))?;
+
+        Ok(Profile {
+            username: user.username,
+            created_at: user.created_at,
+            ban_count: bans.len(),
+            is_banned,
+            badges: achievements
+                .iter()
+                .filter_map(|a| achievements::find(&a.achievement_id))
+                .map(|a| format!("{} {}", a.badge, a.name))
+                .collect(),
+        })
+    }
+
+    /// Pins a message in the default channel, sparing it from retention.
+    /// Pinning it again changes nothing. Moderators only.
+    pub async fn pin_message(&self, message_id: &str) -> Result<(), String> {
+        let auth = self.auth().clone();
+        if !auth.is_moderator {
+            return Err("Only moderators can pin messages".to_string());
+        }
+
+        let query = RestQuery::table("pins").on_conflict("message_id");
+        let response = self
+            .shared
+            .extras()?
+            .post(&query)
+            .header("Content-Type", "application/json")
+            .header("Prefer", "resolution=ignore-duplicates,return=minimal")
+            .json(&json!({
+                "message_id": message_id,
+                "channel": DEFAULT_CHANNEL,
+                "pinned_by": auth.current_user_id,
+                "pinned_at": self.clock.now().to_rfc3339()
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to pin message: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to pin message: {}", error));
+        }
+        Ok(())
+    }
+
+    pub async fn unpin_message(&self, message_id: &str) -> Result<(), String> {
+        if !self.auth().is_moderator {
+            return Err("Only moderators can unpin messages".to_string());
+        }
+
+        let query = RestQuery::table("pins").eq("message_id", message_id);
+        let response = self
+            .shared
+            .extras()?
+            .delete(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to unpin message: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to unpin message: {}", error));
+        }
+        Ok(())
+    }
+
+    pub async fn list_pins(&self) -> Result<Vec<Pin>, String> {
+        #[derive(Deserialize)]
+        struct PinRow {
+            message_id: String,
+            pinned_at: String,
+            messages: MessageRow,
+        }
+
+        #[derive(Deserialize)]
+        struct MessageRow {
+            content: String,
+            created_at: String,
+            users: User,
+        }
+
+        #[derive(Deserialize)]
+        struct User {
+            username: String,
+        }
+
+        let query = RestQuery::table("pins")
+            .eq("channel", DEFAULT_CHANNEL)
+            .select("message_id,pinned_at,messages(content,created_at,users(username))")
+            .order("pinned_at", Order::Desc);
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch pins: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        let rows: Vec<PinRow> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse pins: {}", e))?;
+        Ok(rows
+            .into_iter()
+            .map(|row| Pin {
+                message_id: row.message_id,
+                username: row.messages.users.username,
+                content: row.messages.content,
+                created_at: row.messages.created_at,
+                pinned_at: row.pinned_at,
+            })
+            .collect())
+    }
+
+    pub async fn add_bookmark(&self, message_id: &str, note: Option<String>) -> Result<(), String> {
+        let Some(user_id) = self.auth().current_user_id else {
+            return Err("User not logged in".to_string());
+        };
+
+        #[derive(Deserialize)]
+        struct MessageRow {
+            content: String,
+            created_at: String,
+            users: User,
+        }
+
+        #[derive(Deserialize)]
+        struct User {
+            username: String,
+        }
+
+        let query = RestQuery::table("messages")
+            .eq("id", message_id)
+            .select("content,created_at,users(username)");
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch message: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        let messages: Vec<MessageRow> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse message: {}", e))?;
+        let message = messages.into_iter().next().ok_or("That message is gone")?;
+
+        let query = RestQuery::table("bookmarks").on_conflict("user_id,message_id");
+        let response = self
+            .shared
+            .extras()?
+            .post(&query)
+            .header("Content-Type", "application/json")
+            .header("Prefer", "resolution=merge-duplicates,return=minimal")
+            .json(&json!({
+                "user_id": user_id,
+                "message_id": message_id,
+                "username": message.users.username,
+                "content": message.content,
+                "created_at": message.created_at,
+                "note": note.filter(|n| !n.trim().is_empty()),
+                "bookmarked_at": self.clock.now().to_rfc3339()
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to save bookmark: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to save bookmark: {}", error));
+        }
+        Ok(())
+    }
+
+    pub async fn remove_bookmark(&self, message_id: &str) -> Result<(), String> {
+        let Some(user_id) = self.auth().current_user_id else {
+            return Err("User not logged in".to_string());
+        };
+
+        let query = RestQuery::table("bookmarks")
+            .eq("user_id", user_id)
+            .eq("message_id", message_id);
+        let response = self
+            .shared
+            .extras()?
+            .delete(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to remove bookmark: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to remove bookmark: {}", error));
+        }
+        Ok(())
+    }
+
+    pub async fn list_bookmarks(&self) -> Result<Vec<Bookmark>, String> {
+        let Some(user_id) = self.auth().current_user_id else {
+            return Err("User not logged in".to_string());
+        };
+
+        let query = RestQuery::table("bookmarks")
+            .eq("user_id", user_id)
+            .select("message_id,username,content,created_at,note,bookmarked_at")
+            .order("bookmarked_at", Order::Desc);
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch bookmarks: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse bookmarks: {}", e))
+    }
+
+    /// Full-text search on the server. Returns `(channel, message)` pairs,
+    /// newest first.
+    pub async fn search_messages(
+        &self,
+        query: &SearchQuery,
+    ) -> Result<Vec<(String, ReceivedMessage)>, String> {
+        #[derive(Deserialize)]
+        struct Row {
+            id: String,
+            seq: i64,
+            channel: String,
+            content: String,
+            created_at: String,
+            users: User,
+        }
+
+        #[derive(Deserialize)]
+        struct User {
+            username: String,
+        }
+
+        let response = self
+            .shared
+            .extras()?
+            .get(&query.postgrest_query())
+            .send()
+            .await
+            .map_err(|e| format!("Search failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Search failed: {}", error));
+        }
+
+        let rows: Vec<Row> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse search results: {}", e))?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let msg = ReceivedMessage {
+                    id: row.id,
+                    seq: row.seq,
+                    content: row.content,
+                    username: row.users.username,
+                    created_at: row.created_at,
+                    user_id: String::new(),
+                    client_id: None,
+                };
+                (row.channel, msg)
+            })
+            .collect())
+    }
+
+    /// Sends a queued message. Retries of a message the server already has
+    /// are dropped there by `client_id`.
+    pub async fn send_message(&self, entry: &OutboxEntry) -> Result<(), String> {
+        self.shared
+            .backend
+            .send_message(entry.client_id, entry.user_id, &entry.content)
+            .await
+    }
+
+    /// Tries every queued message whose backoff has run out and reports how
+    /// each one went.
+    pub async fn flush_outbox(&mut self) -> Vec<UiEvent> {
+        let mut events = Vec::new();
+        let auth = self.auth().clone();
+        let Some(user_id) = auth.current_user_id.filter(|_| !auth.is_banned) else {
+            return events;
+        };
+
+        let due = self.outbox.due(user_id, self.clock.now());
+        if due.is_empty() {
+            return events;
+        }
+
+        for entry in due {
+            match self.send_message(&entry).await {
+                Ok(()) => {
+                    self.outbox.delivered(entry.client_id);
+                    events.push(UiEvent::MessageDelivered {
+                        client_id: entry.client_id,
+                    });
+                    let unlocked = self
+                        .record_achievement_event(AchievementEvent::MessageSent)
+                        .await;
+                    events.extend(achievement_events(&unlocked));
+                    self.earn_coins(economy::MESSAGE_REWARD).await;
+                    events.push(UiEvent::wallet(&self.wallet));
+                }
+                Err(e) => {
+                    eprintln!("Failed to send message: {}", e);
+                    if self
+                        .outbox
+                        .record_failure(entry.client_id, self.clock.now())
+                        == Some(OutboxStatus::Failed)
+                    {
+                        events.push(UiEvent::MessageFailed {
+                            client_id: entry.client_id,
+                        });
+                    }
+                }
+            }
+        }
+
+        if let Err(e) = self.outbox.save() {
+            eprintln!("{}", e);
+        }
+        events
+    }
+
+    /// The current user's unsent messages, for showing after login.
+    pub fn outbox_events(&self) -> Vec<UiEvent> {
+        let auth = self.auth();
+        let (Some(user_id), Some(username)) = (auth.current_user_id, &auth.current_username) else {
+            return Vec::new();
+        };
+        self.outbox
+            .for_user(user_id)
+            .map(|entry| UiEvent::outgoing(entry, username))
+            .collect()
+    }
+
+    pub async fn check_random_ban(&mut self) -> Result<BanRoll, String> {
+        let auth = self.auth().clone();
+        if auth.is_banned || !auth.logged_in {
+            return Ok(BanRoll::Spared);
+        }
+
+        // The server rolls for everyone; all that's left is to notice.
+        if self.shared.backend.rolls_bans() {
+            let Some(user_id) = auth.current_user_id else {
+                return Err("User not logged in".to_string());
+            };
+            return match self
+                .shared
+                .backend
+                .current_ban(user_id, self.clock.now())
+                .await?
+            {
+                Some(ban) => {
+                    self.shared.set_banned(&ban.reason, ban.expires_at);
+                    Ok(BanRoll::Banned)
+                }
+                None => Ok(BanRoll::Spared),
+            };
+        }
+
+        let username = auth.current_username.unwrap_or_default();
+        let now = self.clock.now();
+        let chance = self.ban_policy.chance_for(&username, now);
+
+        if self.rng.gen_range(0.0..1.0) < chance {
+            let Some(user_id) = auth.current_user_id else {
+                return Err("User not logged in".to_string());
+            };
+            let reason = BAN_REASONS[self.rng.gen_range(0..BAN_REASONS.len())];
+            let mut duration_secs = self.rng.gen_range(10..30);
+
+            let intercept = self.wallet.intercept_ban(now);
+            let redirected = match intercept {
+                BanIntercept::Blocked => return Ok(BanRoll::Shielded),
+                BanIntercept::Redirected => Some(self.redirect_ban(reason, duration_secs).await),
+                BanIntercept::Halved => {
+                    duration_secs /= 2;
+                    None
+                }
+                BanIntercept::None => None,
+            };
+            // Only now: the database uses up a redirect as it hands the ban on.
+            if matches!(intercept, BanIntercept::Redirected | BanIntercept::Halved) {
+                if let Err(e) = self.save_effects().await {
+                    eprintln!("{}", e);
+                }
+            }
+            match redirected {
+                Some(Ok(username)) => return Ok(BanRoll::Redirected { username }),
+                Some(Err(e)) => eprintln!("Failed to redirect ban, taking it instead: {}", e),
+                None => {}
+            }
+
+            let expires_at = now + chrono::Duration::seconds(duration_secs);
+            self.create_ban(user_id, reason, expires_at).await?;
+
+            self.shared.set_banned(reason, expires_at);
+            Ok(BanRoll::Banned)
+        } else {
+            Ok(BanRoll::Spared)
+        }
+    }
+
+    /// Reloads scheduled ban events from the server at most once a minute and
+    /// merges them with this hour's random events. Backends without
+    /// scheduled events still get the random ones.
+    pub async fn refresh_ban_events(&mut self) -> Result<(), String> {
+        let now = self.clock.now();
+        if self
+            .last_event_refresh
+            .is_some_and(|t| now - t < chrono::Duration::seconds(60))
+        {
+            return Ok(());
+        }
+        self.last_event_refresh = Some(now);
+
+        let Ok(api) = self.shared.extras() else {
+            self.ban_policy.set_events(Vec::new(), now);
+            return Ok(());
+        };
+        let query = RestQuery::table("ban_events")
+            .gt("ends_at", now.to_rfc3339())
+            .select("id,kind,chance,prefix,starts_at,ends_at");
+        let response = api.get(&query).send().await;
+
+        // Random events don't need the server, so keep them going offline.
+        let scheduled = match response {
+            Ok(response) if response.status().is_success() => response
+                .json::<Vec<BanEvent>>()
+                .await
+                .map_err(|e| format!("Failed to parse ban events: {}", e))?,
+            Ok(response) => {
+                let error = response.text().await.map_err(|e| e.to_string())?;
+                self.ban_policy.set_events(Vec::new(), now);
+                return Err(format!("API error: {}", error));
+            }
+            Err(e) => {
+                self.ban_policy.set_events(Vec::new(), now);
+                return Err(format!("Failed to fetch ban events: {}", e));
+            }
+        };
+
+        self.ban_policy.set_events(scheduled, now);
+        Ok(())
+    }
+
+    /// Announcements for events that are about to start or just started,
+    /// each returned only once.
+    pub fn take_event_announcements(&mut self) -> Vec<String> {
+        let now = self.clock.now();
+        let mut announcements = Vec::new();
+
+        for event in self.ban_policy.upcoming(now) {
+            if self
+                .announced_events
+                .insert(format!("upcoming:{}", event.id))
+            {
+                announcements.push(format!(
+                    "⏰ Starting in {}s: {} for {}s",
+                    (event.starts_at - now).num_seconds(),
+                    event.title(),
+                    (event.ends_at - event.starts_at).num_seconds()
+                ));
+            }
+        }
+        for event in self.ban_policy.active(now) {
+            if self.announced_events.insert(format!("active:{}", event.id)) {
+                announcements.push(format!(
+                    "{} is happening now! ({}s left)",
+                    event.title(),
+                    (event.ends_at - now).num_seconds()
+                ));
+            }
+        }
+        announcements
+    }
+
+    pub async fn create_ban(
+        &self,
+        user_id: Uuid,
+        reason: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        self.shared
+            .backend
+            .create_ban(user_id, reason, expires_at)
+            .await
+    }
+
+    /// Bans a random other user in place of the current one and returns
+    /// their username. The database picks them and uses up the redirect.
+    pub async fn redirect_ban(&self, reason: &str, duration_secs: i64) -> Result<String, String> {
+        let expires_at = self.clock.now() + chrono::Duration::seconds(duration_secs);
+        let response = self
+            .shared
+            .extras()?
+            .rpc("gooncord_redirect_ban")
+            .json(&json!({
+                "p_reason": reason,
+                "p_expires_at": expires_at.to_rfc3339()
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to redirect ban: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse redirect: {}", e))
+    }
+
+    pub async fn load_wallet(&mut self) -> Result<(), String> {
+        let Some(user_id) = self.auth().current_user_id else {
+            return Err("User not logged in".to_string());
+        };
+
+        #[derive(Deserialize)]
+        struct WalletRow {
+            balance: i64,
+        }
+
+        #[derive(Deserialize)]
+        struct InventoryRow {
+            item: String,
+            quantity: u32,
+        }
+
+        #[derive(Deserialize)]
+        struct EffectRow {
+            item: String,
+            expires_at: Option<DateTime<Utc>>,
+        }
+
+        let query = RestQuery::table("wallets")
+            .eq("user_id", user_id)
+            .select("balance");
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch wallet: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        let rows: Vec<WalletRow> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse wallet: {}", e))?;
+
+        let query = RestQuery::table("inventory")
+            .eq("user_id", user_id)
+            .select("item,quantity");
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch inventory: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        let inventory: Vec<InventoryRow> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse inventory: {}", e))?;
+
+        let query = RestQuery::table("active_effects")
+            .eq("user_id", user_id)
+            .select("item,expires_at");
+        let response = self
+            .shared
+            .extras()?
+            .get(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch active effects: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        let effects: Vec<EffectRow> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse active effects: {}", e))?;
+
+        let now = self.clock.now();
+        self.wallet = Wallet {
+            balance: rows.first().map_or(0, |w| w.balance),
+            inventory: inventory
+                .into_iter()
+                .filter_map(|row| Some((Item::from_key(&row.item)?, row.quantity)))
+                .collect(),
+            effects: effects
+                .into_iter()
+                .filter(|row| row.expires_at.is_none_or(|expires_at| expires_at > now))
+                .filter_map(|row| {
+                    Some(ActiveEffect {
+                        item: Item::from_key(&row.item)?,
+                        expires_at: row.expires_at,
+                    })
+                })
+                .collect(),
+        };
+        Ok(())
+    }
+
+    pub async fn save_wallet(&self) -> Result<(), String> {
+        let user_id = self.auth().current_user_id;
+        let response = self
+            .shared
+            .extras()?
+            .post(&RestQuery::table("wallets"))
+            .header("Content-Type", "application/json")
+            .header("Prefer", "resolution=merge-duplicates,return=minimal")
+            .json(&json!({
+                "user_id": user_id,
+                "balance": self.wallet.balance
+            }))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to save wallet: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to save wallet: {}", error));
+        }
+        Ok(())
+    }
+
+    pub async fn save_inventory(&self) -> Result<(), String> {
+        let rows: Vec<serde_json::Value> = Item::ALL
+            .iter()
+            .map(|item| {
+                json!({
+                    "user_id": self.auth().current_user_id,
+                    "item": item.key(),
+                    "quantity": self.wallet.inventory.get(item).copied().unwrap_or(0)
+                })
+            })
+            .collect();
+
+        let query = RestQuery::table("inventory").on_conflict("user_id,item");
+        let response = self
+            .shared
+            .extras()?
+            .post(&query)
+            .header("Content-Type", "application/json")
+            .header("Prefer", "resolution=merge-duplicates,return=minimal")
+            .json(&rows)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to save inventory: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to save inventory: {}", error));
+        }
+        Ok(())
+    }
+
+    /// Replaces the stored active effects with the wallet's, after one is
+    /// switched on or used up.
+    pub async fn save_effects(&self) -> Result<(), String> {
+        let Some(user_id) = self.auth().current_user_id else {
+            return Err("User not logged in".to_string());
+        };
+
+        let query = RestQuery::table("active_effects").eq("user_id", user_id);
+        let response = self
+            .shared
+            .extras()?
+            .delete(&query)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to save active effects: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to save active effects: {}", error));
+        }
+        if self.wallet.effects.is_empty() {
+            return Ok(());
+        }
+
+        let rows: Vec<serde_json::Value> = self
+            .wallet
+            .effects
+            .iter()
+            .map(|effect| {
+                json!({
+                    "user_id": user_id,
+                    "item": effect.item.key(),
+                    "expires_at": effect.expires_at.map(|t| t.to_rfc3339())
+                })
+            })
+            .collect();
+        let response = self
+            .shared
+            .extras()?
+            .post(&RestQuery::table("active_effects"))
+            .header("Content-Type", "application/json")
+            .header("Prefer", "return=minimal")
+            .json(&rows)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to save active effects: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to save active effects: {}", error));
+        }
+        Ok(())
+    }
+
+    /// Coins only exist where they can be saved, so other backends earn none.
+    pub async fn earn_coins(&mut self, amount: i64) {
+        if !self.shared.backend.has_extras() {
+            return;
+        }
+        self.wallet.earn(amount);
+        if let Err(e) = self.save_wallet().await {
+            eprintln!("{}", e);
+        }
+    }
+
+    /// Buys `item`. If saving fails the wallet is left as it was.
+    pub async fn buy_item(&mut self, item: Item) -> Result<(), String> {
+        self.shared.extras()?;
+        let before = self.wallet.clone();
+        self.wallet.buy(item)?;
+        let saved = async {
+            self.save_wallet().await?;
+            self.save_inventory().await
+        }
+        .await;
+        if saved.is_err() {
+            self.wallet = before;
+        }
+        saved
+    }
+
+    /// Puts `item` into effect. If saving fails the wallet is left as it was.
+    pub async fn use_item(&mut self, item: Item) -> Result<(), String> {
+        self.shared.extras()?;
+        let before = self.wallet.clone();
+        self.wallet.use_item(item, self.clock.now())?;
+        let saved = async {
+            self.save_inventory().await?;
+            self.save_effects().await
+        }
+        .await;
+        if saved.is_err() {
+            self.wallet = before;
+        }
+        saved
+    }
+
+    pub fn wallet(&self) -> &Wallet {
+        &self.wallet
+    }
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn achievement_events<'a>(unlocked: &'a [&Achievement]) -> impl Iterator<Item = UiEvent> + 'a {
+    unlocked.iter().map(|a| UiEvent::achievement(a))
+}
+
+/// Announces a ban that was just applied and counts it towards achievements.
+async fn ban_started_events(state: &mut AppState) -> Vec<UiEvent> {
+    let mut events = Vec::new();
+    let reason = state.auth().ban_reason.clone();
+    if let (Some(reason), Some(remaining)) = (reason, state.ban_remaining_secs()) {
+        events.push(UiEvent::BanStarted {
+            reason: reason.clone(),
+            seconds: remaining,
+        });
+
+        let mut unlocked = state
+            .record_achievement_event(AchievementEvent::BanReceived)
+            .await;
+        unlocked.extend(
+            state
+                .record_achievement_event(AchievementEvent::ReasonCollected(&reason))
+                .await,
+        );
+        events.extend(achievement_events(&unlocked));
+    }
+    events
+}
+
+async fn webhook_list(state: &AppState) -> Option<UiEvent> {
+    match state.list_webhooks().await {
+        Ok((incoming, outgoing)) => Some(UiEvent::Webhooks { incoming, outgoing }),
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    }
+}
+
+/// Carries out what a slash command decided, other than sending a message.
+async fn apply_command(state: &mut AppState, outcome: Outcome) -> Vec<UiEvent> {
+    match outcome {
+        Outcome::Send(_) => Vec::new(),
+        Outcome::Reply(text) => vec![UiEvent::system(text)],
+        Outcome::Rename(username) => match state.rename(&username).await {
+            Ok(()) => vec![UiEvent::system(format!("You are now {}.", username))],
+            Err(e) => vec![UiEvent::system(e)],
+        },
+        Outcome::BanSelf { reason } => match state.ban_self(&reason).await {
+            Ok(()) => ban_started_events(state).await,
+            Err(e) => vec![UiEvent::system(format!("Couldn't even ban you: {}", e))],
+        },
+        Outcome::CreateBot { name } => match state.create_bot(&name).await {
+            Ok(token) => vec![UiEvent::system(format!(
+                "🤖 Created bot {}. Its token, shown only this once:\n{}",
+                name, token
+            ))],
+            Err(e) => vec![UiEvent::system(e)],
+        },
+        Outcome::RevokeBot { name } => match state.revoke_bot(&name).await {
+            Ok(()) => vec![UiEvent::system(format!("🤖 Revoked {}'s tokens.", name))],
+            Err(e) => vec![UiEvent::system(e)],
+        },
+    }
+}
+
+/// Meant to run once before anything else: checks that the database is one
+/// this build can use, and says so if not.
+pub async fn startup(state: &AppState) -> Vec<UiEvent> {
+    match state.shared.backend.check_schema().await {
+        Ok(()) => Vec::new(),
+        Err(e) => vec![UiEvent::system(e)],
+    }
+}
+
+/// Meant to run once a second: expires bans, announces ban events, rolls
+/// for random bans and drives the countdown.
+pub async fn tick(state: &mut AppState) -> Vec<UiEvent> {
+    let mut events = Vec::new();
+
+    if state.auth().is_banned && state.ban_remaining_secs().is_none() {
+        state.lift_ban();
+        events.push(UiEvent::BanCleared);
+    }
+
+    if let Err(e) = state.refresh_ban_events().await {
+        eprintln!("Failed to refresh ban events: {}", e);
+    }
+    for text in state.take_event_announcements() {
+        events.push(UiEvent::EventAnnouncement { text });
+    }
+
+    if let Some(remaining) = state.ban_remaining_secs() {
+        events.push(UiEvent::BanTick { seconds: remaining });
+        return events;
+    }
+
+    events.extend(state.flush_outbox().await);
+
+    let now = state.clock.now();
+    if !state.auth().logged_in || now - state.last_ban_check < chrono::Duration::seconds(5) {
+        return events;
+    }
+    state.last_ban_check = now;
+
+    match state.check_random_ban().await {
+        Ok(BanRoll::Banned) => events.extend(ban_started_events(state).await),
+        Ok(roll) => {
+            match roll {
+                BanRoll::Shielded => {
+                    events.push(UiEvent::system("🛡️ Your immunity shield blocked a ban!"))
+                }
+                BanRoll::Redirected { username } => events.push(UiEvent::system(format!(
+                    "↪️ Your ban was redirected to {}!",
+                    username
+                ))),
+                BanRoll::Spared | BanRoll::Banned => {}
+            }
+
+            state.earn_coins(economy::SURVIVAL_REWARD).await;
+            events.push(UiEvent::wallet(&state.wallet));
+
+            let minutes = (now - state.ban_free_since).num_minutes().max(0) as u64;
+            let unlocked = state
+                .record_achievement_event(AchievementEvent::Survived { minutes })
+                .await;
+            events.extend(achievement_events(&unlocked));
+        }
+        Err(e) => eprintln!("Ban roll failed: {}", e),
+    }
+    events
+}
+
+/// Does what the UI asked for and says what to show.
+pub async fn handle_web_message(state: &mut AppState, message: WebMessage) -> Vec<UiEvent> {
+    if let Some(remaining) = state.ban_remaining_secs() {
+        if !state.spectator_policy.allows(message.action()) {
+            return vec![UiEvent::SpectatorOnly { seconds: remaining }];
+        }
+    }
+
+    let mut events = Vec::new();
+    match message {
+        WebMessage::Message { content } => {
+            if !state.auth().logged_in {
+                events.push(UiEvent::system("Please login first!"));
+                return events;
+            }
+
+            let content = match commands::parse(&content) {
+                Input::Text(text) => text.to_string(),
+                Input::Command { name, args } => match state.run_command(name, args) {
+                    Ok(Outcome::Send(text)) => text,
+                    Ok(outcome) => {
+                        events.extend(apply_command(state, outcome).await);
+                        return events;
+                    }
+                    Err(e) => {
+                        events.push(UiEvent::system(e));
+                        return events;
+                    }
+                },
+            };
+
+            let Some(user_id) = state.auth().current_user_id else {
+                return events;
+            };
+            let entry = state.outbox.enqueue(user_id, &content, state.clock.now());
+            if let Err(e) = state.outbox.save() {
+                eprintln!("{}", e);
+            }
+            let username = state.auth().current_username.clone().unwrap_or_default();
+            events.push(UiEvent::outgoing(&entry, &username));
+            events.extend(state.flush_outbox().await);
+        }
+        WebMessage::RetryMessage { client_id } => {
+            let username = state.auth().current_username.clone().unwrap_or_default();
+            let Some(entry) = state.outbox.retry(client_id, state.clock.now()) else {
+                return events;
+            };
+            events.push(UiEvent::outgoing(entry, &username));
+            events.extend(state.flush_outbox().await);
+        }
+        WebMessage::Register { username, password } => {
+            match state.register_user(&username, &password).await {
+                Ok(msg) => {
+                    events.push(UiEvent::HideAuthForms);
+                    events.push(UiEvent::system(msg));
+                    events.push(UiEvent::EnableInput);
+                    events.push(UiEvent::badges(&state.achievements));
+                    events.push(UiEvent::wallet(&state.wallet));
+                    events.push(UiEvent::Permissions {
+                        is_moderator: state.auth().is_moderator,
+                    });
+                    events.extend(state.outbox_events());
+                    events.push(state.unread_marker().await);
+                }
+                Err(e) => events.push(UiEvent::system(format!("Registration failed: {}", e))),
+            }
+        }
+        WebMessage::Login { username, password } => {
+            match state.login_user(&username, &password).await {
+                Ok(msg) => {
+                    events.push(UiEvent::HideAuthForms);
+                    events.push(UiEvent::system(msg));
+                    if let Some(remaining) = state.ban_remaining_secs() {
+                        events.push(UiEvent::BanStarted {
+                            reason: state
+                                .auth()
+                                .ban_reason
+                                .clone()
+                                .unwrap_or("no reason".to_string()),
+                            seconds: remaining,
+                        });
+                    } else {
+                        events.push(UiEvent::EnableInput);
+                    }
+                    events.push(UiEvent::badges(&state.achievements));
+                    events.push(UiEvent::wallet(&state.wallet));
+                    events.push(UiEvent::Permissions {
+                        is_moderator: state.auth().is_moderator,
+                    });
+                    events.extend(state.outbox_events());
+                    events.push(state.unread_marker().await);
+                }
+                Err(e) => events.push(UiEvent::system(format!("Login failed: {}", e))),
+            }
+        }
+        WebMessage::CheckAuth => {
+            if state.auth().logged_in {
+                events.push(UiEvent::HideAuthForms);
+                if !state.auth().is_banned {
+                    events.push(UiEvent::EnableInput);
+                }
+            } else {
+                events.push(UiEvent::ShowAuthForms);
+            }
+        }
+        WebMessage::Logout => {
+            if state.auth().logged_in {
+                state.logout().await;
+                events.push(UiEvent::BanCleared);
+                events.push(UiEvent::ShowAuthForms);
+                events.push(UiEvent::badges(&state.achievements));
+                events.push(UiEvent::Permissions {
+                    is_moderator: false,
+                });
+                events.push(UiEvent::UnreadMarker {
+                    last_read_seq: None,
+                    unread: 0,
+                });
+                events.push(UiEvent::system("You have been logged out."));
+            }
+        }
+        WebMessage::RequestMessages => {
+            // Whatever was cached last time, until polling catches up.
+            let owner = state.shared.cache_owner();
+            let cache = state.shared.cache();
+            match cache.recent_messages(&owner, DEFAULT_CHANNEL, 50) {
+                Ok(messages) if !messages.is_empty() => {
+                    if let Some(channel) = cache.channel(&owner, DEFAULT_CHANNEL) {
+                        events.push(UiEvent::system(format!(
+                            "Showing #{} as of {} while we reconnect...",
+                            channel.name, channel.synced_at
+                        )));
+                    }
+                    events.extend(messages.iter().map(UiEvent::message));
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        WebMessage::Wallet => {
+            if state.auth().logged_in {
+                events.push(UiEvent::wallet(&state.wallet));
+            }
+        }
+        WebMessage::BuyItem { item } => {
+            if !state.auth().logged_in {
+                events.push(UiEvent::system("Please login first!"));
+                return events;
+            }
+            let text = match state.buy_item(item).await {
+                Ok(()) => format!("Bought a {}.", item.name()),
+                Err(e) => format!("Purchase failed: {}", e),
+            };
+            events.push(UiEvent::system(text));
+            events.push(UiEvent::wallet(&state.wallet));
+        }
+        WebMessage::UseItem { item } => {
+            if !state.auth().logged_in {
+                events.push(UiEvent::system("Please login first!"));
+                return events;
+            }
+            let text = match state.use_item(item).await {
+                Ok(()) => format!("{} activated!", item.name()),
+                Err(e) => e,
+            };
+            events.push(UiEvent::system(text));
+            events.push(UiEvent::wallet(&state.wallet));
+        }
+        WebMessage::React { message_id, emoji } => {
+            if !state.auth().logged_in {
+                events.push(UiEvent::system("Please login first!"));
+                return events;
+            }
+            match state.react_to_message(&message_id, &emoji).await {
+                Ok(()) => events.push(UiEvent::ReactionAdded { message_id, emoji }),
+                Err(e) => events.push(UiEvent::system(e)),
+            }
+        }
+        WebMessage::ViewProfile { username } => {
+            let owner = state.shared.cache_owner();
+            match state.get_profile(&username).await {
+                Ok(profile) => {
+                    if let Err(e) = state.shared.cache().store_profile(&owner, &profile) {
+                        eprintln!("{}", e);
+                    }
+                    events.push(UiEvent::Profile { profile });
+                }
+                Err(e) => match state.shared.cache().profile(&owner, &username) {
+                    Some(profile) => {
+                        events.push(UiEvent::system(format!(
+                            "Couldn't refresh {}'s profile, showing a saved copy.",
+                            username
+                        )));
+                        events.push(UiEvent::Profile { profile });
+                    }
+                    None => events.push(UiEvent::system(format!("Couldn't load profile: {}", e))),
+                },
+            }
+        }
+        WebMessage::Search(query) => {
+            if query.is_empty() {
+                events.push(UiEvent::system("Type something to search for."));
+                return events;
+            }
+            let results = match state.search_messages(&query).await {
+                Ok(rows) => SearchResults::page(query, rows, false),
+                Err(e) => {
+                    eprintln!("{}, searching the local cache instead", e);
+                    let owner = state.shared.cache_owner();
+                    match state.shared.cache().search(&owner, &query) {
+                        Ok(rows) => SearchResults::page(query, rows, true),
+                        Err(e) => {
+                            events.push(UiEvent::system(format!("Search failed: {}", e)));
+                            return events;
+                        }
+                    }
+                }
+            };
+            events.push(UiEvent::SearchResults { results });
+        }
+        WebMessage::PinMessage { message_id } => {
+            if let Err(e) = state.pin_message(&message_id).await {
+                events.push(UiEvent::system(e));
+                return events;
+            }
+            events.push(UiEvent::system("📌 Message pinned."));
+            match state.list_pins().await {
+                Ok(pins) => events.push(UiEvent::Pins { pins }),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        WebMessage::UnpinMessage { message_id } => {
+            if let Err(e) = state.unpin_message(&message_id).await {
+                events.push(UiEvent::system(e));
+                return events;
+            }
+            match state.list_pins().await {
+                Ok(pins) => events.push(UiEvent::Pins { pins }),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        WebMessage::ListPins => match state.list_pins().await {
+            Ok(pins) => events.push(UiEvent::Pins { pins }),
+            Err(e) => events.push(UiEvent::system(format!("Couldn't load pins: {}", e))),
+        },
+        WebMessage::Bookmark { message_id, note } => {
+            if !state.auth().logged_in {
+                events.push(UiEvent::system("Please login first!"));
+                return events;
+            }
+            match state.add_bookmark(&message_id, note).await {
+                Ok(()) => events.push(UiEvent::system("🔖 Bookmarked.")),
+                Err(e) => events.push(UiEvent::system(e)),
+            }
+        }
+        WebMessage::RemoveBookmark { message_id } => {
+            if let Err(e) = state.remove_bookmark(&message_id).await {
+                events.push(UiEvent::system(e));
+                return events;
+            }
+            match state.list_bookmarks().await {
+                Ok(bookmarks) => events.push(UiEvent::Bookmarks { bookmarks }),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        WebMessage::MarkRead { seq } => {
+            if !state.auth().logged_in {
+                return events;
+            }
+            match state.mark_read(seq).await {
+                Ok(true) => events.push(UiEvent::UnreadMarker {
+                    last_read_seq: Some(seq),
+                    unread: 0,
+                }),
+                Ok(false) => {}
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        WebMessage::SeenBy { seq } => {
+            let me = state.auth().current_username.clone().unwrap_or_default();
+            match state.read_cursors().await {
+                Ok(cursors) => match receipts::seen_by(&cursors, seq, &me) {
+                    Some(usernames) => events.push(UiEvent::SeenBy { seq, usernames }),
+                    None => events.push(UiEvent::system(format!(
+                        "Seen-by is only shown in channels with up to {} readers.",
+                        receipts::SEEN_BY_MAX_READERS
+                    ))),
+                },
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        WebMessage::Autocomplete { prefix } => {
+            events.push(UiEvent::CommandSuggestions {
+                suggestions: state.commands.suggest(&prefix),
+            });
+        }
+        WebMessage::CreateIncomingWebhook { name } => {
+            match state.create_incoming_webhook(name.trim()).await {
+                Ok(secret) => events.push(UiEvent::system(format!(
+                    "🪝 Incoming webhook {} is ready. Integrations POST \
+                     {{\"content\": \"...\"}} to /hooks/{} on your webhook relay. \
+                     Keep the URL secret; it won't be shown again.",
+                    name, secret
+                ))),
+                Err(e) => events.push(UiEvent::system(e)),
+            }
+            events.extend(webhook_list(state).await);
+        }
+        WebMessage::CreateOutgoingWebhook {
+            url,
+            events: kinds,
+            relay,
+        } => {
+            match state
+                .create_outgoing_webhook(url.trim(), &kinds, relay.trim())
+                .await
+            {
+                Ok(secret) => events.push(UiEvent::system(format!(
+                    "🪝 Sending {} events to {}. Payloads are signed with HMAC-SHA256 in the \
+                     X-Gooncord-Signature header using this secret, shown only once:\n{}",
+                    kinds.join(" and "),
+                    url,
+                    secret
+                ))),
+                Err(e) => events.push(UiEvent::system(e)),
+            }
+            events.extend(webhook_list(state).await);
+        }
+        WebMessage::ListWebhooks => events.extend(webhook_list(state).await),
+        WebMessage::DeleteWebhook { kind, id } => {
+            if let Err(e) = state.delete_webhook(kind, id).await {
+                events.push(UiEvent::system(e));
+            }
+            events.extend(webhook_list(state).await);
+        }
+        WebMessage::ListBookmarks => {
+            if !state.auth().logged_in {
+                events.push(UiEvent::system("Please login first!"));
+                return events;
+            }
+            match state.list_bookmarks().await {
+                Ok(bookmarks) => events.push(UiEvent::Bookmarks { bookmarks }),
+                Err(e) => events.push(UiEvent::system(format!("Couldn't load bookmarks: {}", e))),
+            }
+        }
+    }
+    events
+}
@@ -0,0 +1,208 @@
+use base64::Engine;
+use bcrypt::{hash, verify, DEFAULT_COST};
+use chrono::Utc;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::backend::{Backend, User};
+
+/// Who is signed in, as returned by [`Backend::register`] and
+/// [`Backend::login`].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Session {
+    pub token: String,
+    pub user_id: Uuid,
+    pub username: String,
+    pub is_moderator: bool,
+}
+
+/// Shortest and longest usernames allowed.
+pub const USERNAME_LEN: std::ops::RangeInclusive<usize> = 3..=24;
+
+/// Names nobody gets to register, so nobody can pass for staff or the
+/// system. Compared ignoring case.
+pub const RESERVED_USERNAMES: &[&str] = &[
+    "admin",
+    "administrator",
+    "bot",
+    "everyone",
+    "gooncord",
+    "here",
+    "mod",
+    "moderator",
+    "null",
+    "root",
+    "server",
+    "system",
+];
+
+/// Why `username` can't be registered, if it can't: it must be
+/// [`USERNAME_LEN`] long, made of ASCII letters, digits, `_` and `-`, and
+/// not one of the [`RESERVED_USERNAMES`].
+pub fn validate_username(username: &str) -> Result<(), String> {
+    if !USERNAME_LEN.contains(&username.chars().count()) {
+        return Err(format!(
+            "Usernames are {} to {} characters long",
+            USERNAME_LEN.start(),
+            USERNAME_LEN.end()
+        ));
+    }
+    if !username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err("Usernames may only use letters, digits, _ and -".to_string());
+    }
+    if RESERVED_USERNAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(username))
+    {
+        return Err(format!("{} is reserved", username));
+    }
+    Ok(())
+}
+
+/// 32 random bytes, URL-safe base64.
+fn generate_session_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Creates an account and signs it in, using the backend's storage
+/// directly. What [`Backend::register`] does unless a backend knows better.
+pub async fn register<B: Backend + ?Sized>(
+    backend: &B,
+    username: &str,
+    password: &str,
+) -> Result<Session, String> {
+    validate_username(username)?;
+    if backend.find_user(username).await?.is_some() {
+        return Err("Username already exists".to_string());
+    }
+
+    let password = password.to_string();
+    let hashed_password = tokio::task::spawn_blocking(move || hash(password, DEFAULT_COST))
+        .await
+        .map_err(|e| format!("Password hashing failed: {}", e))?
+        .map_err(|e| format!("Password hashing failed: {}", e))?;
+
+    let user = User {
+        id: Uuid::new_v4(),
+        username: username.to_string(),
+        password_hash: hashed_password,
+        is_moderator: false,
+        created_at: Utc::now(),
+    };
+    backend.create_user(&user).await?;
+
+    create_session(backend, user).await
+}
+
+/// Checks the password and starts a session; see [`register`].
+pub async fn login<B: Backend + ?Sized>(
+    backend: &B,
+    username: &str,
+    password: &str,
+) -> Result<Session, String> {
+    let user = backend.find_user(username).await?.ok_or("User not found")?;
+
+    let password = password.to_string();
+    let stored_hash = user.password_hash.clone();
+    let valid = tokio::task::spawn_blocking(move || verify(password, &stored_hash))
+        .await
+        .map_err(|e| format!("Password verification failed: {}", e))?
+        .map_err(|e| format!("Password verification failed: {}", e))?;
+    if !valid {
+        return Err("Invalid password".to_string());
+    }
+
+    create_session(backend, user).await
+}
+
+async fn create_session<B: Backend + ?Sized>(backend: &B, user: User) -> Result<Session, String> {
+    let token = generate_session_token();
+    let expires_at = Utc::now() + chrono::Duration::days(30);
+    backend.create_session(user.id, &token, expires_at).await?;
+
+    Ok(Session {
+        token,
+        user_id: user.id,
+        username: user.username,
+        is_moderator: user.is_moderator,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bans::Ban;
+    use crate::sequencer::Query;
+    use crate::ReceivedMessage;
+    use async_trait::async_trait;
+    use chrono::DateTime;
+
+    /// A backend whose user lookups always fail.
+    struct Down;
+
+    #[async_trait]
+    impl Backend for Down {
+        async fn find_user(&self, _: &str) -> Result<Option<User>, String> {
+            Err("connection refused".to_string())
+        }
+        async fn create_user(&self, _: &User) -> Result<(), String> {
+            unreachable!()
+        }
+        async fn create_session(&self, _: Uuid, _: &str, _: DateTime<Utc>) -> Result<(), String> {
+            unreachable!()
+        }
+        async fn end_session(&self, _: &str) -> Result<(), String> {
+            unreachable!()
+        }
+        async fn find_session(&self, _: &str) -> Result<Option<Session>, String> {
+            unreachable!()
+        }
+        async fn get_messages(&self, _: Query) -> Result<Vec<ReceivedMessage>, String> {
+            unreachable!()
+        }
+        async fn send_message(&self, _: Uuid, _: Uuid, _: &str) -> Result<(), String> {
+            unreachable!()
+        }
+        async fn ban_history(&self, _: Uuid) -> Result<Vec<Ban>, String> {
+            unreachable!()
+        }
+        async fn create_ban(&self, _: Uuid, _: &str, _: DateTime<Utc>) -> Result<(), String> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn usernames_follow_the_rules() {
+        for ok in ["bob", "alice_99", "x-ray", "a".repeat(24).as_str()] {
+            assert_eq!(validate_username(ok), Ok(()), "{}", ok);
+        }
+        for bad in [
+            "",
+            "al",
+            &"a".repeat(25),
+            "a b",
+            "a&b",
+            "a,b",
+            "a.b",
+            "(ab)",
+            "zoë",
+        ] {
+            assert!(validate_username(bad).is_err(), "{}", bad);
+        }
+        assert!(validate_username("Admin").unwrap_err().contains("reserved"));
+        assert!(validate_username("SYSTEM").is_err());
+    }
+
+    #[tokio::test]
+    async fn lookup_failures_are_not_missing_users() {
+        let refused = Some("connection refused".to_string());
+        assert_eq!(login(&Down, "bob", "hunter22").await.err(), refused);
+        assert_eq!(register(&Down, "bob", "hunter22").await.err(), refused);
+    }
+}
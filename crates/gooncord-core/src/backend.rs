@@ -0,0 +1,156 @@
+//! Where accounts, sessions, messages and bans are stored.
+//!
+//! [`Backend`] is the seam: [`ApiClient`] talks to Supabase over PostgREST,
+//! [`ServerBackend`] to a self-hosted `gooncord-server`, and
+//! [`SqliteBackend`] keeps everything in a local file for offline
+//! development, demos and tests. Everything else (achievements, wallets,
+//! pins, ...) still goes straight to Supabase, so it's only there when
+//! Supabase is the backend; see [`Backend::has_extras`].
+
+mod server;
+mod sqlite;
+mod supabase;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::auth::{self, Session};
+use crate::bans::Ban;
+use crate::sequencer::Query;
+use crate::{ApiClient, ReceivedMessage};
+
+pub use server::ServerBackend;
+pub use sqlite::SqliteBackend;
+
+/// The channel keeps this many unpinned messages; sending more deletes the
+/// oldest.
+pub const MAX_MESSAGES: usize = 20;
+
+/// A row of the `users` table.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct User {
+    pub id: Uuid,
+    pub username: String,
+    pub password_hash: String,
+    #[serde(default)]
+    pub is_moderator: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait Backend: Send + Sync {
+    async fn register(&self, username: &str, password: &str) -> Result<Session, String> {
+        auth::register(self, username, password).await
+    }
+
+    async fn login(&self, username: &str, password: &str) -> Result<Session, String> {
+        auth::login(self, username, password).await
+    }
+
+    /// Carries on with a session from an earlier run, for backends that
+    /// authenticate requests with it.
+    fn resume(&self, _session: &Session) {}
+
+    /// Whether the backend rolls for random bans itself, so clients
+    /// shouldn't.
+    fn rolls_bans(&self) -> bool {
+        false
+    }
+
+    /// Whether the features beyond this trait, from achievements to
+    /// webhooks, are stored alongside. Only Supabase has them.
+    fn has_extras(&self) -> bool {
+        false
+    }
+
+    /// Fails if the database schema is too old for this build; see
+    /// [`migrations`](crate::migrations). Backends that set up their own
+    /// schema have nothing to check.
+    async fn check_schema(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn find_user(&self, username: &str) -> Result<Option<User>, String>;
+
+    async fn create_user(&self, user: &User) -> Result<(), String>;
+
+    async fn create_session(
+        &self,
+        user_id: Uuid,
+        token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), String>;
+
+    async fn end_session(&self, token: &str) -> Result<(), String>;
+
+    /// The unexpired session `token` belongs to.
+    async fn find_session(&self, token: &str) -> Result<Option<Session>, String>;
+
+    /// Messages in sequence order, as the
+    /// [`Sequencer`](crate::sequencer::Sequencer) asks for them.
+    async fn get_messages(&self, query: Query) -> Result<Vec<ReceivedMessage>, String>;
+
+    /// Posts a message, first making room if the channel is at
+    /// [`MAX_MESSAGES`]. Resending the same `client_id` is a no-op.
+    async fn send_message(
+        &self,
+        client_id: Uuid,
+        user_id: Uuid,
+        content: &str,
+    ) -> Result<(), String>;
+
+    /// Every ban `user_id` ever got, newest first.
+    async fn ban_history(&self, user_id: Uuid) -> Result<Vec<Ban>, String>;
+
+    async fn create_ban(
+        &self,
+        user_id: Uuid,
+        reason: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), String>;
+
+    /// The ban keeping `user_id` out at `now`, if any.
+    async fn current_ban(&self, user_id: Uuid, now: DateTime<Utc>) -> Result<Option<Ban>, String> {
+        Ok(self
+            .ban_history(user_id)
+            .await?
+            .into_iter()
+            .find(|ban| ban.in_effect(now)))
+    }
+}
+
+/// Picks the backend named by `GOONCORD_BACKEND`: `supabase` (the default)
+/// connects with `supabase()`, `server` to the `gooncord-server` at
+/// `GOONCORD_SERVER_URL`, and `sqlite` opens `GOONCORD_SQLITE_PATH`, or
+/// `local.sqlite3` in Gooncord's data directory.
+pub fn from_env(
+    supabase: impl FnOnce() -> Result<ApiClient, String>,
+) -> Result<Arc<dyn Backend>, String> {
+    match env::var("GOONCORD_BACKEND").as_deref() {
+        Ok("sqlite") => {
+            let path = match env::var("GOONCORD_SQLITE_PATH") {
+                Ok(path) => PathBuf::from(path),
+                Err(_) => dirs::data_local_dir()
+                    .ok_or("No data directory; set GOONCORD_SQLITE_PATH")?
+                    .join("gooncord")
+                    .join("local.sqlite3"),
+            };
+            Ok(Arc::new(SqliteBackend::open(&path)?))
+        }
+        Ok("server") => {
+            let url = env::var("GOONCORD_SERVER_URL")
+                .map_err(|_| "GOONCORD_SERVER_URL is not set".to_string())?;
+            Ok(Arc::new(ServerBackend::new(url)))
+        }
+        Ok("supabase") | Err(_) => Ok(Arc::new(supabase()?)),
+        Ok(other) => Err(format!(
+            "Unknown GOONCORD_BACKEND {:?}; use supabase, server or sqlite",
+            other
+        )),
+    }
+}
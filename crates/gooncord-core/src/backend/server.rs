@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+use super::{Backend, User};
+use crate::auth::Session;
+use crate::bans::Ban;
+use crate::sequencer::Query;
+use crate::ReceivedMessage;
+
+/// A self-hosted `gooncord-server`. Accounts and sessions are the server's
+/// business, so only [`Backend::register`] and [`Backend::login`] reach
+/// them; requests on the user's behalf carry the session token.
+pub struct ServerBackend {
+    url: String,
+    http: Client,
+    token: RwLock<Option<String>>,
+}
+
+fn unsupported<T>(what: &str) -> Result<T, String> {
+    Err(format!("{} is up to the Gooncord server", what))
+}
+
+impl ServerBackend {
+    pub fn new(url: String) -> Self {
+        ServerBackend {
+            url: url.trim_end_matches('/').to_string(),
+            http: Client::new(),
+            token: RwLock::new(None),
+        }
+    }
+
+    fn api_url(&self, path: &str) -> String {
+        format!("{}/api/{}", self.url, path)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match self
+            .token
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_deref()
+        {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder, what: &str) -> Result<Response, String> {
+        let response = self
+            .authorize(request)
+            .send()
+            .await
+            .map_err(|e| format!("{}: {}", what, e))?;
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("{}: {}", what, error));
+        }
+        Ok(response)
+    }
+
+    async fn fetch<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+        what: &str,
+    ) -> Result<T, String> {
+        self.send(request, what)
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("{}: {}", what, e))
+    }
+
+    async fn start_session(
+        &self,
+        path: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<Session, String> {
+        let request = self
+            .http
+            .post(self.api_url(path))
+            .json(&json!({ "username": username, "password": password }));
+        let session: Session = self.fetch(request, "Failed to sign in").await?;
+        self.resume(&session);
+        Ok(session)
+    }
+}
+
+#[async_trait]
+impl Backend for ServerBackend {
+    async fn register(&self, username: &str, password: &str) -> Result<Session, String> {
+        self.start_session("register", username, password).await
+    }
+
+    async fn login(&self, username: &str, password: &str) -> Result<Session, String> {
+        self.start_session("login", username, password).await
+    }
+
+    fn resume(&self, session: &Session) {
+        *self.token.write().unwrap_or_else(|e| e.into_inner()) = Some(session.token.clone());
+    }
+
+    fn rolls_bans(&self) -> bool {
+        true
+    }
+
+    async fn find_user(&self, _username: &str) -> Result<Option<User>, String> {
+        unsupported("Looking up password hashes")
+    }
+
+    async fn create_user(&self, _user: &User) -> Result<(), String> {
+        unsupported("Creating users")
+    }
+
+    async fn create_session(
+        &self,
+        _user_id: Uuid,
+        _token: &str,
+        _expires_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        unsupported("Creating sessions")
+    }
+
+    async fn end_session(&self, token: &str) -> Result<(), String> {
+        let request = self.http.post(self.api_url("logout")).bearer_auth(token);
+        request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to end session: {}", e))?;
+        *self.token.write().unwrap_or_else(|e| e.into_inner()) = None;
+        Ok(())
+    }
+
+    async fn find_session(&self, _token: &str) -> Result<Option<Session>, String> {
+        unsupported("Checking sessions")
+    }
+
+    async fn get_messages(&self, query: Query) -> Result<Vec<ReceivedMessage>, String> {
+        let params = match query {
+            Query::Latest { limit } => vec![("latest", limit as i64)],
+            Query::After { seq, limit } => vec![("after", seq), ("limit", limit as i64)],
+            Query::Between { after, before } => vec![("after", after), ("before", before)],
+        };
+        let request = self.http.get(self.api_url("messages")).query(&params);
+        self.fetch(request, "Failed to fetch messages").await
+    }
+
+    /// The server posts as whoever the session belongs to; `user_id` is
+    /// only checked against that.
+    async fn send_message(
+        &self,
+        client_id: Uuid,
+        user_id: Uuid,
+        content: &str,
+    ) -> Result<(), String> {
+        let request = self.http.post(self.api_url("messages")).json(&json!({
+            "client_id": client_id,
+            "user_id": user_id,
+            "content": content
+        }));
+        self.send(request, "Failed to send message").await?;
+        Ok(())
+    }
+
+    async fn ban_history(&self, user_id: Uuid) -> Result<Vec<Ban>, String> {
+        let request = self
+            .http
+            .get(self.api_url(&format!("users/{}/bans", user_id)));
+        self.fetch(request, "Failed to fetch ban history").await
+    }
+
+    async fn create_ban(
+        &self,
+        user_id: Uuid,
+        reason: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        let request = self.http.post(self.api_url("bans")).json(&json!({
+            "user_id": user_id,
+            "reason": reason,
+            "expires_at": expires_at
+        }));
+        self.send(request, "Failed to create ban").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,429 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use uuid::Uuid;
+
+use super::{Backend, User, MAX_MESSAGES};
+use crate::auth::Session;
+use crate::bans::Ban;
+use crate::sequencer::Query;
+use crate::{ReceivedMessage, DEFAULT_CHANNEL};
+
+/// Users, sessions, messages and bans in one local SQLite file. Queries are
+/// small and local, so they run right on the calling task.
+pub struct SqliteBackend {
+    conn: Mutex<Connection>,
+}
+
+fn timestamp(row: &Row, index: usize) -> rusqlite::Result<DateTime<Utc>> {
+    let text: String = row.get(index)?;
+    DateTime::parse_from_rfc3339(&text)
+        .map(|at| at.with_timezone(&Utc))
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e.into())
+        })
+}
+
+fn uuid(row: &Row, index: usize) -> rusqlite::Result<Uuid> {
+    let text: String = row.get(index)?;
+    Uuid::parse_str(&text).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+impl SqliteBackend {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to open database: {}", e))?;
+        }
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open database: {}", e))?;
+        Self::init(conn)
+    }
+
+    pub fn in_memory() -> Result<Self, String> {
+        let conn =
+            Connection::open_in_memory().map_err(|e| format!("Failed to open database: {}", e))?;
+        Self::init(conn)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS users (
+                id TEXT PRIMARY KEY,
+                username TEXT NOT NULL UNIQUE,
+                password_hash TEXT NOT NULL,
+                is_moderator INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS sessions (
+                token TEXT NOT NULL,
+                user_id TEXT NOT NULL REFERENCES users (id),
+                expires_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS messages (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                id TEXT NOT NULL UNIQUE,
+                client_id TEXT UNIQUE,
+                channel TEXT NOT NULL,
+                content TEXT NOT NULL,
+                user_id TEXT NOT NULL REFERENCES users (id),
+                created_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS bans (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id TEXT NOT NULL REFERENCES users (id),
+                reason TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL,
+                is_active INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS bans_by_user ON bans (user_id);",
+        )
+        .map_err(|e| format!("Failed to set up database: {}", e))?;
+        Ok(SqliteBackend {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl Backend for SqliteBackend {
+    async fn find_user(&self, username: &str) -> Result<Option<User>, String> {
+        self.conn()
+            .query_row(
+                "SELECT id, username, password_hash, is_moderator, created_at
+                 FROM users WHERE username = ?1",
+                params![username],
+                |row| {
+                    Ok(User {
+                        id: uuid(row, 0)?,
+                        username: row.get(1)?,
+                        password_hash: row.get(2)?,
+                        is_moderator: row.get(3)?,
+                        created_at: timestamp(row, 4)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| format!("Failed to look up user: {}", e))
+    }
+
+    async fn create_user(&self, user: &User) -> Result<(), String> {
+        self.conn()
+            .execute(
+                "INSERT INTO users (id, username, password_hash, is_moderator, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    user.id.to_string(),
+                    user.username,
+                    user.password_hash,
+                    user.is_moderator,
+                    user.created_at.to_rfc3339()
+                ],
+            )
+            .map_err(|e| format!("User creation failed: {}", e))?;
+        Ok(())
+    }
+
+    async fn create_session(
+        &self,
+        user_id: Uuid,
+        token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        self.conn()
+            .execute(
+                "INSERT INTO sessions (token, user_id, expires_at) VALUES (?1, ?2, ?3)",
+                params![token, user_id.to_string(), expires_at.to_rfc3339()],
+            )
+            .map_err(|e| format!("Session creation failed: {}", e))?;
+        Ok(())
+    }
+
+    async fn end_session(&self, token: &str) -> Result<(), String> {
+        self.conn()
+            .execute("DELETE FROM sessions WHERE token = ?1", params![token])
+            .map_err(|e| format!("Failed to end session: {}", e))?;
+        Ok(())
+    }
+
+    async fn find_session(&self, token: &str) -> Result<Option<Session>, String> {
+        self.conn()
+            .query_row(
+                "SELECT s.user_id, u.username, u.is_moderator
+                 FROM sessions s JOIN users u ON u.id = s.user_id
+                 WHERE s.token = ?1 AND s.expires_at > ?2",
+                params![token, Utc::now().to_rfc3339()],
+                |row| {
+                    Ok(Session {
+                        token: token.to_string(),
+                        user_id: uuid(row, 0)?,
+                        username: row.get(1)?,
+                        is_moderator: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| format!("Failed to look up session: {}", e))
+    }
+
+    async fn get_messages(&self, query: Query) -> Result<Vec<ReceivedMessage>, String> {
+        let (filter, after, before, limit) = match query {
+            Query::Latest { limit } => ("1 = 1 ORDER BY m.seq DESC", 0, 0, limit),
+            Query::After { seq, limit } => ("m.seq > ?1 ORDER BY m.seq ASC", seq, 0, limit),
+            Query::Between { after, before } => (
+                "m.seq > ?1 AND m.seq < ?2 ORDER BY m.seq ASC",
+                after,
+                before,
+                usize::MAX,
+            ),
+        };
+        let sql = format!(
+            "SELECT m.id, m.seq, m.client_id, m.content, m.created_at, m.user_id, u.username
+             FROM messages m JOIN users u ON u.id = m.user_id
+             WHERE {} LIMIT ?3",
+            filter
+        );
+        let conn = self.conn();
+        let mut statement = conn
+            .prepare(&sql)
+            .map_err(|e| format!("Failed to fetch messages: {}", e))?;
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let mut messages = statement
+            .query_map(params![after, before, limit], |row| {
+                let client_id: Option<String> = row.get(2)?;
+                Ok(ReceivedMessage {
+                    id: row.get(0)?,
+                    seq: row.get(1)?,
+                    client_id: client_id.and_then(|id| Uuid::parse_str(&id).ok()),
+                    content: row.get(3)?,
+                    created_at: row.get(4)?,
+                    user_id: row.get(5)?,
+                    username: row.get(6)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to fetch messages: {}", e))?;
+        messages.sort_by_key(|m| m.seq);
+        Ok(messages)
+    }
+
+    async fn send_message(
+        &self,
+        client_id: Uuid,
+        user_id: Uuid,
+        content: &str,
+    ) -> Result<(), String> {
+        let conn = self.conn();
+        let already_sent = conn
+            .query_row(
+                "SELECT 1 FROM messages WHERE client_id = ?1",
+                params![client_id.to_string()],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .is_some();
+        if already_sent {
+            return Ok(());
+        }
+
+        let total: usize = conn
+            .query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        if total >= MAX_MESSAGES {
+            conn.execute(
+                "DELETE FROM messages WHERE seq = (SELECT MIN(seq) FROM messages)",
+                [],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        conn.execute(
+            "INSERT INTO messages (id, client_id, channel, content, user_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                Uuid::new_v4().to_string(),
+                client_id.to_string(),
+                DEFAULT_CHANNEL,
+                content,
+                user_id.to_string(),
+                Utc::now().to_rfc3339()
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    async fn ban_history(&self, user_id: Uuid) -> Result<Vec<Ban>, String> {
+        let conn = self.conn();
+        let mut statement = conn
+            .prepare(
+                "SELECT reason, created_at, expires_at, is_active FROM bans
+                 WHERE user_id = ?1 ORDER BY id DESC",
+            )
+            .map_err(|e| format!("Failed to fetch ban history: {}", e))?;
+        let bans = statement
+            .query_map(params![user_id.to_string()], |row| {
+                Ok(Ban {
+                    reason: row.get(0)?,
+                    created_at: timestamp(row, 1)?,
+                    expires_at: timestamp(row, 2)?,
+                    is_active: row.get(3)?,
+                })
+            })
+            .and_then(|rows| rows.collect())
+            .map_err(|e| format!("Failed to fetch ban history: {}", e));
+        bans
+    }
+
+    async fn create_ban(
+        &self,
+        user_id: Uuid,
+        reason: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        self.conn()
+            .execute(
+                "INSERT INTO bans (user_id, reason, created_at, expires_at, is_active)
+                 VALUES (?1, ?2, ?3, ?4, 1)",
+                params![
+                    user_id.to_string(),
+                    reason,
+                    Utc::now().to_rfc3339(),
+                    expires_at.to_rfc3339()
+                ],
+            )
+            .map_err(|e| format!("Failed to create ban: {}", e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth;
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn registers_and_logs_in() {
+        let backend = SqliteBackend::in_memory().unwrap();
+        let session = auth::register(&backend, "alice", "hunter2").await.unwrap();
+        assert_eq!(session.username, "alice");
+        assert!(!session.is_moderator);
+
+        let err = auth::register(&backend, "alice", "again")
+            .await
+            .unwrap_err();
+        assert_eq!(err, "Username already exists");
+        let err = auth::login(&backend, "alice", "wrong").await.unwrap_err();
+        assert_eq!(err, "Invalid password");
+        let again = backend.login("alice", "hunter2").await.unwrap();
+        assert_eq!(again.user_id, session.user_id);
+        assert_ne!(again.token, session.token);
+
+        let found = backend.find_session(&again.token).await.unwrap().unwrap();
+        assert_eq!(found.username, "alice");
+        backend.end_session(&again.token).await.unwrap();
+        assert!(backend.find_session(&again.token).await.unwrap().is_none());
+    }
+
+    async fn user(backend: &SqliteBackend, username: &str) -> Uuid {
+        let user = User {
+            id: Uuid::new_v4(),
+            username: username.to_string(),
+            password_hash: "!".to_string(),
+            is_moderator: false,
+            created_at: Utc::now(),
+        };
+        backend.create_user(&user).await.unwrap();
+        user.id
+    }
+
+    #[tokio::test]
+    async fn answers_sequencer_queries_in_order() {
+        let backend = SqliteBackend::in_memory().unwrap();
+        let alice = user(&backend, "alice").await;
+        for i in 1..=5 {
+            let client_id = Uuid::new_v4();
+            backend
+                .send_message(client_id, alice, &format!("m{}", i))
+                .await
+                .unwrap();
+            // Retries of the same message are dropped.
+            backend.send_message(client_id, alice, "dup").await.unwrap();
+        }
+
+        let contents = |messages: Vec<ReceivedMessage>| -> Vec<String> {
+            messages.into_iter().map(|m| m.content).collect()
+        };
+        let latest = backend
+            .get_messages(Query::Latest { limit: 2 })
+            .await
+            .unwrap();
+        assert_eq!(latest[0].username, "alice");
+        assert_eq!(contents(latest), ["m4", "m5"]);
+        let after = backend
+            .get_messages(Query::After { seq: 3, limit: 10 })
+            .await
+            .unwrap();
+        assert_eq!(contents(after), ["m4", "m5"]);
+        let between = backend
+            .get_messages(Query::Between {
+                after: 1,
+                before: 4,
+            })
+            .await
+            .unwrap();
+        assert_eq!(contents(between), ["m2", "m3"]);
+    }
+
+    #[tokio::test]
+    async fn keeps_at_most_max_messages() {
+        let backend = SqliteBackend::in_memory().unwrap();
+        let alice = user(&backend, "alice").await;
+        for i in 0..MAX_MESSAGES + 3 {
+            backend
+                .send_message(Uuid::new_v4(), alice, &i.to_string())
+                .await
+                .unwrap();
+        }
+        let all = backend
+            .get_messages(Query::Latest { limit: 100 })
+            .await
+            .unwrap();
+        assert_eq!(all.len(), MAX_MESSAGES);
+        assert_eq!(all[0].content, "3");
+    }
+
+    #[tokio::test]
+    async fn current_ban_is_the_one_in_effect() {
+        let backend = SqliteBackend::in_memory().unwrap();
+        let alice = user(&backend, "alice").await;
+        assert_eq!(backend.current_ban(alice, Utc::now()).await.unwrap(), None);
+
+        backend
+            .create_ban(alice, "old news", Utc::now() - Duration::seconds(5))
+            .await
+            .unwrap();
+        backend
+            .create_ban(alice, "existing", Utc::now() + Duration::seconds(30))
+            .await
+            .unwrap();
+        let ban = backend
+            .current_ban(alice, Utc::now())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ban.reason, "existing");
+        let history = backend.ban_history(alice).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].reason, "old news");
+    }
+}
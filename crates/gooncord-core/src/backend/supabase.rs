@@ -0,0 +1,310 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::header;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use super::{Backend, User, MAX_MESSAGES};
+use crate::auth::{self, Session};
+use crate::bans::Ban;
+use crate::migrations;
+use crate::postgrest::{Order, RestQuery};
+use crate::sequencer::Query;
+use crate::{ApiClient, ReceivedMessage};
+
+/// Password hashes and session tokens aren't granted to the anon key, so
+/// accounts and sessions are only reached through the database functions in
+/// `migrations/0004_row_level_security.sql`.
+fn unsupported<T>(what: &str) -> Result<T, String> {
+    Err(format!(
+        "{} is up to the database's gooncord_* functions",
+        what
+    ))
+}
+
+impl ApiClient {
+    async fn start_session(
+        &self,
+        function: &'static str,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<Session>, String> {
+        let request = self
+            .rpc(function)
+            .json(&json!({ "p_username": username, "p_password": password }));
+        let session: Option<Session> = self
+            .send(request, "Failed to sign in")
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse session: {}", e))?;
+        if let Some(session) = &session {
+            self.resume(session);
+        }
+        Ok(session)
+    }
+}
+
+#[async_trait]
+impl Backend for ApiClient {
+    async fn register(&self, username: &str, password: &str) -> Result<Session, String> {
+        auth::validate_username(username)?;
+        self.start_session("gooncord_register", username, password)
+            .await?
+            .ok_or_else(|| "Registration failed".to_string())
+    }
+
+    async fn login(&self, username: &str, password: &str) -> Result<Session, String> {
+        self.start_session("gooncord_login", username, password)
+            .await?
+            .ok_or_else(|| "Invalid username or password".to_string())
+    }
+
+    fn resume(&self, session: &Session) {
+        self.set_session(Some(&session.token));
+    }
+
+    fn has_extras(&self) -> bool {
+        true
+    }
+
+    async fn check_schema(&self) -> Result<(), String> {
+        #[derive(Deserialize)]
+        struct Applied {
+            version: i64,
+        }
+
+        let query = RestQuery::table("schema_migrations")
+            .select("version")
+            .order("version", Order::Desc)
+            .limit(1);
+        let request = self.get(&query);
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to check the database schema: {}", e))?;
+        // PostgREST answers 404 for a table that doesn't exist.
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return migrations::check_compatible(None);
+        }
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("Failed to check the database schema: {}", error));
+        }
+        let applied: Vec<Applied> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse the schema version: {}", e))?;
+        migrations::check_compatible(applied.first().map(|applied| applied.version))
+    }
+
+    async fn find_user(&self, _username: &str) -> Result<Option<User>, String> {
+        unsupported("Looking up password hashes")
+    }
+
+    async fn create_user(&self, _user: &User) -> Result<(), String> {
+        unsupported("Creating users")
+    }
+
+    async fn create_session(
+        &self,
+        _user_id: Uuid,
+        _token: &str,
+        _expires_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        unsupported("Creating sessions")
+    }
+
+    async fn end_session(&self, token: &str) -> Result<(), String> {
+        let request = self
+            .rpc("gooncord_end_session")
+            .json(&json!({ "p_token": token }));
+        self.send(request, "Failed to end session").await?;
+        self.set_session(None);
+        Ok(())
+    }
+
+    async fn find_session(&self, token: &str) -> Result<Option<Session>, String> {
+        let request = self
+            .rpc("gooncord_session")
+            .json(&json!({ "p_token": token }));
+        self.send(request, "Failed to look up session")
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse session: {}", e))
+    }
+
+    async fn get_messages(&self, query: Query) -> Result<Vec<ReceivedMessage>, String> {
+        let messages = RestQuery::table("messages")
+            .select("id,seq,client_id,content,created_at,user_id,users(username)");
+        let messages = match query {
+            Query::Latest { limit } => messages.order("seq", Order::Desc).limit(limit),
+            Query::After { seq, limit } => messages
+                .gt("seq", seq)
+                .order("seq", Order::Asc)
+                .limit(limit),
+            Query::Between { after, before } => messages
+                .gt("seq", after)
+                .lt("seq", before)
+                .order("seq", Order::Asc),
+        };
+        let response = self
+            .send(self.get(&messages), "Failed to fetch messages")
+            .await?;
+
+        #[derive(Deserialize)]
+        struct MessageWithUser {
+            id: String,
+            seq: i64,
+            client_id: Option<Uuid>,
+            content: String,
+            created_at: String,
+            user_id: String,
+            users: Author,
+        }
+
+        #[derive(Deserialize)]
+        struct Author {
+            username: String,
+        }
+
+        let messages_with_users: Vec<MessageWithUser> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse messages: {}", e))?;
+
+        let mut messages = messages_with_users
+            .into_iter()
+            .map(|msg| {
+                let created_at = DateTime::parse_from_rfc3339(&msg.created_at)
+                    .map_err(|e| format!("Invalid timestamp: {}", e))?
+                    .with_timezone(&Utc)
+                    .to_rfc3339();
+
+                Ok(ReceivedMessage {
+                    id: msg.id,
+                    seq: msg.seq,
+                    content: msg.content,
+                    username: msg.users.username,
+                    created_at,
+                    user_id: msg.user_id,
+                    client_id: msg.client_id,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        messages.sort_by_key(|m| m.seq);
+        Ok(messages)
+    }
+
+    async fn send_message(
+        &self,
+        client_id: Uuid,
+        user_id: Uuid,
+        content: &str,
+    ) -> Result<(), String> {
+        #[derive(Deserialize)]
+        struct MessageId {
+            id: String,
+        }
+
+        // Insert first: a retried `client_id` or a row the database refuses
+        // creates nothing, and so shouldn't cost anyone their oldest message.
+        let request = self
+            .post(&RestQuery::table("messages").on_conflict("client_id"))
+            .header(
+                "Prefer",
+                "resolution=ignore-duplicates,return=representation",
+            )
+            .json(&json!({
+                "client_id": client_id,
+                "content": content,
+                "user_id": user_id
+            }));
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(response.text().await.map_err(|e| e.to_string())?);
+        }
+        let created: Vec<MessageId> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse sent message: {}", e))?;
+        if created.is_empty() {
+            return Ok(());
+        }
+
+        // Pinned messages neither count towards the cap nor get deleted.
+        let pinned = self.pinned_ids().await.unwrap_or_default();
+        let unpinned = || {
+            let messages = RestQuery::table("messages").select("id");
+            if pinned.is_empty() {
+                messages
+            } else {
+                messages.not_in("id", &pinned)
+            }
+        };
+
+        let count = self
+            .get(&unpinned().limit(1))
+            .header("Prefer", "count=exact");
+        let total = self
+            .send(count, "Failed to count messages")
+            .await
+            .ok()
+            .and_then(|response| {
+                let range = response
+                    .headers()
+                    .get(header::CONTENT_RANGE)?
+                    .to_str()
+                    .ok()?;
+                range.split('/').nth(1)?.parse::<usize>().ok()
+            });
+
+        if total.is_some_and(|total| total > MAX_MESSAGES) {
+            let query = unpinned().order("created_at", Order::Asc).limit(1);
+            let oldest = self.send(self.get(&query), "Failed to fetch oldest message");
+            if let Ok(response) = oldest.await {
+                let oldest: Vec<MessageId> = response
+                    .json()
+                    .await
+                    .map_err(|e| format!("Failed to parse oldest message: {}", e))?;
+                if let Some(oldest) = oldest.first() {
+                    self.delete(&RestQuery::table("messages").eq("id", &oldest.id))
+                        .send()
+                        .await
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn ban_history(&self, user_id: Uuid) -> Result<Vec<Ban>, String> {
+        let query = RestQuery::table("bans")
+            .eq("user_id", user_id)
+            .select("reason,created_at,expires_at,is_active")
+            .order("created_at", Order::Desc);
+        self.send(self.get(&query), "Failed to fetch ban history")
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse ban history: {}", e))
+    }
+
+    async fn create_ban(
+        &self,
+        user_id: Uuid,
+        reason: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        let request = self.post(&RestQuery::table("bans")).json(&json!({
+            "user_id": user_id,
+            "reason": reason,
+            "expires_at": expires_at.to_rfc3339(),
+            "is_active": true
+        }));
+        self.send(request, "Failed to create ban").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// Chance of getting banned on a roll when no event is active.
+pub const BASE_BAN_CHANCE: f64 = 0.05;
+
+/// How long before an event starts that clients announce it.
+pub const ANNOUNCE_LEAD_SECS: i64 = 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BanEventKind {
+    /// Everyone is rolled against `chance` instead of the base chance.
+    BanStorm { chance: f64 },
+    /// Nobody gets banned.
+    Amnesty,
+    /// Users whose name starts with `prefix` are rolled against `chance`.
+    NamePrefix { prefix: String, chance: f64 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BanEvent {
+    pub id: String,
+    #[serde(flatten)]
+    pub kind: BanEventKind,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+}
+
+impl BanEvent {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.starts_at <= now && now < self.ends_at
+    }
+
+    pub fn applies_to(&self, username: &str) -> bool {
+        match &self.kind {
+            BanEventKind::BanStorm { .. } | BanEventKind::Amnesty => true,
+            BanEventKind::NamePrefix { prefix, .. } => {
+                username.to_lowercase().starts_with(&prefix.to_lowercase())
+            }
+        }
+    }
+
+    pub fn title(&self) -> String {
+        match &self.kind {
+            BanEventKind::BanStorm { chance } => {
+                format!("🌩️ BAN STORM ({:.0}% ban chance)", chance * 100.0)
+            }
+            BanEventKind::Amnesty => "🕊️ AMNESTY (no bans)".to_string(),
+            BanEventKind::NamePrefix { prefix, chance } => format!(
+                "🔤 Everyone whose name starts with \"{}\" ({:.0}% ban chance)",
+                prefix.to_uppercase(),
+                chance * 100.0
+            ),
+        }
+    }
+}
+
+/// The ban chance for a user at a given moment: the base chance with any
+/// active events layered on top.
+#[derive(Debug, Clone)]
+pub struct BanPolicy {
+    pub base_chance: f64,
+    pub events: Vec<BanEvent>,
+}
+
+impl Default for BanPolicy {
+    fn default() -> Self {
+        BanPolicy {
+            base_chance: BASE_BAN_CHANCE,
+            events: Vec::new(),
+        }
+    }
+}
+
+impl BanPolicy {
+    /// Amnesty beats everything; otherwise the harshest applicable event wins.
+    pub fn chance_for(&self, username: &str, now: DateTime<Utc>) -> f64 {
+        let applicable = self
+            .events
+            .iter()
+            .filter(|e| e.is_active(now) && e.applies_to(username));
+
+        let mut chance = self.base_chance;
+        for event in applicable {
+            match event.kind {
+                BanEventKind::Amnesty => return 0.0,
+                BanEventKind::BanStorm { chance: c }
+                | BanEventKind::NamePrefix { chance: c, .. } => chance = chance.max(c),
+            }
+        }
+        chance
+    }
+
+    pub fn active(&self, now: DateTime<Utc>) -> impl Iterator<Item = &BanEvent> {
+        self.events.iter().filter(move |e| e.is_active(now))
+    }
+
+    /// Events starting within the announcement lead time.
+    pub fn upcoming(&self, now: DateTime<Utc>) -> impl Iterator<Item = &BanEvent> {
+        let horizon = now + Duration::seconds(ANNOUNCE_LEAD_SECS);
+        self.events
+            .iter()
+            .filter(move |e| e.starts_at > now && e.starts_at <= horizon)
+    }
+
+    /// Replaces the event list with the scheduled events plus the random
+    /// events of the current and next hour.
+    pub fn set_events(&mut self, scheduled: Vec<BanEvent>, now: DateTime<Utc>) {
+        let hour = now.duration_trunc(Duration::hours(1)).unwrap_or(now);
+        self.events = scheduled;
+        self.events.extend(random_events_for_hour(hour));
+        self.events
+            .extend(random_events_for_hour(hour + Duration::hours(1)));
+    }
+}
+
+/// Random server-wide events for the hour starting at `hour`.
+///
+/// Seeded from the hour itself so every client computes the same schedule
+/// without any coordination.
+pub fn random_events_for_hour(hour: DateTime<Utc>) -> Vec<BanEvent> {
+    let mut rng = StdRng::seed_from_u64(hour.timestamp() as u64);
+    let mut events = Vec::new();
+
+    if rng.gen_bool(0.05) {
+        events.push(BanEvent {
+            id: format!("amnesty-{}", hour.timestamp()),
+            kind: BanEventKind::Amnesty,
+            starts_at: hour,
+            ends_at: hour + Duration::hours(1),
+        });
+    }
+
+    if rng.gen_bool(0.25) {
+        let starts_at = hour + Duration::seconds(rng.gen_range(0..3600 - 30));
+        events.push(BanEvent {
+            id: format!("storm-{}", hour.timestamp()),
+            kind: BanEventKind::BanStorm { chance: 0.5 },
+            starts_at,
+            ends_at: starts_at + Duration::seconds(30),
+        });
+    }
+
+    if rng.gen_bool(0.1) {
+        let letter = rng.gen_range(b'a'..=b'z') as char;
+        let starts_at = hour + Duration::minutes(rng.gen_range(0..50));
+        events.push(BanEvent {
+            id: format!("prefix-{}", hour.timestamp()),
+            kind: BanEventKind::NamePrefix {
+                prefix: letter.to_string(),
+                chance: 0.25,
+            },
+            starts_at,
+            ends_at: starts_at + Duration::minutes(10),
+        });
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn event(kind: BanEventKind, start: i64, end: i64) -> BanEvent {
+        BanEvent {
+            id: format!("test-{}", start),
+            kind,
+            starts_at: at(start),
+            ends_at: at(end),
+        }
+    }
+
+    fn policy(events: Vec<BanEvent>) -> BanPolicy {
+        BanPolicy {
+            base_chance: BASE_BAN_CHANCE,
+            events,
+        }
+    }
+
+    #[test]
+    fn base_chance_without_events() {
+        assert_eq!(policy(vec![]).chance_for("alice", at(0)), BASE_BAN_CHANCE);
+    }
+
+    #[test]
+    fn storm_only_applies_inside_its_window() {
+        let p = policy(vec![event(BanEventKind::BanStorm { chance: 0.5 }, 10, 40)]);
+        assert_eq!(p.chance_for("alice", at(9)), BASE_BAN_CHANCE);
+        assert_eq!(p.chance_for("alice", at(10)), 0.5);
+        assert_eq!(p.chance_for("alice", at(39)), 0.5);
+        assert_eq!(p.chance_for("alice", at(40)), BASE_BAN_CHANCE);
+    }
+
+    #[test]
+    fn amnesty_overrides_storm() {
+        let p = policy(vec![
+            event(BanEventKind::BanStorm { chance: 0.5 }, 0, 100),
+            event(BanEventKind::Amnesty, 50, 100),
+        ]);
+        assert_eq!(p.chance_for("alice", at(25)), 0.5);
+        assert_eq!(p.chance_for("alice", at(75)), 0.0);
+    }
+
+    #[test]
+    fn name_prefix_is_case_insensitive_and_targeted() {
+        let p = policy(vec![event(
+            BanEventKind::NamePrefix {
+                prefix: "a".to_string(),
+                chance: 0.25,
+            },
+            0,
+            100,
+        )]);
+        assert_eq!(p.chance_for("Alice", at(1)), 0.25);
+        assert_eq!(p.chance_for("bob", at(1)), BASE_BAN_CHANCE);
+    }
+
+    #[test]
+    fn harshest_event_wins() {
+        let p = policy(vec![
+            event(
+                BanEventKind::NamePrefix {
+                    prefix: "a".to_string(),
+                    chance: 0.25,
+                },
+                0,
+                100,
+            ),
+            event(BanEventKind::BanStorm { chance: 0.5 }, 0, 100),
+        ]);
+        assert_eq!(p.chance_for("alice", at(1)), 0.5);
+    }
+
+    #[test]
+    fn upcoming_respects_announce_lead() {
+        let p = policy(vec![
+            event(BanEventKind::Amnesty, 30, 60),
+            event(BanEventKind::Amnesty, ANNOUNCE_LEAD_SECS + 1, 200),
+        ]);
+        let upcoming: Vec<_> = p.upcoming(at(0)).map(|e| e.id.clone()).collect();
+        assert_eq!(upcoming, vec!["test-30".to_string()]);
+
+        // Once started an event is active, not upcoming.
+        let upcoming: Vec<_> = p.upcoming(at(30)).map(|e| e.id.clone()).collect();
+        assert_eq!(upcoming, vec![format!("test-{}", ANNOUNCE_LEAD_SECS + 1)]);
+    }
+
+    #[test]
+    fn random_schedule_is_deterministic_per_hour() {
+        let hour = at(0).duration_trunc(Duration::hours(1)).unwrap();
+        for offset in 0..48 {
+            let h = hour + Duration::hours(offset);
+            let events = random_events_for_hour(h);
+            assert_eq!(events, random_events_for_hour(h));
+            for e in &events {
+                assert!(e.starts_at >= h && e.ends_at <= h + Duration::hours(1));
+            }
+        }
+    }
+}
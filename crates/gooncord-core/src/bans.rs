@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A row of the `bans` table.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Ban {
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub is_active: bool,
+}
+
+impl Ban {
+    /// Whether the ban still keeps its user out at `now`.
+    pub fn in_effect(&self, now: DateTime<Utc>) -> bool {
+        self.is_active && self.expires_at > now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn lifted_and_expired_bans_are_not_in_effect() {
+        let now = Utc::now();
+        let ban = Ban {
+            reason: "vibes".to_string(),
+            created_at: now - Duration::seconds(10),
+            expires_at: now + Duration::seconds(10),
+            is_active: true,
+        };
+        assert!(ban.in_effect(now));
+        assert!(!ban.in_effect(now + Duration::seconds(10)));
+        assert!(!Ban {
+            is_active: false,
+            ..ban
+        }
+        .in_effect(now));
+    }
+}
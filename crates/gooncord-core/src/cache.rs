@@ -0,0 +1,555 @@
+use chrono::{DateTime, Utc};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use std::collections::HashSet;
+use std::env;
+use std::path::Path;
+
+use crate::app::Profile;
+use crate::search::{SearchQuery, PAGE_SIZE};
+use crate::ReceivedMessage;
+
+const DEFAULT_MAX_MESSAGES: usize = 500;
+const DEFAULT_MAX_PROFILES: usize = 200;
+
+/// How much the cache keeps per user before dropping the oldest entries.
+#[derive(Debug, Clone, Copy)]
+pub struct CacheConfig {
+    /// Messages kept per channel.
+    pub max_messages: usize,
+    pub max_profiles: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            max_messages: DEFAULT_MAX_MESSAGES,
+            max_profiles: DEFAULT_MAX_PROFILES,
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Defaults, overridden by `GOONCORD_CACHE_MESSAGES` and
+    /// `GOONCORD_CACHE_PROFILES` when set.
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: usize| {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+        CacheConfig {
+            max_messages: read("GOONCORD_CACHE_MESSAGES", DEFAULT_MAX_MESSAGES),
+            max_profiles: read("GOONCORD_CACHE_PROFILES", DEFAULT_MAX_PROFILES),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelMeta {
+    pub name: String,
+    pub last_message_at: Option<String>,
+    pub synced_at: String,
+}
+
+/// On-disk copy of recent messages, viewed profiles and channel metadata so
+/// the chat has something to show before (or without) the network. Every
+/// row belongs to an owner: the id of the user it was cached for.
+pub struct Cache {
+    conn: Connection,
+    config: CacheConfig,
+}
+
+impl Cache {
+    pub fn open(path: &Path, config: CacheConfig) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to open cache: {}", e))?;
+        }
+        let conn = Connection::open(path).map_err(|e| format!("Failed to open cache: {}", e))?;
+        Self::init(conn, config)
+    }
+
+    pub fn in_memory(config: CacheConfig) -> Result<Self, String> {
+        let conn =
+            Connection::open_in_memory().map_err(|e| format!("Failed to open cache: {}", e))?;
+        Self::init(conn, config)
+    }
+
+    fn init(conn: Connection, config: CacheConfig) -> Result<Self, String> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS meta (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS messages (
+                owner TEXT NOT NULL,
+                channel TEXT NOT NULL,
+                id TEXT NOT NULL,
+                client_id TEXT,
+                user_id TEXT NOT NULL,
+                username TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                created_ms INTEGER NOT NULL,
+                PRIMARY KEY (owner, channel, id)
+            );
+            CREATE INDEX IF NOT EXISTS messages_by_time
+                ON messages (owner, channel, created_ms);
+            CREATE TABLE IF NOT EXISTS profiles (
+                owner TEXT NOT NULL,
+                username TEXT NOT NULL,
+                profile TEXT NOT NULL,
+                cached_at INTEGER NOT NULL,
+                PRIMARY KEY (owner, username)
+            );
+            CREATE TABLE IF NOT EXISTS channels (
+                owner TEXT NOT NULL,
+                name TEXT NOT NULL,
+                last_message_at TEXT,
+                synced_at TEXT NOT NULL,
+                PRIMARY KEY (owner, name)
+            );",
+        )
+        .map_err(|e| format!("Failed to set up cache: {}", e))?;
+
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to set up cache: {}", e))?;
+        if version < 1 {
+            conn.execute_batch(
+                "ALTER TABLE messages ADD COLUMN seq INTEGER NOT NULL DEFAULT 0;
+                 PRAGMA user_version = 1;",
+            )
+            .map_err(|e| format!("Failed to upgrade cache: {}", e))?;
+        }
+        Ok(Cache { conn, config })
+    }
+
+    /// The owner whose cache was used last, so startup can show their chat
+    /// before anyone has logged in.
+    pub fn last_owner(&self) -> Option<String> {
+        self.conn
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'last_owner'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .ok()
+            .flatten()
+    }
+
+    pub fn set_last_owner(&self, owner: &str) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO meta (key, value) VALUES ('last_owner', ?1)
+                 ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                params![owner],
+            )
+            .map_err(|e| format!("Failed to update cache: {}", e))?;
+        Ok(())
+    }
+
+    /// The newest `limit` cached messages of a channel, oldest first.
+    pub fn recent_messages(
+        &self,
+        owner: &str,
+        channel: &str,
+        limit: usize,
+    ) -> Result<Vec<ReceivedMessage>, String> {
+        let mut statement = self
+            .conn
+            .prepare(
+                "SELECT id, seq, client_id, user_id, username, content, created_at FROM (
+                    SELECT * FROM messages WHERE owner = ?1 AND channel = ?2
+                    ORDER BY seq DESC, created_ms DESC LIMIT ?3
+                 ) ORDER BY seq, created_ms",
+            )
+            .map_err(|e| format!("Failed to read cache: {}", e))?;
+
+        let rows = statement
+            .query_map(params![owner, channel, limit as i64], |row| {
+                let client_id: Option<String> = row.get(2)?;
+                Ok(ReceivedMessage {
+                    id: row.get(0)?,
+                    seq: row.get(1)?,
+                    client_id: client_id.and_then(|id| id.parse().ok()),
+                    user_id: row.get(3)?,
+                    username: row.get(4)?,
+                    content: row.get(5)?,
+                    created_at: row.get(6)?,
+                })
+            })
+            .map_err(|e| format!("Failed to read cache: {}", e))?;
+
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to read cache: {}", e))
+    }
+
+    /// Adds or updates messages and trims the channel to the configured size.
+    pub fn store_messages(
+        &self,
+        owner: &str,
+        channel: &str,
+        messages: &[ReceivedMessage],
+    ) -> Result<(), String> {
+        for msg in messages {
+            self.conn
+                .execute(
+                    "INSERT INTO messages
+                        (owner, channel, id, seq, client_id, user_id, username, content, created_at, created_ms)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                     ON CONFLICT (owner, channel, id) DO UPDATE SET
+                        content = excluded.content,
+                        username = excluded.username",
+                    params![
+                        owner,
+                        channel,
+                        msg.id,
+                        msg.seq,
+                        msg.client_id.map(|id| id.to_string()),
+                        msg.user_id,
+                        msg.username,
+                        msg.content,
+                        msg.created_at,
+                        timestamp_ms(&msg.created_at),
+                    ],
+                )
+                .map_err(|e| format!("Failed to update cache: {}", e))?;
+        }
+
+        self.conn
+            .execute(
+                "DELETE FROM messages WHERE owner = ?1 AND channel = ?2 AND id NOT IN (
+                    SELECT id FROM messages WHERE owner = ?1 AND channel = ?2
+                    ORDER BY seq DESC, created_ms DESC LIMIT ?3
+                 )",
+                params![owner, channel, self.config.max_messages as i64],
+            )
+            .map_err(|e| format!("Failed to trim cache: {}", e))?;
+
+        let last_message_at = messages
+            .iter()
+            .map(|m| m.created_at.as_str())
+            .max_by_key(|t| timestamp_ms(t));
+        self.conn
+            .execute(
+                "INSERT INTO channels (owner, name, last_message_at, synced_at)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (owner, name) DO UPDATE SET
+                    last_message_at = COALESCE(excluded.last_message_at, channels.last_message_at),
+                    synced_at = excluded.synced_at",
+                params![owner, channel, last_message_at, Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| format!("Failed to update cache: {}", e))?;
+        Ok(())
+    }
+
+    /// Brings the cache in line with the server's newest messages. Anything
+    /// cached within the `seq` range `latest` covers but missing from it has
+    /// been deleted on the server and is dropped here too. An empty `latest`
+    /// covers nothing, so the cache is left alone.
+    pub fn reconcile(
+        &self,
+        owner: &str,
+        channel: &str,
+        latest: &[ReceivedMessage],
+    ) -> Result<(), String> {
+        let (Some(first), Some(last)) = (
+            latest.iter().map(|m| m.seq).min(),
+            latest.iter().map(|m| m.seq).max(),
+        ) else {
+            return Ok(());
+        };
+        let keep: HashSet<&str> = latest.iter().map(|m| m.id.as_str()).collect();
+
+        let cached = self.recent_messages(owner, channel, self.config.max_messages)?;
+        for msg in cached {
+            if (first..=last).contains(&msg.seq) && !keep.contains(msg.id.as_str()) {
+                self.conn
+                    .execute(
+                        "DELETE FROM messages WHERE owner = ?1 AND channel = ?2 AND id = ?3",
+                        params![owner, channel, msg.id],
+                    )
+                    .map_err(|e| format!("Failed to update cache: {}", e))?;
+            }
+        }
+
+        self.store_messages(owner, channel, latest)
+    }
+
+    /// Offline stand-in for full-text search: every term has to appear in
+    /// the message, case-insensitively. Returns `(channel, message)` pairs,
+    /// newest first, one more than a page to tell whether more follow.
+    pub fn search(
+        &self,
+        owner: &str,
+        query: &SearchQuery,
+    ) -> Result<Vec<(String, ReceivedMessage)>, String> {
+        let mut sql = "SELECT channel, id, seq, client_id, user_id, username, content, created_at
+             FROM messages WHERE owner = ?"
+            .to_string();
+        let mut values = vec![Value::Text(owner.to_string())];
+
+        for term in query.terms() {
+            let escaped = term
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            sql.push_str(" AND lower(content) LIKE ? ESCAPE '\\'");
+            values.push(Value::Text(format!("%{}%", escaped)));
+        }
+        if let Some(author) = &query.author {
+            sql.push_str(" AND lower(username) = lower(?)");
+            values.push(Value::Text(author.clone()));
+        }
+        if let Some(channel) = &query.channel {
+            sql.push_str(" AND channel = ?");
+            values.push(Value::Text(channel.clone()));
+        }
+        if let Some(from) = query.from {
+            sql.push_str(" AND created_ms >= ?");
+            values.push(Value::Integer(from.timestamp_millis()));
+        }
+        if let Some(to) = query.to {
+            sql.push_str(" AND created_ms < ?");
+            values.push(Value::Integer(to.timestamp_millis()));
+        }
+        sql.push_str(" ORDER BY seq DESC, created_ms DESC LIMIT ? OFFSET ?");
+        values.push(Value::Integer(PAGE_SIZE as i64 + 1));
+        values.push(Value::Integer((query.page * PAGE_SIZE) as i64));
+
+        let mut statement = self
+            .conn
+            .prepare(&sql)
+            .map_err(|e| format!("Failed to search cache: {}", e))?;
+        let rows = statement
+            .query_map(params_from_iter(values), |row| {
+                let client_id: Option<String> = row.get(3)?;
+                Ok((
+                    row.get(0)?,
+                    ReceivedMessage {
+                        id: row.get(1)?,
+                        seq: row.get(2)?,
+                        client_id: client_id.and_then(|id| id.parse().ok()),
+                        user_id: row.get(4)?,
+                        username: row.get(5)?,
+                        content: row.get(6)?,
+                        created_at: row.get(7)?,
+                    },
+                ))
+            })
+            .map_err(|e| format!("Failed to search cache: {}", e))?;
+
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to search cache: {}", e))
+    }
+
+    pub fn channel(&self, owner: &str, name: &str) -> Option<ChannelMeta> {
+        self.conn
+            .query_row(
+                "SELECT name, last_message_at, synced_at FROM channels
+                 WHERE owner = ?1 AND name = ?2",
+                params![owner, name],
+                |row| {
+                    Ok(ChannelMeta {
+                        name: row.get(0)?,
+                        last_message_at: row.get(1)?,
+                        synced_at: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+            .ok()
+            .flatten()
+    }
+
+    pub fn profile(&self, owner: &str, username: &str) -> Option<Profile> {
+        let json: String = self
+            .conn
+            .query_row(
+                "SELECT profile FROM profiles WHERE owner = ?1 AND username = ?2",
+                params![owner, username],
+                |row| row.get(0),
+            )
+            .optional()
+            .ok()
+            .flatten()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    pub fn store_profile(&self, owner: &str, profile: &Profile) -> Result<(), String> {
+        let json = serde_json::to_string(profile).map_err(|e| e.to_string())?;
+        self.conn
+            .execute(
+                "INSERT INTO profiles (owner, username, profile, cached_at)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (owner, username) DO UPDATE SET
+                    profile = excluded.profile,
+                    cached_at = excluded.cached_at",
+                params![owner, profile.username, json, Utc::now().timestamp_millis()],
+            )
+            .map_err(|e| format!("Failed to update cache: {}", e))?;
+
+        self.conn
+            .execute(
+                "DELETE FROM profiles WHERE owner = ?1 AND username NOT IN (
+                    SELECT username FROM profiles WHERE owner = ?1
+                    ORDER BY cached_at DESC LIMIT ?2
+                 )",
+                params![owner, self.config.max_profiles as i64],
+            )
+            .map_err(|e| format!("Failed to trim cache: {}", e))?;
+        Ok(())
+    }
+}
+
+fn timestamp_ms(timestamp: &str) -> i64 {
+    DateTime::parse_from_rfc3339(timestamp).map_or(0, |t| t.timestamp_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, secs: i64) -> ReceivedMessage {
+        ReceivedMessage {
+            id: id.to_string(),
+            seq: secs,
+            client_id: None,
+            content: format!("message {}", id),
+            username: "alice".to_string(),
+            created_at: DateTime::from_timestamp(1_700_000_000 + secs, 0)
+                .unwrap()
+                .to_rfc3339(),
+            user_id: "u1".to_string(),
+        }
+    }
+
+    fn ids(messages: &[ReceivedMessage]) -> Vec<&str> {
+        messages.iter().map(|m| m.id.as_str()).collect()
+    }
+
+    fn cache(max_messages: usize) -> Cache {
+        Cache::in_memory(CacheConfig {
+            max_messages,
+            max_profiles: 2,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn messages_come_back_in_order_and_bounded() {
+        let cache = cache(3);
+        let batch = [
+            message("c", 3),
+            message("a", 1),
+            message("d", 4),
+            message("b", 2),
+        ];
+        cache.store_messages("me", "general", &batch).unwrap();
+
+        let cached = cache.recent_messages("me", "general", 10).unwrap();
+        assert_eq!(ids(&cached), vec!["b", "c", "d"]);
+
+        let meta = cache.channel("me", "general").unwrap();
+        assert_eq!(meta.last_message_at, Some(message("d", 4).created_at));
+    }
+
+    #[test]
+    fn owners_do_not_share_a_cache() {
+        let cache = cache(10);
+        cache
+            .store_messages("me", "general", &[message("a", 1)])
+            .unwrap();
+        assert!(cache
+            .recent_messages("you", "general", 10)
+            .unwrap()
+            .is_empty());
+        assert!(cache.channel("you", "general").is_none());
+    }
+
+    #[test]
+    fn reconcile_drops_messages_deleted_on_the_server() {
+        let cache = cache(10);
+        let old = [
+            message("a", 1),
+            message("b", 2),
+            message("c", 3),
+            message("e", 5),
+        ];
+        cache.store_messages("me", "general", &old).unwrap();
+
+        // "c" was deleted; "a" and "e" are outside the range the server sent
+        // back, so there's no telling and they stay.
+        cache
+            .reconcile("me", "general", &[message("b", 2), message("d", 4)])
+            .unwrap();
+        let cached = cache.recent_messages("me", "general", 10).unwrap();
+        assert_eq!(ids(&cached), vec!["a", "b", "d", "e"]);
+
+        // An empty answer says nothing about what was deleted.
+        cache.reconcile("me", "general", &[]).unwrap();
+        assert_eq!(cache.recent_messages("me", "general", 10).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn profiles_round_trip_and_are_bounded() {
+        let cache = cache(10);
+        for name in ["a", "b", "c"] {
+            let profile = Profile {
+                username: name.to_string(),
+                created_at: "2024-01-01T00:00:00Z".to_string(),
+                ban_count: 1,
+                is_banned: false,
+                badges: vec!["🔨 First Ban".to_string()],
+            };
+            cache.store_profile("me", &profile).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        assert!(cache.profile("me", "a").is_none());
+        let c = cache.profile("me", "c").unwrap();
+        assert_eq!(c.ban_count, 1);
+        assert_eq!(c.badges, vec!["🔨 First Ban".to_string()]);
+        assert!(cache.profile("you", "c").is_none());
+    }
+
+    #[test]
+    fn search_matches_all_terms_and_filters() {
+        let cache = cache(10);
+        let mut pizza = message("a", 1);
+        pizza.content = "Pineapple pizza 100% forever".to_string();
+        let mut bob = message("b", 2);
+        bob.content = "pizza time".to_string();
+        bob.username = "bob".to_string();
+        cache
+            .store_messages("me", "general", &[pizza, bob])
+            .unwrap();
+
+        let search = |text: &str, author: Option<&str>| {
+            let query = SearchQuery {
+                text: text.to_string(),
+                author: author.map(str::to_string),
+                ..SearchQuery::default()
+            };
+            let rows = cache.search("me", &query).unwrap();
+            rows.into_iter().map(|(_, m)| m.id).collect::<Vec<_>>()
+        };
+        assert_eq!(search("PIZZA", None), vec!["b", "a"]);
+        assert_eq!(search("pizza pineapple", None), vec!["a"]);
+        assert_eq!(search("pizza", Some("Bob")), vec!["b"]);
+        assert_eq!(search("100%", None), vec!["a"]);
+        assert!(search("0%f", None).is_empty());
+    }
+
+    #[test]
+    fn remembers_last_owner() {
+        let cache = cache(10);
+        assert_eq!(cache.last_owner(), None);
+        cache.set_last_owner("me").unwrap();
+        cache.set_last_owner("you").unwrap();
+        assert_eq!(cache.last_owner(), Some("you".to_string()));
+    }
+}
@@ -0,0 +1,338 @@
+//! Where the ban system gets the time and its luck from.
+//!
+//! Normally that's the system clock and an RNG seeded from the OS. Tests pin
+//! both down with [`ManualClock`] and [`Chance::seeded`], and
+//! `GOONCORD_SEED` does the same for a whole client. A session run with
+//! `GOONCORD_RECORD=<file>` writes down every random draw and clock reading
+//! as it goes; `GOONCORD_REPLAY=<file>` plays that file back, so the same
+//! actions meet the same bans again.
+
+use chrono::{DateTime, Duration, Utc};
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to.
+pub struct ManualClock(Mutex<DateTime<Utc>>);
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        ManualClock(Mutex::new(now))
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.0.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// The random numbers behind ban rolls, reasons and durations. Not for
+/// secrets: a seeded or replayed one is entirely predictable.
+pub type Rng = Box<dyn RngCore + Send + Sync>;
+
+/// The clock and RNG an [`crate::app::AppState`] runs on.
+pub struct Chance {
+    pub clock: Arc<dyn Clock>,
+    pub rng: Rng,
+}
+
+impl Chance {
+    /// The real time and real luck.
+    pub fn system() -> Self {
+        Chance {
+            clock: Arc::new(SystemClock),
+            rng: Box::new(StdRng::from_entropy()),
+        }
+    }
+
+    /// Luck that comes out the same for the same `seed`, on `clock`.
+    pub fn seeded(seed: u64, clock: Arc<dyn Clock>) -> Self {
+        Chance {
+            clock,
+            rng: Box::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    /// The same clock and RNG, with every reading and draw written to
+    /// `recorder`.
+    pub fn recorded(self, recorder: Recorder) -> Self {
+        Chance {
+            clock: Arc::new(RecordingClock {
+                clock: self.clock,
+                recorder: recorder.clone(),
+            }),
+            rng: Box::new(RecordingRng {
+                rng: self.rng,
+                recorder,
+            }),
+        }
+    }
+
+    /// Plays back a recorded session. Once the recording runs out, time
+    /// goes on from the system clock and the draws from a fixed seed.
+    pub fn replay(entries: Vec<Entry>) -> Self {
+        let mut draws = VecDeque::new();
+        let mut times = VecDeque::new();
+        for entry in entries {
+            match entry {
+                Entry::Draw(draw) => draws.push_back(draw),
+                Entry::Time(time) => times.push_back(time),
+            }
+        }
+        Chance {
+            clock: Arc::new(ReplayClock(Mutex::new(times))),
+            rng: Box::new(ReplayRng {
+                draws,
+                after: StdRng::seed_from_u64(0),
+            }),
+        }
+    }
+
+    /// Replays `GOONCORD_REPLAY` if set, otherwise seeds from `GOONCORD_SEED`
+    /// if set, otherwise uses [`Chance::system`]. `GOONCORD_RECORD` records
+    /// whichever it is.
+    pub fn from_env() -> Result<Self, String> {
+        let chance = if let Ok(path) = env::var("GOONCORD_REPLAY") {
+            Chance::replay(load(Path::new(&path))?)
+        } else if let Ok(seed) = env::var("GOONCORD_SEED") {
+            let seed = seed
+                .parse()
+                .map_err(|_| format!("GOONCORD_SEED {:?} is not a number", seed))?;
+            Chance::seeded(seed, Arc::new(SystemClock))
+        } else {
+            Chance::system()
+        };
+        match env::var("GOONCORD_RECORD") {
+            Ok(path) => Ok(chance.recorded(Recorder::create(Path::new(&path))?)),
+            Err(_) => Ok(chance),
+        }
+    }
+}
+
+/// One thing a session asked for, in the order it asked.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Entry {
+    Draw(u64),
+    Time(DateTime<Utc>),
+}
+
+/// Reads a recording: one JSON [`Entry`] per line.
+pub fn load(path: &Path) -> Result<Vec<Entry>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Can't read recording {}: {}", path.display(), e))?;
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str(line).map_err(|e| format!("Bad recording entry {:?}: {}", line, e))
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct Recording {
+    entries: Vec<Entry>,
+    file: Option<File>,
+}
+
+/// Collects a session's entries, and appends them to a file if it has one
+/// so a crash doesn't lose the recording.
+#[derive(Clone, Default)]
+pub struct Recorder(Arc<Mutex<Recording>>);
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file =
+            File::create(path).map_err(|e| format!("Can't record to {}: {}", path.display(), e))?;
+        Ok(Recorder(Arc::new(Mutex::new(Recording {
+            entries: Vec::new(),
+            file: Some(file),
+        }))))
+    }
+
+    /// Everything recorded so far.
+    pub fn entries(&self) -> Vec<Entry> {
+        self.0.lock().unwrap().entries.clone()
+    }
+
+    fn record(&self, entry: Entry) {
+        let mut recording = self.0.lock().unwrap();
+        if let Some(file) = &mut recording.file {
+            let line = serde_json::to_string(&entry).expect("entries serialize");
+            if let Err(e) = writeln!(file, "{}", line) {
+                eprintln!("Failed to record, recording in memory only: {}", e);
+                recording.file = None;
+            }
+        }
+        recording.entries.push(entry);
+    }
+}
+
+struct RecordingClock {
+    clock: Arc<dyn Clock>,
+    recorder: Recorder,
+}
+
+impl Clock for RecordingClock {
+    fn now(&self) -> DateTime<Utc> {
+        let now = self.clock.now();
+        self.recorder.record(Entry::Time(now));
+        now
+    }
+}
+
+struct ReplayClock(Mutex<VecDeque<DateTime<Utc>>>);
+
+impl Clock for ReplayClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0.lock().unwrap().pop_front().unwrap_or_else(Utc::now)
+    }
+}
+
+/// Fills `dest` from `next_u64`, so an RNG that records or replays whole
+/// draws sees byte requests as draws too.
+fn fill_from_u64(rng: &mut impl RngCore, dest: &mut [u8]) {
+    for chunk in dest.chunks_mut(8) {
+        let bytes = rng.next_u64().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+struct RecordingRng {
+    rng: Rng,
+    recorder: Recorder,
+}
+
+impl RngCore for RecordingRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let draw = self.rng.next_u64();
+        self.recorder.record(Entry::Draw(draw));
+        draw
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_from_u64(self, dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+struct ReplayRng {
+    draws: VecDeque<u64>,
+    after: StdRng,
+}
+
+impl RngCore for ReplayRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.draws
+            .pop_front()
+            .unwrap_or_else(|| self.after.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_from_u64(self, dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng as _;
+
+    fn draws(rng: &mut Rng) -> Vec<i64> {
+        (0..20).map(|_| rng.gen_range(10..30)).collect()
+    }
+
+    #[test]
+    fn seeds_repeat_themselves() {
+        let clock = Arc::new(ManualClock::new(Utc::now()));
+        let mut a = Chance::seeded(7, clock.clone());
+        let mut b = Chance::seeded(7, clock.clone());
+        assert_eq!(draws(&mut a.rng), draws(&mut b.rng));
+        assert_ne!(draws(&mut a.rng), draws(&mut Chance::seeded(8, clock).rng));
+    }
+
+    #[test]
+    fn manual_clocks_move_only_when_told() {
+        let start = Utc::now();
+        let clock = ManualClock::new(start);
+        assert_eq!(clock.now(), start);
+        clock.advance(Duration::seconds(5));
+        assert_eq!(clock.now(), start + Duration::seconds(5));
+    }
+
+    #[test]
+    fn replays_what_was_recorded() {
+        let recorder = Recorder::default();
+        let mut original = Chance::system().recorded(recorder.clone());
+        let times: Vec<_> = (0..3).map(|_| original.clock.now()).collect();
+        let rolls = draws(&mut original.rng);
+        let chance: f64 = original.rng.gen_range(0.0..1.0);
+
+        let mut replay = Chance::replay(recorder.entries());
+        assert_eq!(
+            (0..3).map(|_| replay.clock.now()).collect::<Vec<_>>(),
+            times
+        );
+        assert_eq!(draws(&mut replay.rng), rolls);
+        assert_eq!(replay.rng.gen_range(0.0..1.0), chance);
+    }
+
+    #[test]
+    fn recordings_survive_the_round_trip_through_a_file() {
+        let path = env::temp_dir().join(format!("gooncord-chance-{}.jsonl", std::process::id()));
+        let recorder = Recorder::create(&path).unwrap();
+        let mut original = Chance::seeded(3, Arc::new(SystemClock)).recorded(recorder.clone());
+        original.clock.now();
+        draws(&mut original.rng);
+
+        let loaded = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, recorder.entries());
+        assert!(matches!(loaded[0], Entry::Time(_)));
+    }
+}
@@ -0,0 +1,113 @@
+use reqwest::{header, Client, RequestBuilder, Response};
+use serde::Deserialize;
+use std::sync::{Arc, RwLock};
+
+use crate::postgrest::RestQuery;
+
+/// The header carrying who a request is from; the row-level security
+/// policies in `migrations/0004_row_level_security.sql` read it.
+pub const SESSION_HEADER: &str = "X-Gooncord-Session";
+
+/// A connection to the Supabase backend. Holds one `reqwest::Client`, so
+/// everything made through it shares a connection pool. Clones share the
+/// session too: signing in through one signs them all in.
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    pub url: String,
+    pub key: String,
+    pub http: Client,
+    session: Arc<RwLock<Option<String>>>,
+}
+
+impl ApiClient {
+    pub fn new(url: String, key: String) -> Self {
+        ApiClient {
+            url,
+            key,
+            http: Client::new(),
+            session: Arc::default(),
+        }
+    }
+
+    /// Sends `token` (a session's, or a bot's) with every request from now
+    /// on, or nothing for `None`.
+    pub fn set_session(&self, token: Option<&str>) {
+        *self.session.write().unwrap_or_else(|e| e.into_inner()) = token.map(str::to_string);
+    }
+
+    /// The PostgREST URL for `query`.
+    pub fn rest_url(&self, query: &RestQuery) -> String {
+        format!("{}/rest/v1/{}", self.url, query)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = request
+            .header(header::AUTHORIZATION, format!("Bearer {}", self.key))
+            .header("apikey", &self.key);
+        match self
+            .session
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_deref()
+        {
+            Some(token) => request.header(SESSION_HEADER, token),
+            None => request,
+        }
+    }
+
+    pub fn get(&self, query: &RestQuery) -> RequestBuilder {
+        self.authorize(self.http.get(self.rest_url(query)))
+    }
+
+    pub fn post(&self, query: &RestQuery) -> RequestBuilder {
+        self.authorize(self.http.post(self.rest_url(query)))
+    }
+
+    pub fn patch(&self, query: &RestQuery) -> RequestBuilder {
+        self.authorize(self.http.patch(self.rest_url(query)))
+    }
+
+    pub fn delete(&self, query: &RestQuery) -> RequestBuilder {
+        self.authorize(self.http.delete(self.rest_url(query)))
+    }
+
+    /// Calls the database function `function`; arguments go in the JSON
+    /// body.
+    pub fn rpc(&self, function: &'static str) -> RequestBuilder {
+        let url = format!("{}/rest/v1/rpc/{}", self.url, function);
+        self.authorize(self.http.post(url))
+    }
+
+    /// Sends a request, turning transport failures and error statuses into
+    /// a message starting with `what`.
+    pub async fn send(&self, request: RequestBuilder, what: &str) -> Result<Response, String> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("{}: {}", what, e))?;
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("{}: {}", what, error));
+        }
+        Ok(response)
+    }
+
+    /// Ids of pinned messages, which are exempt from retention cleanup.
+    pub async fn pinned_ids(&self) -> Result<Vec<String>, String> {
+        #[derive(Deserialize)]
+        struct PinnedId {
+            message_id: String,
+        }
+
+        let pins: Vec<PinnedId> = self
+            .send(
+                self.get(&RestQuery::table("pins").select("message_id")),
+                "Failed to fetch pins",
+            )
+            .await?
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse pins: {}", e))?;
+        Ok(pins.into_iter().map(|p| p.message_id).collect())
+    }
+}
@@ -0,0 +1,439 @@
+use rand::{Rng, RngCore};
+use serde::Serialize;
+
+use crate::BAN_REASONS;
+
+/// Most dice `/roll` will throw at once.
+const MAX_DICE: u32 = 20;
+/// Most sides a `/roll` die can have.
+const MAX_SIDES: u32 = 1000;
+
+/// What a command wants done. Commands only decide; the caller carries it
+/// out, so they stay free of network and UI code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// Post this as a chat message.
+    Send(String),
+    /// Show this to the user only.
+    Reply(String),
+    /// Change the user's name.
+    Rename(String),
+    /// Ban the user, on request.
+    BanSelf { reason: String },
+    /// Make a bot account and hand out its token. Moderators only.
+    CreateBot { name: String },
+    /// Invalidate a bot's tokens. Moderators only.
+    RevokeBot { name: String },
+}
+
+/// Numbers shown by `/stats`.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub bans: u32,
+    pub reasons_collected: usize,
+    pub messages: u32,
+    pub balance: i64,
+}
+
+/// What a command gets to look at besides its arguments.
+pub struct Context<'a> {
+    pub username: &'a str,
+    pub stats: Stats,
+    pub rng: &'a mut dyn RngCore,
+}
+
+/// Everything typed after the command name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Args<'a> {
+    pub raw: &'a str,
+}
+
+impl<'a> Args<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    pub fn words(&self) -> Vec<&'a str> {
+        self.raw.split_whitespace().collect()
+    }
+
+    /// Errors unless there is exactly one word.
+    pub fn single(&self) -> Result<&'a str, String> {
+        match self.words()[..] {
+            [word] => Ok(word),
+            [] => Err("Missing argument".to_string()),
+            _ => Err("Too many arguments".to_string()),
+        }
+    }
+}
+
+/// Runs a command. Errors are shown to the user along with its usage.
+pub type Handler = Box<dyn Fn(Args<'_>, &mut Context<'_>) -> Result<Outcome, String> + Send + Sync>;
+
+pub struct Command {
+    pub name: &'static str,
+    /// How to call it, e.g. `/roll [NdM]`.
+    pub usage: &'static str,
+    pub summary: &'static str,
+    pub handler: Handler,
+}
+
+/// A command as listed in help and autocomplete.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub summary: &'static str,
+}
+
+/// How a line typed into the composer should be treated.
+#[derive(Debug, PartialEq)]
+pub enum Input<'a> {
+    /// Plain text. A leading `//` is sent as a single `/`.
+    Text(&'a str),
+    Command {
+        name: &'a str,
+        args: Args<'a>,
+    },
+}
+
+/// Splits `/name args...` from plain text.
+pub fn parse(line: &str) -> Input<'_> {
+    let Some(rest) = line.strip_prefix('/') else {
+        return Input::Text(line);
+    };
+    if rest.starts_with('/') {
+        return Input::Text(rest);
+    }
+    let (name, raw) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    Input::Command {
+        name,
+        args: Args { raw: raw.trim() },
+    }
+}
+
+/// The slash commands available in the composer. Starts out with the
+/// built-in ones; plugins add theirs with [`CommandRegistry::register`].
+pub struct CommandRegistry {
+    commands: Vec<Command>,
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        let mut registry = CommandRegistry {
+            commands: Vec::new(),
+        };
+        for command in builtins() {
+            registry
+                .register(command)
+                .expect("built-in names are unique");
+        }
+        registry
+    }
+}
+
+impl CommandRegistry {
+    /// Adds a command. Names are matched case-insensitively and must be
+    /// unique, `help` included.
+    pub fn register(&mut self, command: Command) -> Result<(), String> {
+        if command.name.eq_ignore_ascii_case("help") || self.find(command.name).is_some() {
+            return Err(format!("/{} is already a command", command.name));
+        }
+        self.commands.push(command);
+        Ok(())
+    }
+
+    fn find(&self, name: &str) -> Option<&Command> {
+        self.commands
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Runs a parsed command. Errors come back with the command's usage.
+    pub fn run(
+        &self,
+        name: &str,
+        args: Args<'_>,
+        ctx: &mut Context<'_>,
+    ) -> Result<Outcome, String> {
+        if name.eq_ignore_ascii_case("help") {
+            return Ok(Outcome::Reply(self.help(args.raw)));
+        }
+        let Some(command) = self.find(name) else {
+            return Err(format!("Unknown command /{}. Try /help", name));
+        };
+        (command.handler)(args, ctx).map_err(|e| format!("{}. Usage: {}", e, command.usage))
+    }
+
+    fn help(&self, topic: &str) -> String {
+        let topic = topic.trim_start_matches('/');
+        if let Some(command) = self.find(topic) {
+            return format!("{}: {}", command.usage, command.summary);
+        }
+        let mut lines = vec!["Commands (start with // to send a literal /):".to_string()];
+        lines.extend(
+            self.suggest("")
+                .iter()
+                .map(|s| format!("{} \u{2014} {}", s.usage, s.summary)),
+        );
+        lines.join("\n")
+    }
+
+    /// Commands whose name starts with `prefix` (with or without the
+    /// slash), alphabetically.
+    pub fn suggest(&self, prefix: &str) -> Vec<Suggestion> {
+        let prefix = prefix.trim_start_matches('/').to_lowercase();
+        let help = Suggestion {
+            name: "help",
+            usage: "/help [command]",
+            summary: "List commands or explain one",
+        };
+        let mut suggestions: Vec<Suggestion> = self
+            .commands
+            .iter()
+            .map(|c| Suggestion {
+                name: c.name,
+                usage: c.usage,
+                summary: c.summary,
+            })
+            .chain([help])
+            .filter(|s| s.name.to_lowercase().starts_with(&prefix))
+            .collect();
+        suggestions.sort_by_key(|s| s.name);
+        suggestions
+    }
+}
+
+fn builtins() -> Vec<Command> {
+    vec![
+        Command {
+            name: "me",
+            usage: "/me <action>",
+            summary: "Describe what you're doing",
+            handler: Box::new(|args, ctx| {
+                if args.is_empty() {
+                    return Err("Missing action".to_string());
+                }
+                Ok(Outcome::Send(format!("* {} {}", ctx.username, args.raw)))
+            }),
+        },
+        Command {
+            name: "shrug",
+            usage: "/shrug [message]",
+            summary: "Append ¯\\_(ツ)_/¯",
+            handler: Box::new(|args, _| {
+                let shrug = "¯\\_(ツ)_/¯";
+                Ok(Outcome::Send(if args.is_empty() {
+                    shrug.to_string()
+                } else {
+                    format!("{} {}", args.raw, shrug)
+                }))
+            }),
+        },
+        Command {
+            name: "nick",
+            usage: "/nick <new name>",
+            summary: "Change your username",
+            handler: Box::new(|args, _| Ok(Outcome::Rename(args.single()?.to_string()))),
+        },
+        Command {
+            name: "roll",
+            usage: "/roll [NdM]",
+            summary: "Roll dice, 1d6 by default",
+            handler: Box::new(|args, ctx| {
+                let spec = if args.is_empty() { "1d6" } else { args.raw };
+                let (dice, sides) = parse_dice(spec)?;
+                let rolls: Vec<u32> = (0..dice).map(|_| ctx.rng.gen_range(1..=sides)).collect();
+                let total: u32 = rolls.iter().sum();
+                let shown: Vec<String> = rolls.iter().map(u32::to_string).collect();
+                Ok(Outcome::Send(format!(
+                    "🎲 {} rolled {}d{}: {} = {}",
+                    ctx.username,
+                    dice,
+                    sides,
+                    shown.join(" + "),
+                    total
+                )))
+            }),
+        },
+        Command {
+            name: "banme",
+            usage: "/banme [reason]",
+            summary: "Ban yourself, no waiting for luck",
+            handler: Box::new(|args, _| {
+                Ok(Outcome::BanSelf {
+                    reason: if args.is_empty() {
+                        "asking for it".to_string()
+                    } else {
+                        args.raw.to_string()
+                    },
+                })
+            }),
+        },
+        Command {
+            name: "bot",
+            usage: "/bot create|revoke <name>",
+            summary: "Manage bot accounts (moderators)",
+            handler: Box::new(|args, _| match args.words()[..] {
+                ["create", name] => Ok(Outcome::CreateBot {
+                    name: name.to_string(),
+                }),
+                ["revoke", name] => Ok(Outcome::RevokeBot {
+                    name: name.to_string(),
+                }),
+                _ => Err("Expected create or revoke and a name".to_string()),
+            }),
+        },
+        Command {
+            name: "reasons",
+            usage: "/reasons",
+            summary: "List the ban reasons to collect",
+            handler: Box::new(|_, _| {
+                let lines: Vec<String> = BAN_REASONS
+                    .iter()
+                    .map(|r| format!("\u{2022} {}", r))
+                    .collect();
+                Ok(Outcome::Reply(format!(
+                    "Ban reasons:\n{}",
+                    lines.join("\n")
+                )))
+            }),
+        },
+        Command {
+            name: "stats",
+            usage: "/stats",
+            summary: "Show your bans, reasons, messages and coins",
+            handler: Box::new(|_, ctx| {
+                let stats = &ctx.stats;
+                Ok(Outcome::Reply(format!(
+                    "🔨 {} bans, {}/{} reasons collected, {} messages this session, 🪙 {}",
+                    stats.bans,
+                    stats.reasons_collected,
+                    BAN_REASONS.len(),
+                    stats.messages,
+                    stats.balance
+                )))
+            }),
+        },
+    ]
+}
+
+/// Parses `NdM`, e.g. `2d6`. A missing count means one die.
+fn parse_dice(spec: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("Can't roll {:?}", spec);
+    let spec = spec.trim().to_lowercase();
+    let (dice, sides) = spec.split_once('d').ok_or_else(invalid)?;
+    let dice: u32 = if dice.is_empty() {
+        1
+    } else {
+        dice.parse().map_err(|_| invalid())?
+    };
+    let sides: u32 = sides.parse().map_err(|_| invalid())?;
+    if !(1..=MAX_DICE).contains(&dice) || !(2..=MAX_SIDES).contains(&sides) {
+        return Err(format!(
+            "Between 1 and {} dice of 2 to {} sides",
+            MAX_DICE, MAX_SIDES
+        ));
+    }
+    Ok((dice, sides))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn run(registry: &CommandRegistry, line: &str) -> Result<Outcome, String> {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut ctx = Context {
+            username: "alice",
+            stats: Stats::default(),
+            rng: &mut rng,
+        };
+        match parse(line) {
+            Input::Command { name, args } => registry.run(name, args, &mut ctx),
+            Input::Text(text) => panic!("not a command: {}", text),
+        }
+    }
+
+    #[test]
+    fn parses_commands_and_escapes() {
+        assert_eq!(parse("hello"), Input::Text("hello"));
+        assert_eq!(parse("//not a command"), Input::Text("/not a command"));
+        let Input::Command { name, args } = parse("/me  waves hello ") else {
+            panic!("expected a command");
+        };
+        assert_eq!(name, "me");
+        assert_eq!(args.raw, "waves hello");
+    }
+
+    #[test]
+    fn builtins_produce_outcomes() {
+        let registry = CommandRegistry::default();
+        assert_eq!(
+            run(&registry, "/me waves"),
+            Ok(Outcome::Send("* alice waves".to_string()))
+        );
+        assert_eq!(
+            run(&registry, "/SHRUG ok"),
+            Ok(Outcome::Send("ok ¯\\_(ツ)_/¯".to_string()))
+        );
+        assert_eq!(
+            run(&registry, "/nick bob"),
+            Ok(Outcome::Rename("bob".to_string()))
+        );
+        assert!(matches!(
+            run(&registry, "/banme"),
+            Ok(Outcome::BanSelf { .. })
+        ));
+        assert!(run(&registry, "/nick")
+            .unwrap_err()
+            .contains("Usage: /nick"));
+        assert!(run(&registry, "/nope")
+            .unwrap_err()
+            .contains("Unknown command"));
+    }
+
+    #[test]
+    fn rolls_stay_in_range() {
+        let registry = CommandRegistry::default();
+        let Ok(Outcome::Send(text)) = run(&registry, "/roll 3d4") else {
+            panic!("expected a roll");
+        };
+        let total: u32 = text.rsplit(' ').next().unwrap().parse().unwrap();
+        assert!((3..=12).contains(&total), "{}", text);
+
+        assert_eq!(parse_dice("d20"), Ok((1, 20)));
+        assert!(parse_dice("0d6").is_err());
+        assert!(parse_dice("2x6").is_err());
+    }
+
+    #[test]
+    fn plugins_register_and_autocomplete() {
+        let mut registry = CommandRegistry::default();
+        registry
+            .register(Command {
+                name: "rules",
+                usage: "/rules",
+                summary: "There are no rules",
+                handler: Box::new(|_, _| Ok(Outcome::Reply("There are no rules.".to_string()))),
+            })
+            .unwrap();
+        assert_eq!(
+            run(&registry, "/rules"),
+            Ok(Outcome::Reply("There are no rules.".to_string()))
+        );
+
+        let names: Vec<&str> = registry.suggest("/r").iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["reasons", "roll", "rules"]);
+
+        let duplicate = Command {
+            name: "Roll",
+            usage: "/roll",
+            summary: "",
+            handler: Box::new(|_, _| Ok(Outcome::Reply(String::new()))),
+        };
+        assert!(registry.register(duplicate).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Coins earned for every message that reaches the server.
+pub const MESSAGE_REWARD: i64 = 2;
+/// Coins earned for every ban roll survived.
+pub const SURVIVAL_REWARD: i64 = 1;
+
+const SHIELD_DURATION_SECS: i64 = 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Item {
+    ImmunityShield,
+    BanHalving,
+    BanRedirect,
+}
+
+impl Item {
+    pub const ALL: [Item; 3] = [Item::ImmunityShield, Item::BanHalving, Item::BanRedirect];
+
+    pub fn price(self) -> i64 {
+        match self {
+            Item::ImmunityShield => 60,
+            Item::BanHalving => 25,
+            Item::BanRedirect => 100,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Item::ImmunityShield => "Immunity shield",
+            Item::BanHalving => "Ban halving",
+            Item::BanRedirect => "Ban redirect",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Item::ImmunityShield => "No bans for 60 seconds",
+            Item::BanHalving => "Your next ban lasts half as long",
+            Item::BanRedirect => "Your next ban hits a random other user instead",
+        }
+    }
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Item::ImmunityShield => "immunity_shield",
+            Item::BanHalving => "ban_halving",
+            Item::BanRedirect => "ban_redirect",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Item> {
+        Item::ALL.into_iter().find(|item| item.key() == key)
+    }
+}
+
+/// An item in use. Stored in `active_effects`, so it outlasts the session.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ActiveEffect {
+    pub item: Item,
+    /// `None` for effects that last until the next ban consumes them.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// What the active effects do to a ban that was just rolled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BanIntercept {
+    None,
+    Blocked,
+    Halved,
+    Redirected,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Wallet {
+    pub balance: i64,
+    pub inventory: HashMap<Item, u32>,
+    pub effects: Vec<ActiveEffect>,
+}
+
+impl Wallet {
+    pub fn earn(&mut self, amount: i64) {
+        self.balance += amount;
+    }
+
+    pub fn buy(&mut self, item: Item) -> Result<(), String> {
+        if self.balance < item.price() {
+            return Err(format!(
+                "{} costs {} coins, you have {}",
+                item.name(),
+                item.price(),
+                self.balance
+            ));
+        }
+        self.balance -= item.price();
+        *self.inventory.entry(item).or_insert(0) += 1;
+        Ok(())
+    }
+
+    pub fn use_item(&mut self, item: Item, now: DateTime<Utc>) -> Result<(), String> {
+        self.expire_effects(now);
+        if self.effects.iter().any(|e| e.item == item) {
+            return Err(format!("{} is already active", item.name()));
+        }
+
+        let count = self.inventory.get_mut(&item).filter(|c| **c > 0);
+        let Some(count) = count else {
+            return Err(format!("You don't own a {}", item.name()));
+        };
+        *count -= 1;
+
+        let expires_at = match item {
+            Item::ImmunityShield => Some(now + Duration::seconds(SHIELD_DURATION_SECS)),
+            Item::BanHalving | Item::BanRedirect => None,
+        };
+        self.effects.push(ActiveEffect { item, expires_at });
+        Ok(())
+    }
+
+    /// Decides how a freshly rolled ban is handled and consumes the effect
+    /// responsible. A shield wins over a redirect, which wins over halving.
+    pub fn intercept_ban(&mut self, now: DateTime<Utc>) -> BanIntercept {
+        self.expire_effects(now);
+
+        if self.effects.iter().any(|e| e.item == Item::ImmunityShield) {
+            return BanIntercept::Blocked;
+        }
+        if self.take_effect(Item::BanRedirect) {
+            return BanIntercept::Redirected;
+        }
+        if self.take_effect(Item::BanHalving) {
+            return BanIntercept::Halved;
+        }
+        BanIntercept::None
+    }
+
+    fn take_effect(&mut self, item: Item) -> bool {
+        match self.effects.iter().position(|e| e.item == item) {
+            Some(index) => {
+                self.effects.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expire_effects(&mut self, now: DateTime<Utc>) {
+        self.effects
+            .retain(|e| e.expires_at.is_none_or(|expires| expires > now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wallet(balance: i64, items: &[Item]) -> Wallet {
+        let mut wallet = Wallet {
+            balance,
+            ..Wallet::default()
+        };
+        for item in items {
+            *wallet.inventory.entry(*item).or_insert(0) += 1;
+        }
+        wallet
+    }
+
+    #[test]
+    fn buying_needs_the_coins() {
+        let mut wallet = wallet(Item::BanHalving.price() - 1, &[]);
+        assert!(wallet.buy(Item::BanHalving).unwrap_err().contains("costs"));
+        assert_eq!(wallet.balance, Item::BanHalving.price() - 1);
+        assert!(wallet.inventory.is_empty());
+
+        wallet.earn(1);
+        wallet.buy(Item::BanHalving).unwrap();
+        assert_eq!(wallet.balance, 0);
+        assert_eq!(wallet.inventory[&Item::BanHalving], 1);
+    }
+
+    #[test]
+    fn using_an_item_spends_it_once() {
+        let now = Utc::now();
+        let mut wallet = wallet(0, &[Item::BanRedirect]);
+        assert!(wallet.use_item(Item::BanHalving, now).is_err());
+
+        wallet.use_item(Item::BanRedirect, now).unwrap();
+        assert_eq!(wallet.inventory[&Item::BanRedirect], 0);
+        assert_eq!(wallet.effects.len(), 1);
+        assert!(wallet.use_item(Item::BanRedirect, now).is_err());
+    }
+
+    #[test]
+    fn bans_use_up_redirects_before_halvings() {
+        let now = Utc::now();
+        let mut wallet = wallet(0, &[Item::BanHalving, Item::BanRedirect]);
+        wallet.use_item(Item::BanHalving, now).unwrap();
+        wallet.use_item(Item::BanRedirect, now).unwrap();
+
+        assert_eq!(wallet.intercept_ban(now), BanIntercept::Redirected);
+        assert_eq!(wallet.intercept_ban(now), BanIntercept::Halved);
+        assert_eq!(wallet.intercept_ban(now), BanIntercept::None);
+        assert!(wallet.effects.is_empty());
+    }
+
+    #[test]
+    fn shields_block_every_ban_until_they_expire() {
+        let now = Utc::now();
+        let mut wallet = wallet(0, &[Item::ImmunityShield, Item::BanHalving]);
+        wallet.use_item(Item::ImmunityShield, now).unwrap();
+        wallet.use_item(Item::BanHalving, now).unwrap();
+
+        assert_eq!(wallet.intercept_ban(now), BanIntercept::Blocked);
+        assert_eq!(wallet.intercept_ban(now), BanIntercept::Blocked);
+        let later = now + Duration::seconds(SHIELD_DURATION_SECS);
+        assert_eq!(wallet.intercept_ban(later), BanIntercept::Halved);
+    }
+}
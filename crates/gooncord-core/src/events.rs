@@ -0,0 +1,207 @@
+use serde::Serialize;
+
+use crate::achievements::{Achievement, AchievementTracker};
+use crate::app::{Bookmark, IncomingWebhook, OutgoingWebhook, Pin, Profile};
+use crate::commands::Suggestion;
+use crate::economy::{Item, Wallet};
+use crate::outbox::{OutboxEntry, OutboxStatus};
+use crate::search::SearchResults;
+use crate::ReceivedMessage;
+use uuid::Uuid;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Badge {
+    pub badge: &'static str,
+    pub name: &'static str,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ShopItem {
+    pub item: Item,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub price: i64,
+}
+
+impl ShopItem {
+    pub fn catalog() -> Vec<ShopItem> {
+        Item::ALL
+            .iter()
+            .map(|&item| ShopItem {
+                item,
+                name: item.name(),
+                description: item.description(),
+                price: item.price(),
+            })
+            .collect()
+    }
+}
+
+/// Everything Rust tells the UI. Serialized as JSON and handed to
+/// `window.gooncord.dispatch` in `index.html`, which switches on `type`.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum UiEvent {
+    Message {
+        id: String,
+        seq: i64,
+        client_id: Option<Uuid>,
+        username: String,
+        avatar: String,
+        content: String,
+        created_at: String,
+    },
+    /// One of our own messages that hasn't reached the server yet.
+    OutgoingMessage {
+        client_id: Uuid,
+        username: String,
+        avatar: String,
+        content: String,
+        created_at: String,
+        status: OutboxStatus,
+    },
+    MessageDelivered {
+        client_id: Uuid,
+    },
+    MessageFailed {
+        client_id: Uuid,
+    },
+    SystemMessage {
+        text: String,
+    },
+    ClearMessages,
+    ShowAuthForms,
+    HideAuthForms,
+    EnableInput,
+    BanStarted {
+        reason: String,
+        seconds: i64,
+    },
+    BanTick {
+        seconds: i64,
+    },
+    BanCleared,
+    SpectatorOnly {
+        seconds: i64,
+    },
+    AchievementUnlocked {
+        badge: &'static str,
+        name: &'static str,
+        description: &'static str,
+    },
+    Badges {
+        badges: Vec<Badge>,
+    },
+    Wallet {
+        wallet: Wallet,
+        shop: Vec<ShopItem>,
+    },
+    EventAnnouncement {
+        text: String,
+    },
+    ReactionAdded {
+        message_id: String,
+        emoji: String,
+    },
+    Profile {
+        profile: Profile,
+    },
+    SearchResults {
+        results: SearchResults,
+    },
+    /// What the current user may do beyond chatting.
+    Permissions {
+        is_moderator: bool,
+    },
+    Pins {
+        pins: Vec<Pin>,
+    },
+    Bookmarks {
+        bookmarks: Vec<Bookmark>,
+    },
+    /// Where the "new messages" divider goes and how many are unread.
+    UnreadMarker {
+        last_read_seq: Option<i64>,
+        unread: u64,
+    },
+    SeenBy {
+        seq: i64,
+        usernames: Vec<String>,
+    },
+    /// Slash commands matching what's typed so far.
+    CommandSuggestions {
+        suggestions: Vec<Suggestion>,
+    },
+    Webhooks {
+        incoming: Vec<IncomingWebhook>,
+        outgoing: Vec<OutgoingWebhook>,
+    },
+}
+
+impl UiEvent {
+    pub fn system(text: impl Into<String>) -> Self {
+        UiEvent::SystemMessage { text: text.into() }
+    }
+
+    pub fn message(msg: &ReceivedMessage) -> Self {
+        UiEvent::Message {
+            id: msg.id.clone(),
+            seq: msg.seq,
+            client_id: msg.client_id,
+            username: msg.username.clone(),
+            avatar: msg.username.chars().next().unwrap_or('?').to_string(),
+            content: msg.content.clone(),
+            created_at: msg.created_at.clone(),
+        }
+    }
+
+    pub fn outgoing(entry: &OutboxEntry, username: &str) -> Self {
+        UiEvent::OutgoingMessage {
+            client_id: entry.client_id,
+            username: username.to_string(),
+            avatar: username.chars().next().unwrap_or('?').to_string(),
+            content: entry.content.clone(),
+            created_at: entry.created_at.to_rfc3339(),
+            status: entry.status,
+        }
+    }
+
+    pub fn achievement(achievement: &Achievement) -> Self {
+        UiEvent::AchievementUnlocked {
+            badge: achievement.badge,
+            name: achievement.name,
+            description: achievement.description,
+        }
+    }
+
+    pub fn badges(tracker: &AchievementTracker) -> Self {
+        UiEvent::Badges {
+            badges: tracker
+                .unlocked()
+                .map(|a| Badge {
+                    badge: a.badge,
+                    name: a.name,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn wallet(wallet: &Wallet) -> Self {
+        UiEvent::Wallet {
+            wallet: wallet.clone(),
+            shop: ShopItem::catalog(),
+        }
+    }
+}
+
+/// Serializes `event` into a single `window.gooncord.dispatch(...)` call.
+///
+/// JSON is a JavaScript expression except that U+2028 and U+2029 are legal
+/// inside JSON strings but end a line in older JS engines, so those are
+/// escaped as well.
+pub fn to_script(event: &UiEvent) -> Result<String, serde_json::Error> {
+    let json = serde_json::to_string(event)?
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029");
+    Ok(format!("window.gooncord.dispatch({});", json))
+}
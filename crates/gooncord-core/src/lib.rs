@@ -0,0 +1,107 @@
+//! Everything Gooncord does, minus the window: the backend connection,
+//! accounts, bans, messages and the [`app::AppState`] that ties them
+//! together. UIs send it [`app::WebMessage`]s and show the
+//! [`events::UiEvent`]s it answers with. Builds without GTK or WebKit.
+
+pub mod achievements;
+pub mod app;
+pub mod auth;
+pub mod backend;
+pub mod ban_events;
+pub mod bans;
+pub mod cache;
+pub mod chance;
+pub mod client;
+pub mod commands;
+pub mod economy;
+pub mod events;
+pub mod migrations;
+pub mod outbox;
+pub mod postgrest;
+pub mod receipts;
+pub mod search;
+pub mod sequencer;
+pub mod shared;
+pub mod spectator;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub use auth::Session;
+pub use backend::Backend;
+pub use bans::Ban;
+pub use client::ApiClient;
+
+pub const BAN_REASONS: &[&str] = &[
+    "illegal emoji usage",
+    "excessive happiness",
+    "breathing too loudly",
+    "suspicious typing patterns",
+    "the Random Ban God's will",
+    "using Comic Sans unironically",
+    "sending messages too fast",
+    "sending messages too slow",
+    "liking pineapple on pizza",
+    "disliking pineapple on pizza",
+    "existing",
+    "spelling mistakes",
+    "capitalizing every word",
+    "using too many exclamation marks!!!",
+    "being too polite",
+    "being too rude",
+    "suspicious silence",
+    "laughing too much",
+    "not laughing enough",
+    "incorrect opinion detected",
+    "overusing GIFs",
+    "speaking forbidden languages",
+    "sending cursed images",
+    "being a bot (maybe)",
+    "having a suspiciously cool username",
+    "having no profile picture",
+    "having too many profile pictures",
+    "sending memes at 3 AM",
+    "excessive lurking",
+    "breathing in Morse code",
+    "using tabs instead of spaces",
+    "using spaces instead of tabs",
+    "being too smart",
+    "being too dumb",
+    "using forbidden words",
+    "using forbidden thoughts",
+    "being suspiciously normal",
+    "changing nicknames too often",
+    "having a lucky day",
+    "having an unlucky day",
+    "responding to bots",
+    "arguing with moderators",
+    "being too relatable",
+    "winning too many arguments",
+    "losing too many arguments",
+    "complaining about random bans",
+    "random quantum fluctuations",
+    "karma imbalance detected",
+    "the server hamster tripped",
+    "too much drip",
+    "not enough drip",
+    "interdimensional travel violations",
+];
+
+/// The only channel there is, for now.
+pub const DEFAULT_CHANNEL: &str = "general";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReceivedMessage {
+    #[serde(default)]
+    pub id: String,
+    /// Server-assigned position in the channel; messages are shown in this
+    /// order.
+    #[serde(default)]
+    pub seq: i64,
+    pub content: String,
+    pub username: String,
+    pub created_at: String,
+    pub user_id: String,
+    #[serde(default)]
+    pub client_id: Option<Uuid>,
+}
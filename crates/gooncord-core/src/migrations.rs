@@ -0,0 +1,133 @@
+//! The database schema, as numbered SQL migrations under `migrations/`.
+//!
+//! `gooncord-server migrate` applies them to any Postgres, Supabase's
+//! included, and records each in `schema_migrations`. Clients check that
+//! table on startup and refuse to run against a schema older than the one
+//! they were built for.
+
+/// One file under `migrations/`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every migration, oldest first. Versions only ever grow; a released
+/// migration is never edited, only followed by another.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "accounts_and_messages",
+        sql: include_str!("../migrations/0001_accounts_and_messages.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "extras",
+        sql: include_str!("../migrations/0002_extras.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "bots_and_webhooks",
+        sql: include_str!("../migrations/0003_bots_and_webhooks.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "row_level_security",
+        sql: include_str!("../migrations/0004_row_level_security.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "active_effects",
+        sql: include_str!("../migrations/0005_active_effects.sql"),
+    },
+];
+
+/// The schema version this build expects: the newest migration's.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
+
+/// Where applied migrations are recorded. The runner creates it before
+/// anything else, so the first migration can already refer to it.
+pub const CREATE_VERSION_TABLE: &str = "CREATE TABLE IF NOT EXISTS schema_migrations (
+    version bigint PRIMARY KEY,
+    name text NOT NULL,
+    applied_at timestamptz NOT NULL DEFAULT now()
+)";
+
+/// The migrations not yet in `applied`, in the order to run them.
+pub fn pending(applied: &[i64]) -> Vec<&'static Migration> {
+    MIGRATIONS
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect()
+}
+
+/// Whether this build can use a database at schema `version` (`None` if it
+/// was never migrated). Migrations only add, so newer schemas are fine.
+pub fn check_compatible(version: Option<i64>) -> Result<(), String> {
+    match version {
+        Some(version) if version >= SCHEMA_VERSION => Ok(()),
+        Some(version) => Err(format!(
+            "The database schema is at version {} but this Gooncord needs {}; \
+             run `gooncord-server migrate` against it",
+            version, SCHEMA_VERSION
+        )),
+        None => Err(
+            "The database has no Gooncord schema; run `gooncord-server migrate` against it"
+                .to_string(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_consecutive() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, index as i64 + 1, "{}", migration.name);
+        }
+        assert_eq!(pending(&[]).len(), MIGRATIONS.len());
+        assert_eq!(pending(&[1, 2]).first().map(|m| m.version), Some(3));
+        assert!(pending(&(1..=SCHEMA_VERSION).collect::<Vec<_>>()).is_empty());
+    }
+
+    #[test]
+    fn older_schemas_are_rejected() {
+        assert!(check_compatible(Some(SCHEMA_VERSION)).is_ok());
+        assert!(check_compatible(Some(SCHEMA_VERSION + 1)).is_ok());
+        assert!(check_compatible(Some(SCHEMA_VERSION - 1)).is_err());
+        assert!(check_compatible(None).is_err());
+    }
+
+    /// Everything the client and bot query, so a table can't go missing
+    /// from the migrations unnoticed.
+    #[test]
+    fn creates_every_table_gooncord_uses() {
+        let schema: String = MIGRATIONS.iter().map(|m| m.sql).collect();
+        for table in [
+            "users",
+            "sessions",
+            "messages",
+            "bans",
+            "achievements",
+            "wallets",
+            "inventory",
+            "active_effects",
+            "ban_events",
+            "reactions",
+            "pins",
+            "bookmarks",
+            "read_cursors",
+            "bot_tokens",
+            "incoming_webhooks",
+            "outgoing_webhooks",
+        ] {
+            let create = format!("CREATE TABLE IF NOT EXISTS {} (", table);
+            assert!(schema.contains(&create), "{} is never created", table);
+            let rls = format!("ALTER TABLE {} ENABLE ROW LEVEL SECURITY", table);
+            assert!(schema.contains(&rls), "{} has no row-level security", table);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Send attempts before a message is marked failed and left for the user
+/// to retry by hand.
+pub const MAX_ATTEMPTS: u32 = 5;
+
+const BASE_BACKOFF_SECS: i64 = 2;
+const MAX_BACKOFF_SECS: i64 = 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    Pending,
+    Failed,
+}
+
+/// A message that hasn't reached the server yet. `client_id` is generated
+/// locally and sent along so the server can drop retries it already has.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutboxEntry {
+    pub client_id: Uuid,
+    pub user_id: Uuid,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub status: OutboxStatus,
+}
+
+/// Unsent messages, kept on disk so they survive a restart.
+#[derive(Debug, Default)]
+pub struct Outbox {
+    path: Option<PathBuf>,
+    entries: Vec<OutboxEntry>,
+}
+
+impl Outbox {
+    /// Loads the outbox stored at `path`, starting empty if there is none.
+    /// Without a path the outbox only lives in memory.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let entries = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Outbox { path, entries }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to save outbox: {}", e))?;
+        }
+        let json = serde_json::to_string(&self.entries).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| format!("Failed to save outbox: {}", e))
+    }
+
+    pub fn enqueue(&mut self, user_id: Uuid, content: &str, now: DateTime<Utc>) -> OutboxEntry {
+        let entry = OutboxEntry {
+            client_id: Uuid::new_v4(),
+            user_id,
+            content: content.to_string(),
+            created_at: now,
+            attempts: 0,
+            next_attempt_at: now,
+            status: OutboxStatus::Pending,
+        };
+        self.entries.push(entry.clone());
+        entry
+    }
+
+    /// Everything queued by `user_id`, oldest first.
+    pub fn for_user(&self, user_id: Uuid) -> impl Iterator<Item = &OutboxEntry> {
+        self.entries.iter().filter(move |e| e.user_id == user_id)
+    }
+
+    /// Pending messages of `user_id` whose backoff has run out.
+    pub fn due(&self, user_id: Uuid, now: DateTime<Utc>) -> Vec<OutboxEntry> {
+        self.for_user(user_id)
+            .filter(|e| e.status == OutboxStatus::Pending && e.next_attempt_at <= now)
+            .cloned()
+            .collect()
+    }
+
+    /// Drops a message the server has accepted.
+    pub fn delivered(&mut self, client_id: Uuid) {
+        self.entries.retain(|e| e.client_id != client_id);
+    }
+
+    /// Backs off exponentially after a failed attempt and gives up after
+    /// [`MAX_ATTEMPTS`]. Returns the entry's new status.
+    pub fn record_failure(&mut self, client_id: Uuid, now: DateTime<Utc>) -> Option<OutboxStatus> {
+        let entry = self.entries.iter_mut().find(|e| e.client_id == client_id)?;
+        entry.attempts += 1;
+        if entry.attempts >= MAX_ATTEMPTS {
+            entry.status = OutboxStatus::Failed;
+        } else {
+            entry.next_attempt_at = now + backoff(entry.attempts);
+        }
+        Some(entry.status)
+    }
+
+    /// Puts a failed message back in the queue with a fresh set of attempts.
+    pub fn retry(&mut self, client_id: Uuid, now: DateTime<Utc>) -> Option<&OutboxEntry> {
+        let entry = self.entries.iter_mut().find(|e| e.client_id == client_id)?;
+        entry.attempts = 0;
+        entry.next_attempt_at = now;
+        entry.status = OutboxStatus::Pending;
+        Some(entry)
+    }
+}
+
+fn backoff(attempts: u32) -> Duration {
+    let secs = BASE_BACKOFF_SECS.saturating_mul(1 << attempts.saturating_sub(1).min(16));
+    Duration::seconds(secs.min(MAX_BACKOFF_SECS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let delays: Vec<i64> = (1..=7).map(|n| backoff(n).num_seconds()).collect();
+        assert_eq!(delays, vec![2, 4, 8, 16, 32, 60, 60]);
+    }
+
+    #[test]
+    fn failed_send_waits_for_backoff() {
+        let user = Uuid::new_v4();
+        let mut outbox = Outbox::default();
+        let entry = outbox.enqueue(user, "hi", at(0));
+        assert_eq!(outbox.due(user, at(0)).len(), 1);
+
+        assert_eq!(
+            outbox.record_failure(entry.client_id, at(0)),
+            Some(OutboxStatus::Pending)
+        );
+        assert!(outbox.due(user, at(1)).is_empty());
+        assert_eq!(outbox.due(user, at(2)).len(), 1);
+        assert!(outbox.due(Uuid::new_v4(), at(2)).is_empty());
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts_until_retried() {
+        let user = Uuid::new_v4();
+        let mut outbox = Outbox::default();
+        let id = outbox.enqueue(user, "hi", at(0)).client_id;
+
+        for _ in 1..MAX_ATTEMPTS {
+            assert_eq!(
+                outbox.record_failure(id, at(0)),
+                Some(OutboxStatus::Pending)
+            );
+        }
+        assert_eq!(outbox.record_failure(id, at(0)), Some(OutboxStatus::Failed));
+        assert!(outbox.due(user, at(1000)).is_empty());
+
+        outbox.retry(id, at(1000));
+        assert_eq!(outbox.due(user, at(1000)).len(), 1);
+
+        outbox.delivered(id);
+        assert_eq!(outbox.for_user(user).count(), 0);
+    }
+
+    #[test]
+    fn survives_a_restart() {
+        let path = std::env::temp_dir().join(format!("gooncord-outbox-{}.json", Uuid::new_v4()));
+        let user = Uuid::new_v4();
+
+        let mut outbox = Outbox::load(Some(path.clone()));
+        let id = outbox.enqueue(user, "still here", at(0)).client_id;
+        outbox.save().unwrap();
+
+        let reloaded = Outbox::load(Some(path.clone()));
+        let entries: Vec<_> = reloaded.for_user(user).collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].client_id, id);
+        assert_eq!(entries[0].content, "still here");
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,246 @@
+//! PostgREST query strings, built a filter at a time.
+//!
+//! Table names, columns, `select` lists and the like are `&'static str`:
+//! they come from the code, never from users. Values are what users get to
+//! say, so every one is percent-encoded, and quoted too where PostgREST
+//! would otherwise read commas or parentheses in it as syntax. A username
+//! like `a&is_moderator=eq.true` stays one value instead of a second filter.
+
+use std::fmt::{self, Display};
+
+/// Comparison operators for [`RestQuery::filter`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Neq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl Op {
+    fn as_str(self) -> &'static str {
+        match self {
+            Op::Eq => "eq",
+            Op::Neq => "neq",
+            Op::Gt => "gt",
+            Op::Gte => "gte",
+            Op::Lt => "lt",
+            Op::Lte => "lte",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+/// A table and what to ask of it, e.g.
+/// `RestQuery::table("users").eq("username", name).select("id")`. Displays
+/// as the path [`crate::ApiClient`] appends to `/rest/v1/`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestQuery {
+    table: &'static str,
+    params: Vec<(&'static str, String)>,
+}
+
+impl RestQuery {
+    pub fn table(table: &'static str) -> Self {
+        RestQuery {
+            table,
+            params: Vec::new(),
+        }
+    }
+
+    /// `column=op.value`.
+    pub fn filter(self, column: &'static str, op: Op, value: impl Display) -> Self {
+        self.param(
+            column,
+            format!("{}.{}", op.as_str(), encode(&value.to_string())),
+        )
+    }
+
+    pub fn eq(self, column: &'static str, value: impl Display) -> Self {
+        self.filter(column, Op::Eq, value)
+    }
+
+    pub fn neq(self, column: &'static str, value: impl Display) -> Self {
+        self.filter(column, Op::Neq, value)
+    }
+
+    pub fn gt(self, column: &'static str, value: impl Display) -> Self {
+        self.filter(column, Op::Gt, value)
+    }
+
+    pub fn gte(self, column: &'static str, value: impl Display) -> Self {
+        self.filter(column, Op::Gte, value)
+    }
+
+    pub fn lt(self, column: &'static str, value: impl Display) -> Self {
+        self.filter(column, Op::Lt, value)
+    }
+
+    /// `column=is.true`, `is.false` or, for `None`, `is.null`.
+    pub fn is(self, column: &'static str, value: Option<bool>) -> Self {
+        let value = match value {
+            Some(true) => "true",
+            Some(false) => "false",
+            None => "null",
+        };
+        self.param(column, format!("is.{}", value))
+    }
+
+    /// `column=in.("a","b")`: rows whose `column` is any of `values`.
+    pub fn in_list<T: Display>(self, column: &'static str, values: &[T]) -> Self {
+        self.param(column, format!("in.{}", list(values)))
+    }
+
+    /// `column=not.in.("a","b")`: rows whose `column` is none of `values`.
+    pub fn not_in<T: Display>(self, column: &'static str, values: &[T]) -> Self {
+        self.param(column, format!("not.in.{}", list(values)))
+    }
+
+    /// `column=wfts(config).text`: full-text search with web search syntax
+    /// (`"exact phrase"`, `or`, `-excluded`).
+    pub fn full_text(self, column: &'static str, config: &'static str, text: &str) -> Self {
+        self.param(column, format!("wfts({}).{}", config, encode(text)))
+    }
+
+    /// The columns to return, embeds included, e.g. `id,users(username)`.
+    pub fn select(self, columns: &'static str) -> Self {
+        self.param("select", columns.to_string())
+    }
+
+    /// Sorts by `column`; called again, sorts ties by the next column.
+    pub fn order(mut self, column: &'static str, order: Order) -> Self {
+        let order = match order {
+            Order::Asc => format!("{}.asc", column),
+            Order::Desc => format!("{}.desc", column),
+        };
+        match self.params.iter_mut().find(|(key, _)| *key == "order") {
+            Some((_, value)) => {
+                value.push(',');
+                value.push_str(&order);
+            }
+            None => self.params.push(("order", order)),
+        }
+        self
+    }
+
+    pub fn limit(self, limit: usize) -> Self {
+        self.param("limit", limit.to_string())
+    }
+
+    pub fn offset(self, offset: usize) -> Self {
+        self.param("offset", offset.to_string())
+    }
+
+    /// The unique columns an upsert resolves conflicts on, e.g.
+    /// `user_id,channel`.
+    pub fn on_conflict(self, columns: &'static str) -> Self {
+        self.param("on_conflict", columns.to_string())
+    }
+
+    fn param(mut self, key: &'static str, value: String) -> Self {
+        self.params.push((key, value));
+        self
+    }
+}
+
+impl Display for RestQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.table)?;
+        for (index, (key, value)) in self.params.iter().enumerate() {
+            let separator = if index == 0 { '?' } else { '&' };
+            write!(f, "{}{}={}", separator, key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Percent-encodes everything but letters, digits and `-_.~`.
+pub fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// `("a","b")`, each value double-quoted (with `"` and `\` escaped) so
+/// commas and parentheses in it aren't taken for list syntax, then encoded.
+fn list<T: Display>(values: &[T]) -> String {
+    let quoted: Vec<String> = values
+        .iter()
+        .map(|value| {
+            let value = value.to_string().replace('\\', "\\\\").replace('"', "\\\"");
+            encode(&format!("\"{}\"", value))
+        })
+        .collect();
+    format!("({})", quoted.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_cannot_add_filters() {
+        let query = RestQuery::table("users")
+            .eq("username", "a&is_moderator=eq.true")
+            .select("id,username");
+        assert_eq!(
+            query.to_string(),
+            "users?username=eq.a%26is_moderator%3Deq.true&select=id,username"
+        );
+        assert_eq!(
+            RestQuery::table("bans")
+                .gt("expires_at", "2024-01-01T00:00:00+00:00")
+                .to_string(),
+            "bans?expires_at=gt.2024-01-01T00%3A00%3A00%2B00%3A00"
+        );
+    }
+
+    #[test]
+    fn lists_quote_their_values() {
+        let query = RestQuery::table("messages")
+            .in_list("id", &["1", "a,b", "c\"d"])
+            .not_in("channel", &["(x)"]);
+        assert_eq!(
+            query.to_string(),
+            "messages?id=in.(%221%22,%22a%2Cb%22,%22c%5C%22d%22)\
+             &channel=not.in.(%22%28x%29%22)"
+        );
+    }
+
+    #[test]
+    fn builds_the_rest_of_the_query() {
+        let query = RestQuery::table("messages")
+            .is("pinned", None)
+            .full_text("content", "english", "cats -dogs")
+            .order("seq", Order::Desc)
+            .order("id", Order::Asc)
+            .limit(21)
+            .offset(40);
+        assert_eq!(
+            query.to_string(),
+            "messages?pinned=is.null&content=wfts(english).cats%20-dogs\
+             &order=seq.desc,id.asc&limit=21&offset=40"
+        );
+        assert_eq!(RestQuery::table("users").to_string(), "users");
+        assert_eq!(
+            RestQuery::table("pins")
+                .on_conflict("message_id")
+                .to_string(),
+            "pins?on_conflict=message_id"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use serde::Serialize;
+
+/// "Seen by" is only worked out for channels with at most this many
+/// readers; beyond that the list stops being useful.
+pub const SEEN_BY_MAX_READERS: usize = 10;
+
+/// How far one user has read a channel.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReadCursor {
+    pub username: String,
+    pub last_read_seq: i64,
+}
+
+/// The total from a PostgREST `Content-Range` header such as `0-24/57` or
+/// `*/0`. `None` if the server didn't count (`0-24/*`).
+pub fn parse_total(content_range: &str) -> Option<u64> {
+    content_range.rsplit_once('/')?.1.parse().ok()
+}
+
+/// Who besides `me` has read up to `seq`, or `None` if the channel has too
+/// many readers to say.
+pub fn seen_by(cursors: &[ReadCursor], seq: i64, me: &str) -> Option<Vec<String>> {
+    if cursors.len() > SEEN_BY_MAX_READERS {
+        return None;
+    }
+    let mut names: Vec<String> = cursors
+        .iter()
+        .filter(|c| c.last_read_seq >= seq && c.username != me)
+        .map(|c| c.username.clone())
+        .collect();
+    names.sort();
+    Some(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(username: &str, last_read_seq: i64) -> ReadCursor {
+        ReadCursor {
+            username: username.to_string(),
+            last_read_seq,
+        }
+    }
+
+    #[test]
+    fn parses_content_range_totals() {
+        assert_eq!(parse_total("0-24/57"), Some(57));
+        assert_eq!(parse_total("*/0"), Some(0));
+        assert_eq!(parse_total("0-24/*"), None);
+        assert_eq!(parse_total("garbage"), None);
+    }
+
+    #[test]
+    fn seen_by_lists_other_readers_past_the_message() {
+        let cursors = vec![
+            cursor("carol", 12),
+            cursor("me", 20),
+            cursor("alice", 10),
+            cursor("bob", 9),
+        ];
+        assert_eq!(
+            seen_by(&cursors, 10, "me"),
+            Some(vec!["alice".to_string(), "carol".to_string()])
+        );
+        assert_eq!(seen_by(&cursors, 30, "me"), Some(vec![]));
+    }
+
+    #[test]
+    fn seen_by_gives_up_on_big_channels() {
+        let cursors: Vec<_> = (0..=SEEN_BY_MAX_READERS)
+            .map(|i| cursor(&format!("user{}", i), 100))
+            .collect();
+        assert_eq!(seen_by(&cursors, 1, "me"), None);
+    }
+}
@@ -0,0 +1,253 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::postgrest::{Order, RestQuery};
+use crate::ReceivedMessage;
+
+/// Results per page.
+pub const PAGE_SIZE: usize = 20;
+
+/// A search as typed into the search bar. Every filter is optional; `text`
+/// uses web search syntax (`"exact phrase"`, `or`, `-excluded`).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub channel: Option<String>,
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub page: usize,
+}
+
+impl SearchQuery {
+    pub fn is_empty(&self) -> bool {
+        self.terms().is_empty()
+            && self.author.is_none()
+            && self.channel.is_none()
+            && self.from.is_none()
+            && self.to.is_none()
+    }
+
+    /// The words worth highlighting: lowercased, without quotes, `or` and
+    /// excluded (`-word`) terms.
+    pub fn terms(&self) -> Vec<String> {
+        self.text
+            .split_whitespace()
+            .map(|word| word.trim_matches('"').to_lowercase())
+            .filter(|word| !word.is_empty() && word != "or" && !word.starts_with('-'))
+            .collect()
+    }
+
+    /// The PostgREST query for this search, using Postgres full-text search
+    /// on the message content. One more row than a page is asked for to tell
+    /// whether there is a next page.
+    pub fn postgrest_query(&self) -> RestQuery {
+        let mut query = RestQuery::table("messages")
+            .select("id,seq,channel,content,created_at,users!inner(username)");
+        let text = self.text.trim();
+        if !text.is_empty() {
+            query = query.full_text("content", "english", text);
+        }
+        if let Some(author) = &self.author {
+            query = query.eq("users.username", author);
+        }
+        if let Some(channel) = &self.channel {
+            query = query.eq("channel", channel);
+        }
+        if let Some(from) = self.from {
+            query = query.gte("created_at", from.to_rfc3339());
+        }
+        if let Some(to) = self.to {
+            query = query.lt("created_at", to.to_rfc3339());
+        }
+        query
+            .order("seq", Order::Desc)
+            .limit(PAGE_SIZE + 1)
+            .offset(self.page * PAGE_SIZE)
+    }
+}
+
+/// A piece of a message, highlighted if it matched the search.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub text: String,
+    pub highlight: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SearchHit {
+    pub message_id: String,
+    pub seq: i64,
+    pub channel: String,
+    pub username: String,
+    pub created_at: String,
+    pub segments: Vec<Segment>,
+}
+
+impl SearchHit {
+    pub fn new(msg: &ReceivedMessage, channel: &str, terms: &[String]) -> Self {
+        SearchHit {
+            message_id: msg.id.clone(),
+            seq: msg.seq,
+            channel: channel.to_string(),
+            username: msg.username.clone(),
+            created_at: msg.created_at.clone(),
+            segments: highlight(&msg.content, terms),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SearchResults {
+    pub query: SearchQuery,
+    pub hits: Vec<SearchHit>,
+    pub has_more: bool,
+    /// Searched the local cache because the server couldn't be reached.
+    pub offline: bool,
+}
+
+impl SearchResults {
+    /// Builds a page from up to `PAGE_SIZE + 1` rows, the extra row only
+    /// telling whether more follow.
+    pub fn page(
+        query: SearchQuery,
+        mut rows: Vec<(String, ReceivedMessage)>,
+        offline: bool,
+    ) -> Self {
+        let has_more = rows.len() > PAGE_SIZE;
+        rows.truncate(PAGE_SIZE);
+        let terms = query.terms();
+        let hits = rows
+            .iter()
+            .map(|(channel, msg)| SearchHit::new(msg, channel, &terms))
+            .collect();
+        SearchResults {
+            query,
+            hits,
+            has_more,
+            offline,
+        }
+    }
+}
+
+/// Splits `content` into segments, marking case-insensitive occurrences of
+/// any of `terms`.
+pub fn highlight(content: &str, terms: &[String]) -> Vec<Segment> {
+    let lower = content.to_lowercase();
+    // Lowercasing can change byte lengths; only use match positions when it
+    // didn't, so they line up with the original text.
+    let mut matched = vec![false; content.len()];
+    if lower.len() == content.len() {
+        for term in terms.iter().filter(|t| !t.is_empty()) {
+            for (start, _) in lower.match_indices(term.as_str()) {
+                matched[start..start + term.len()].fill(true);
+            }
+        }
+    }
+
+    let mut segments: Vec<Segment> = Vec::new();
+    for (i, c) in content.char_indices() {
+        let highlight = matched[i];
+        match segments.last_mut() {
+            Some(last) if last.highlight == highlight => last.text.push(c),
+            _ => segments.push(Segment {
+                text: c.to_string(),
+                highlight,
+            }),
+        }
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(text: &str, highlight: bool) -> Segment {
+        Segment {
+            text: text.to_string(),
+            highlight,
+        }
+    }
+
+    #[test]
+    fn terms_skip_operators() {
+        let query = SearchQuery {
+            text: "\"Ban Hammer\" or pizza -pineapple".to_string(),
+            ..SearchQuery::default()
+        };
+        assert_eq!(query.terms(), vec!["ban", "hammer", "pizza"]);
+    }
+
+    #[test]
+    fn highlights_every_match_case_insensitively() {
+        let terms = vec!["ban".to_string()];
+        assert_eq!(
+            highlight("Banned? ban me 🔨", &terms),
+            vec![
+                segment("Ban", true),
+                segment("ned? ", false),
+                segment("ban", true),
+                segment(" me 🔨", false),
+            ]
+        );
+        assert_eq!(
+            highlight("nothing", &terms),
+            vec![segment("nothing", false)]
+        );
+    }
+
+    #[test]
+    fn postgrest_query_includes_filters_and_paging() {
+        let query = SearchQuery {
+            text: "pizza".to_string(),
+            author: Some("alice".to_string()),
+            channel: Some("general".to_string()),
+            from: Some("2024-01-01T00:00:00Z".parse().unwrap()),
+            to: None,
+            page: 2,
+        };
+        assert_eq!(
+            query.postgrest_query().to_string(),
+            "messages?select=id,seq,channel,content,created_at,users!inner(username)\
+             &content=wfts(english).pizza&users.username=eq.alice&channel=eq.general\
+             &created_at=gte.2024-01-01T00%3A00%3A00%2B00%3A00\
+             &order=seq.desc&limit=21&offset=40"
+        );
+    }
+
+    #[test]
+    fn page_reports_whether_more_follow() {
+        let row = |seq: i64| {
+            (
+                "general".to_string(),
+                ReceivedMessage {
+                    id: seq.to_string(),
+                    seq,
+                    client_id: None,
+                    content: "hi".to_string(),
+                    username: "alice".to_string(),
+                    created_at: String::new(),
+                    user_id: String::new(),
+                },
+            )
+        };
+        let full = SearchResults::page(
+            SearchQuery::default(),
+            (0..=PAGE_SIZE as i64).map(row).collect(),
+            false,
+        );
+        assert!(full.has_more);
+        assert_eq!(full.hits.len(), PAGE_SIZE);
+
+        let last = SearchResults::page(SearchQuery::default(), vec![row(1)], true);
+        assert!(!last.has_more);
+        assert!(last.offline);
+    }
+}
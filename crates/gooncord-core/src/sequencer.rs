@@ -0,0 +1,394 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashSet, VecDeque};
+use std::future::Future;
+
+use crate::ReceivedMessage;
+
+/// Messages fetched on the first poll after a reset.
+pub const BACKLOG_SIZE: usize = 20;
+/// Most messages fetched per poll once caught up.
+const PAGE_SIZE: usize = 100;
+/// Ids remembered for deduplication.
+const SEEN_CAPACITY: usize = 1000;
+/// How long to wait before looking for a missing message again.
+const BACKFILL_DELAY_SECS: i64 = 1;
+/// Lookups before a missing message is taken to be deleted or rolled back.
+const MAX_BACKFILL_ATTEMPTS: u32 = 3;
+
+/// What the sequencer asks the backend for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+    /// The newest `limit` messages.
+    Latest { limit: usize },
+    /// Up to `limit` messages with a sequence number above `seq`.
+    After { seq: i64, limit: usize },
+    /// Messages strictly between two sequence numbers.
+    Between { after: i64, before: i64 },
+}
+
+/// A run of sequence numbers we skipped over and haven't seen yet.
+#[derive(Debug, Clone, PartialEq)]
+struct Gap {
+    after: i64,
+    before: i64,
+    attempts: u32,
+    next_attempt_at: DateTime<Utc>,
+}
+
+/// Turns repeated polls into an ordered, duplicate-free message stream.
+///
+/// Messages are keyed by their server id and ordered by the server's
+/// sequence number. A jump in the sequence is remembered as a gap and looked
+/// up again a few times, since a message can commit after a later one.
+#[derive(Debug, Default)]
+pub struct Sequencer {
+    cursor: Option<i64>,
+    seen: HashSet<String>,
+    seen_order: VecDeque<String>,
+    gaps: Vec<Gap>,
+}
+
+impl Sequencer {
+    /// Fetches whatever is new, plus any gaps due for another look, and
+    /// returns the messages not delivered before in sequence order. Fails
+    /// only if the new messages can't be fetched; a failed gap lookup is
+    /// tried again on the next poll.
+    pub async fn poll<F, Fut>(
+        &mut self,
+        now: DateTime<Utc>,
+        mut fetch: F,
+    ) -> Result<Vec<ReceivedMessage>, String>
+    where
+        F: FnMut(Query) -> Fut,
+        Fut: Future<Output = Result<Vec<ReceivedMessage>, String>>,
+    {
+        let query = match self.cursor {
+            None => Query::Latest {
+                limit: BACKLOG_SIZE,
+            },
+            Some(seq) => Query::After {
+                seq,
+                limit: PAGE_SIZE,
+            },
+        };
+        let mut delivered = self.accept(fetch(query).await?, now);
+
+        let due: Vec<Gap> = self
+            .gaps
+            .iter()
+            .filter(|gap| gap.next_attempt_at <= now)
+            .cloned()
+            .collect();
+        for gap in due {
+            let query = Query::Between {
+                after: gap.after,
+                before: gap.before,
+            };
+            match fetch(query).await {
+                Ok(found) => delivered.extend(self.backfill(&gap, found, now)),
+                // What was accepted above is already behind the cursor, so
+                // it goes out regardless; the gap stays due for next poll.
+                Err(e) => {
+                    eprintln!("Failed to backfill messages: {}", e);
+                    break;
+                }
+            }
+        }
+
+        delivered.sort_by_key(|m| m.seq);
+        Ok(delivered)
+    }
+
+    /// Takes a batch from the main query and advances the cursor.
+    fn accept(
+        &mut self,
+        mut batch: Vec<ReceivedMessage>,
+        now: DateTime<Utc>,
+    ) -> Vec<ReceivedMessage> {
+        batch.sort_by_key(|m| m.seq);
+
+        let mut delivered = Vec::new();
+        for msg in batch {
+            if let Some(cursor) = self.cursor {
+                if msg.seq > cursor + 1 {
+                    self.gaps.push(Gap {
+                        after: cursor,
+                        before: msg.seq,
+                        attempts: 0,
+                        next_attempt_at: now + Duration::seconds(BACKFILL_DELAY_SECS),
+                    });
+                }
+            }
+            self.cursor = Some(self.cursor.map_or(msg.seq, |c| c.max(msg.seq)));
+            if self.mark_seen(&msg.id) {
+                delivered.push(msg);
+            }
+        }
+        delivered
+    }
+
+    /// Takes the result of looking a gap up again. Sequence numbers still
+    /// missing stay a gap until they've been looked for often enough.
+    fn backfill(
+        &mut self,
+        gap: &Gap,
+        found: Vec<ReceivedMessage>,
+        now: DateTime<Utc>,
+    ) -> Vec<ReceivedMessage> {
+        self.gaps.retain(|g| g != gap);
+
+        let mut delivered = Vec::new();
+        let mut found_seqs: Vec<i64> = Vec::new();
+        for msg in found {
+            if msg.seq <= gap.after || msg.seq >= gap.before {
+                continue;
+            }
+            found_seqs.push(msg.seq);
+            if self.mark_seen(&msg.id) {
+                delivered.push(msg);
+            }
+        }
+
+        if gap.attempts + 1 < MAX_BACKFILL_ATTEMPTS {
+            found_seqs.sort_unstable();
+            let mut after = gap.after;
+            for seq in found_seqs.into_iter().chain([gap.before]) {
+                if seq > after + 1 {
+                    self.gaps.push(Gap {
+                        after,
+                        before: seq,
+                        attempts: gap.attempts + 1,
+                        next_attempt_at: now + Duration::seconds(BACKFILL_DELAY_SECS),
+                    });
+                }
+                after = seq;
+            }
+        }
+        delivered
+    }
+
+    fn mark_seen(&mut self, id: &str) -> bool {
+        if !self.seen.insert(id.to_string()) {
+            return false;
+        }
+        self.seen_order.push_back(id.to_string());
+        if self.seen_order.len() > SEEN_CAPACITY {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::cell::{Cell, RefCell};
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn message(seq: i64) -> ReceivedMessage {
+        ReceivedMessage {
+            id: format!("m{}", seq),
+            seq,
+            client_id: None,
+            content: format!("message {}", seq),
+            username: "alice".to_string(),
+            // Deliberately identical: ordering must not depend on it.
+            created_at: at(0).to_rfc3339(),
+            user_id: "u1".to_string(),
+        }
+    }
+
+    /// A stand-in for PostgREST answering the sequencer's queries from a
+    /// list of committed messages.
+    #[derive(Default)]
+    struct MockBackend {
+        committed: RefCell<Vec<ReceivedMessage>>,
+        queries: RefCell<Vec<Query>>,
+        /// Return results newest first, like a careless server might.
+        reversed: bool,
+        /// Fail gap lookups, like a flaky connection might.
+        failing_backfills: Cell<bool>,
+    }
+
+    impl MockBackend {
+        fn commit(&self, seqs: &[i64]) {
+            self.committed
+                .borrow_mut()
+                .extend(seqs.iter().map(|&seq| message(seq)));
+        }
+
+        async fn fetch(&self, query: Query) -> Result<Vec<ReceivedMessage>, String> {
+            self.queries.borrow_mut().push(query);
+            if self.failing_backfills.get() && matches!(query, Query::Between { .. }) {
+                return Err("connection reset".to_string());
+            }
+            let mut rows = self.committed.borrow().clone();
+            rows.sort_by_key(|m| m.seq);
+            let mut rows: Vec<_> = match query {
+                Query::Latest { limit } => {
+                    let skip = rows.len().saturating_sub(limit);
+                    rows.into_iter().skip(skip).collect()
+                }
+                Query::After { seq, limit } => rows
+                    .into_iter()
+                    .filter(|m| m.seq > seq)
+                    .take(limit)
+                    .collect(),
+                Query::Between { after, before } => rows
+                    .into_iter()
+                    .filter(|m| m.seq > after && m.seq < before)
+                    .collect(),
+            };
+            if self.reversed {
+                rows.reverse();
+            }
+            Ok(rows)
+        }
+    }
+
+    async fn poll(sequencer: &mut Sequencer, backend: &MockBackend, now: i64) -> Vec<i64> {
+        sequencer
+            .poll(at(now), |query| backend.fetch(query))
+            .await
+            .unwrap()
+            .iter()
+            .map(|m| m.seq)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn starts_with_the_backlog_then_follows_the_cursor() {
+        let backend = MockBackend::default();
+        backend.commit(&(1..=25).collect::<Vec<_>>());
+        let mut sequencer = Sequencer::default();
+
+        assert_eq!(
+            poll(&mut sequencer, &backend, 0).await,
+            (6..=25).collect::<Vec<_>>()
+        );
+        backend.commit(&[26]);
+        assert_eq!(poll(&mut sequencer, &backend, 0).await, vec![26]);
+
+        let queries = backend.queries.borrow();
+        assert_eq!(
+            queries[0],
+            Query::Latest {
+                limit: BACKLOG_SIZE
+            }
+        );
+        assert_eq!(
+            queries[1],
+            Query::After {
+                seq: 25,
+                limit: PAGE_SIZE
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn same_timestamp_messages_are_all_delivered() {
+        let backend = MockBackend::default();
+        let mut sequencer = Sequencer::default();
+        backend.commit(&[1]);
+        poll(&mut sequencer, &backend, 0).await;
+
+        backend.commit(&[2, 3, 4]);
+        assert_eq!(poll(&mut sequencer, &backend, 0).await, vec![2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn duplicates_are_delivered_once_and_in_order() {
+        let backend = MockBackend {
+            reversed: true,
+            ..MockBackend::default()
+        };
+        let mut sequencer = Sequencer::default();
+        backend.commit(&[1, 2, 3]);
+        assert_eq!(poll(&mut sequencer, &backend, 0).await, vec![1, 2, 3]);
+
+        // The same message showing up again, e.g. from an overlapping page.
+        let again = sequencer.accept(vec![message(3)], at(0));
+        assert!(again.is_empty());
+    }
+
+    #[tokio::test]
+    async fn late_commit_is_backfilled() {
+        let backend = MockBackend::default();
+        let mut sequencer = Sequencer::default();
+        backend.commit(&[1, 2]);
+        poll(&mut sequencer, &backend, 0).await;
+
+        // 3 was assigned first but commits after 4.
+        backend.commit(&[4]);
+        assert_eq!(poll(&mut sequencer, &backend, 0).await, vec![4]);
+        backend.commit(&[3]);
+
+        // Not looked for again until the backfill delay has passed.
+        assert!(poll(&mut sequencer, &backend, 0).await.is_empty());
+        assert_eq!(
+            poll(&mut sequencer, &backend, BACKFILL_DELAY_SECS).await,
+            vec![3]
+        );
+        assert!(sequencer.gaps.is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_backfill_keeps_new_messages_and_the_gap() {
+        let backend = MockBackend::default();
+        let mut sequencer = Sequencer::default();
+        backend.commit(&[1]);
+        poll(&mut sequencer, &backend, 0).await;
+        backend.commit(&[3]);
+        poll(&mut sequencer, &backend, 0).await;
+
+        backend.failing_backfills.set(true);
+        backend.commit(&[2, 4]);
+        assert_eq!(poll(&mut sequencer, &backend, 1).await, vec![4]);
+        assert_eq!(sequencer.gaps.len(), 1);
+        assert_eq!(sequencer.gaps[0].attempts, 0);
+
+        backend.failing_backfills.set(false);
+        assert_eq!(poll(&mut sequencer, &backend, 1).await, vec![2]);
+        assert!(sequencer.gaps.is_empty());
+    }
+
+    #[tokio::test]
+    async fn partially_filled_gap_keeps_looking_for_the_rest() {
+        let backend = MockBackend::default();
+        let mut sequencer = Sequencer::default();
+        backend.commit(&[1]);
+        poll(&mut sequencer, &backend, 0).await;
+
+        backend.commit(&[5]);
+        poll(&mut sequencer, &backend, 0).await;
+        backend.commit(&[3]);
+        assert_eq!(poll(&mut sequencer, &backend, 1).await, vec![3]);
+
+        let gaps: Vec<(i64, i64)> = sequencer.gaps.iter().map(|g| (g.after, g.before)).collect();
+        assert_eq!(gaps, vec![(1, 3), (3, 5)]);
+
+        backend.commit(&[2, 4]);
+        assert_eq!(poll(&mut sequencer, &backend, 2).await, vec![2, 4]);
+        assert!(sequencer.gaps.is_empty());
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_messages_that_never_show() {
+        let backend = MockBackend::default();
+        let mut sequencer = Sequencer::default();
+        // 2 was deleted, or its insert rolled back.
+        backend.commit(&[1, 3]);
+        assert_eq!(poll(&mut sequencer, &backend, 0).await, vec![1, 3]);
+        for attempt in 1..=MAX_BACKFILL_ATTEMPTS as i64 {
+            assert!(!sequencer.gaps.is_empty());
+            poll(&mut sequencer, &backend, attempt * BACKFILL_DELAY_SECS).await;
+        }
+        assert!(sequencer.gaps.is_empty());
+    }
+}
@@ -0,0 +1,306 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::cache::Cache;
+use crate::postgrest::RestQuery;
+use crate::sequencer::{Query, Sequencer, BACKLOG_SIZE};
+use crate::{ApiClient, Backend};
+use crate::{ReceivedMessage, DEFAULT_CHANNEL};
+
+/// Who is logged in and whether they are banned. Lives behind a lock in
+/// [`SharedState`] so background workers always see the current values.
+#[derive(Debug, Clone, Default)]
+pub struct AuthState {
+    pub session_token: Option<String>,
+    pub logged_in: bool,
+    pub current_user_id: Option<Uuid>,
+    pub current_username: Option<String>,
+    pub is_moderator: bool,
+    pub is_banned: bool,
+    pub ban_reason: Option<String>,
+    pub ban_expires: Option<DateTime<Utc>>,
+}
+
+/// Broadcast to every subscriber whenever [`AuthState`] changes. Details
+/// are read back through [`SharedState::auth`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateEvent {
+    LoggedIn,
+    LoggedOut,
+    Banned,
+    Unbanned,
+    Renamed,
+}
+
+/// How often the cache is checked against the server for deleted messages.
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// What the polling worker hands to the UI, in order.
+#[derive(Debug, Clone)]
+pub enum FeedItem {
+    /// Drop everything shown so far; a fresh backlog follows.
+    Reset,
+    Message(ReceivedMessage),
+}
+
+/// State shared between the UI thread and the background workers: the
+/// backend connections, the auth state and the local cache.
+pub struct SharedState {
+    /// Supabase, for everything [`Backend`] doesn't cover. Features go
+    /// through [`Self::extras`], which checks the backend actually is it.
+    pub api: ApiClient,
+    /// Users, sessions, messages and bans.
+    pub backend: Arc<dyn Backend>,
+    auth: RwLock<AuthState>,
+    subscribers: Mutex<Vec<mpsc::Sender<StateEvent>>>,
+    cache: Mutex<Cache>,
+}
+
+impl SharedState {
+    pub fn new(api: ApiClient, backend: Arc<dyn Backend>, cache: Cache) -> Arc<Self> {
+        Arc::new(SharedState {
+            api,
+            backend,
+            auth: RwLock::new(AuthState::default()),
+            subscribers: Mutex::new(Vec::new()),
+            cache: Mutex::new(cache),
+        })
+    }
+
+    /// [`Self::api`], unless the backend isn't Supabase and the client
+    /// only has a placeholder URL.
+    pub fn extras(&self) -> Result<&ApiClient, String> {
+        if self.backend.has_extras() {
+            Ok(&self.api)
+        } else {
+            Err("Only available with the Supabase backend".to_string())
+        }
+    }
+
+    pub fn cache(&self) -> MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Whose cache to read and write: the logged in user, or whoever used
+    /// this install last.
+    pub fn cache_owner(&self) -> String {
+        match self.auth().current_user_id {
+            Some(user_id) => user_id.to_string(),
+            None => self.cache().last_owner().unwrap_or_default(),
+        }
+    }
+
+    pub fn auth(&self) -> RwLockReadGuard<'_, AuthState> {
+        self.auth.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn subscribe(&self) -> mpsc::Receiver<StateEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(tx);
+        rx
+    }
+
+    fn update(&self, event: StateEvent, apply: impl FnOnce(&mut AuthState)) {
+        apply(&mut self.auth.write().unwrap_or_else(|e| e.into_inner()));
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|tx| tx.send(event).is_ok());
+    }
+
+    pub fn set_logged_in(
+        &self,
+        session_token: String,
+        user_id: Uuid,
+        username: &str,
+        is_moderator: bool,
+    ) {
+        self.update(StateEvent::LoggedIn, |auth| {
+            *auth = AuthState {
+                session_token: Some(session_token),
+                logged_in: true,
+                current_user_id: Some(user_id),
+                current_username: Some(username.to_string()),
+                is_moderator,
+                ..AuthState::default()
+            }
+        });
+    }
+
+    pub fn set_logged_out(&self) {
+        self.update(StateEvent::LoggedOut, |auth| *auth = AuthState::default());
+    }
+
+    pub fn set_banned(&self, reason: &str, expires_at: DateTime<Utc>) {
+        self.update(StateEvent::Banned, |auth| {
+            auth.is_banned = true;
+            auth.ban_reason = Some(reason.to_string());
+            auth.ban_expires = Some(expires_at);
+        });
+    }
+
+    pub fn set_username(&self, username: &str) {
+        self.update(StateEvent::Renamed, |auth| {
+            auth.current_username = Some(username.to_string());
+        });
+    }
+
+    pub fn set_unbanned(&self) {
+        self.update(StateEvent::Unbanned, |auth| {
+            auth.is_banned = false;
+            auth.ban_reason = None;
+            auth.ban_expires = None;
+        });
+    }
+
+    /// Wipes the chat every couple of minutes, but only on behalf of a
+    /// logged in user who isn't currently banned. Other backends keep
+    /// their own retention, so this is Supabase only.
+    pub fn start_message_cleanup(self: &Arc<Self>) {
+        if !self.backend.has_extras() {
+            return;
+        }
+        let state = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(120)).await; // 2 minutes
+
+                let auth = state.auth().clone();
+                if !auth.logged_in || auth.is_banned {
+                    continue;
+                }
+
+                if let Err(e) = state.cleanup_messages().await {
+                    eprintln!("Failed to cleanup messages: {}", e);
+                }
+            }
+        });
+    }
+
+    pub async fn cleanup_messages(&self) -> Result<(), String> {
+        // First get all message IDs
+        let response = self
+            .extras()?
+            .get(&RestQuery::table("messages").select("id"))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch messages: {}", e))?;
+
+        if !response.status().is_success() {
+            let error = response.text().await.map_err(|e| e.to_string())?;
+            return Err(format!("API error: {}", error));
+        }
+
+        #[derive(Deserialize)]
+        struct MessageId {
+            id: String,
+        }
+
+        let message_ids: Vec<MessageId> = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse message IDs: {}", e))?;
+
+        // Pinned messages are kept around for good.
+        let pinned = self.pinned_ids().await?;
+        let message_ids: Vec<MessageId> = message_ids
+            .into_iter()
+            .filter(|m| !pinned.contains(&m.id))
+            .collect();
+
+        // Delete messages in batches to avoid overloading the server
+        for chunk in message_ids.chunks(100) {
+            let ids: Vec<&str> = chunk.iter().map(|m| m.id.as_str()).collect();
+            let delete_response = self
+                .extras()?
+                .delete(&RestQuery::table("messages").in_list("id", &ids))
+                .send()
+                .await
+                .map_err(|e| format!("Failed to delete messages: {}", e))?;
+
+            if !delete_response.status().is_success() {
+                let error = delete_response.text().await.map_err(|e| e.to_string())?;
+                return Err(format!("API error during deletion: {}", error));
+            }
+
+            // Small delay between batches to avoid rate limiting
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+
+        Ok(())
+    }
+
+    /// Ids of pinned messages, which are exempt from retention cleanup.
+    pub async fn pinned_ids(&self) -> Result<Vec<String>, String> {
+        self.extras()?.pinned_ids().await
+    }
+
+    /// Polls for new messages and feeds them to `feed` in server order.
+    /// Logging in or out starts the feed over so the UI shows the backlog
+    /// for the new session.
+    pub fn start_message_polling(self: &Arc<Self>, feed: mpsc::Sender<FeedItem>) {
+        let state = Arc::clone(self);
+        let events = self.subscribe();
+        tokio::spawn(async move {
+            let mut sequencer = Sequencer::default();
+            let mut last_reconcile: Option<Instant> = None;
+
+            loop {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+
+                let mut reset = false;
+                while let Ok(event) = events.try_recv() {
+                    if matches!(event, StateEvent::LoggedIn | StateEvent::LoggedOut) {
+                        reset = true;
+                    }
+                }
+                if reset {
+                    sequencer = Sequencer::default();
+                    if feed.send(FeedItem::Reset).is_err() {
+                        return;
+                    }
+                }
+
+                let owner = state.cache_owner();
+                let messages = match sequencer
+                    .poll(Utc::now(), |query| state.backend.get_messages(query))
+                    .await
+                {
+                    Ok(messages) => messages,
+                    Err(_) => continue,
+                };
+                if let Err(e) = state
+                    .cache()
+                    .store_messages(&owner, DEFAULT_CHANNEL, &messages)
+                {
+                    eprintln!("{}", e);
+                }
+                for msg in messages {
+                    if let Err(e) = feed.send(FeedItem::Message(msg)) {
+                        eprintln!("Failed to send message: {}", e);
+                    }
+                }
+
+                // New messages are cached as they come in; deletions only
+                // show up by comparing against the server now and then.
+                if last_reconcile.is_none_or(|t| t.elapsed() >= RECONCILE_INTERVAL) {
+                    last_reconcile = Some(Instant::now());
+                    let latest = Query::Latest {
+                        limit: BACKLOG_SIZE,
+                    };
+                    if let Ok(latest) = state.backend.get_messages(latest).await {
+                        if let Err(e) = state.cache().reconcile(&owner, DEFAULT_CHANNEL, &latest) {
+                            eprintln!("{}", e);
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,41 @@
+/// Kinds of things a user can ask for, as far as a ban is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Always allowed: auth, status ticks, fetching data for display.
+    Passive,
+    Chat,
+    React,
+    ViewProfile,
+    Shop,
+}
+
+/// What a banned user may still do while they wait out their ban. Message
+/// delivery is never affected; banned users always get to watch.
+#[derive(Debug, Clone, Copy)]
+pub struct SpectatorPolicy {
+    pub can_react: bool,
+    pub can_view_profiles: bool,
+    pub can_shop: bool,
+}
+
+impl Default for SpectatorPolicy {
+    fn default() -> Self {
+        SpectatorPolicy {
+            can_react: true,
+            can_view_profiles: true,
+            can_shop: false,
+        }
+    }
+}
+
+impl SpectatorPolicy {
+    pub fn allows(&self, action: Action) -> bool {
+        match action {
+            Action::Passive => true,
+            Action::Chat => false,
+            Action::React => self.can_react,
+            Action::ViewProfile => self.can_view_profiles,
+            Action::Shop => self.can_shop,
+        }
+    }
+}
@@ -0,0 +1,503 @@
+//! End-to-end flows against a mock PostgREST: what the app does to the
+//! database when people register, log in, get banned, chat and clean up.
+
+mod support;
+
+use chrono::{DateTime, TimeZone, Utc};
+use gooncord_core::app::{handle_web_message, startup, tick, AppState, BanRoll, WebMessage};
+use gooncord_core::backend::{SqliteBackend, MAX_MESSAGES};
+use gooncord_core::ban_events::random_events_for_hour;
+use gooncord_core::cache::{Cache, CacheConfig};
+use gooncord_core::chance::{Chance, Clock, ManualClock, Recorder};
+use gooncord_core::commands::{Args, Command, Outcome};
+use gooncord_core::economy::Item;
+use gooncord_core::events::UiEvent;
+use gooncord_core::migrations::SCHEMA_VERSION;
+use gooncord_core::outbox::Outbox;
+use gooncord_core::shared::FeedItem;
+use gooncord_core::{Backend, ReceivedMessage, Session};
+use serde_json::json;
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+use support::MockPostgrest;
+use uuid::Uuid;
+
+fn app(mock: &MockPostgrest) -> AppState {
+    let api = mock.api();
+    let cache = Cache::in_memory(CacheConfig::default()).unwrap();
+    AppState::with_backend(api.clone(), Arc::new(api), cache, Outbox::load(None))
+}
+
+/// A fresh app state with `username` registered and logged in.
+async fn registered(mock: &MockPostgrest, username: &str) -> (AppState, Uuid) {
+    let mut state = app(mock);
+    state.register_user(username, "hunter22").await.unwrap();
+    let user_id = state.auth().current_user_id.unwrap();
+    (state, user_id)
+}
+
+/// Rolls for a ban 120 times on a fresh server, serving each ban out
+/// straight away, and lists the bans that came up.
+async fn ban_sequence(chance: Chance) -> Vec<(String, DateTime<Utc>)> {
+    let mock = MockPostgrest::start().await;
+    let (state, _) = registered(&mock, "ivan").await;
+    let mut state = state.with_chance(chance);
+    let mut bans = Vec::new();
+    for _ in 0..120 {
+        if let BanRoll::Banned = state.check_random_ban().await.unwrap() {
+            let auth = state.auth().clone();
+            bans.push((auth.ban_reason.unwrap(), auth.ban_expires.unwrap()));
+            state.lift_ban();
+        }
+    }
+    bans
+}
+
+/// Waits for the next `n` messages from the polling worker.
+async fn next_messages(
+    feed: mpsc::Receiver<FeedItem>,
+    n: usize,
+) -> (mpsc::Receiver<FeedItem>, Vec<ReceivedMessage>) {
+    tokio::task::spawn_blocking(move || {
+        let mut messages = Vec::new();
+        while messages.len() < n {
+            let item = feed
+                .recv_timeout(Duration::from_secs(5))
+                .expect("polling delivered nothing");
+            if let FeedItem::Message(message) = item {
+                messages.push(message);
+            }
+        }
+        (feed, messages)
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn registers_logs_out_and_back_in() {
+    let mock = MockPostgrest::start().await;
+    let (mut state, user_id) = registered(&mock, "alice").await;
+    assert!(state.auth().logged_in);
+
+    let users = mock.rows("users");
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0]["username"], "alice");
+    assert!(users[0]["password_hash"]
+        .as_str()
+        .unwrap()
+        .starts_with("$2"));
+    assert_eq!(mock.rows("sessions").len(), 1);
+    assert!(app(&mock).register_user("alice", "again").await.is_err());
+    assert!(app(&mock).register_user("a&b", "hunter22").await.is_err());
+    // Lookups can't be talked into matching more than the one name.
+    let sneaky = "nobody&username=neq.nobody";
+    assert!(state.get_profile(sneaky).await.is_err());
+
+    state.logout().await;
+    assert!(!state.auth().logged_in);
+    assert!(mock.rows("sessions").is_empty());
+
+    let mut state = app(&mock);
+    assert!(state.login_user("alice", "wrong").await.is_err());
+    assert!(!state.auth().logged_in);
+    state.login_user("alice", "hunter22").await.unwrap();
+    assert_eq!(state.auth().current_user_id, Some(user_id));
+
+    // Sessions resolve through `gooncord_session`, not the table.
+    let token = state.auth().session_token.clone().unwrap();
+    let session = mock.api().find_session(&token).await.unwrap().unwrap();
+    assert_eq!(session.username, "alice");
+    assert!(mock.api().find_session("forged").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn login_picks_up_bans_in_effect() {
+    let mock = MockPostgrest::start().await;
+    let (_, user_id) = registered(&mock, "bob").await;
+    let hours = |n| (Utc::now() + chrono::Duration::hours(n)).to_rfc3339();
+
+    mock.insert(
+        "bans",
+        json!({ "user_id": user_id, "reason": "long over", "expires_at": hours(-1) }),
+    );
+    let mut state = app(&mock);
+    state.login_user("bob", "hunter22").await.unwrap();
+    assert!(!state.auth().is_banned);
+
+    mock.insert(
+        "bans",
+        json!({ "user_id": user_id, "reason": "breathing too loudly", "expires_at": hours(1) }),
+    );
+    let mut state = app(&mock);
+    state.login_user("bob", "hunter22").await.unwrap();
+    assert!(state.auth().is_banned);
+    assert_eq!(
+        state.auth().ban_reason.as_deref(),
+        Some("breathing too loudly")
+    );
+
+    // Whether a ban is still on is up to the app's clock.
+    let later = ManualClock::new(Utc::now() + chrono::Duration::hours(2));
+    let mut later = app(&mock).with_chance(Chance::seeded(1, Arc::new(later)));
+    later.login_user("bob", "hunter22").await.unwrap();
+    assert!(!later.auth().is_banned);
+    assert!(!later.get_profile("bob").await.unwrap().is_banned);
+
+    // Banned means nothing reaches the server.
+    let content = "let me out".to_string();
+    handle_web_message(&mut state, WebMessage::Message { content }).await;
+    assert!(mock.rows("messages").is_empty());
+
+    // `/banme` writes a ban like any other.
+    let (mut carol, carol_id) = registered(&mock, "carol").await;
+    carol.ban_self("volunteered").await.unwrap();
+    assert!(carol.auth().is_banned);
+    let ban = mock
+        .api()
+        .current_ban(carol_id, Utc::now())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(ban.reason, "volunteered");
+
+    // Nobody else's, and nothing past an hour.
+    let api = carol.shared.api.clone();
+    let soon = Utc::now() + chrono::Duration::seconds(30);
+    assert!(api.create_ban(user_id, "because", soon).await.is_err());
+    let later = Utc::now() + chrono::Duration::hours(2);
+    assert!(api.create_ban(carol_id, "forever", later).await.is_err());
+    assert_eq!(mock.rows("bans").len(), 3);
+}
+
+#[tokio::test]
+async fn seeded_and_replayed_sessions_ban_alike() {
+    let start = Utc::now();
+    let seeded = |seed| Chance::seeded(seed, Arc::new(ManualClock::new(start)));
+
+    let bans = ban_sequence(seeded(42)).await;
+    assert!(!bans.is_empty());
+    assert!(bans.iter().all(|(_, expires_at)| *expires_at > start));
+    assert_eq!(ban_sequence(seeded(42)).await, bans);
+    assert_ne!(ban_sequence(seeded(43)).await, bans);
+
+    // A live session, recorded, then played back.
+    let recorder = Recorder::default();
+    let recorded = ban_sequence(Chance::system().recorded(recorder.clone())).await;
+    let replayed = ban_sequence(Chance::replay(recorder.entries())).await;
+    assert_eq!(replayed, recorded);
+}
+
+#[tokio::test]
+async fn sends_once_and_caps_the_channel() {
+    let mock = MockPostgrest::start().await;
+    let (mut state, user_id) = registered(&mock, "dave").await;
+
+    let content = "hello".to_string();
+    let events = handle_web_message(&mut state, WebMessage::Message { content }).await;
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, UiEvent::MessageDelivered { .. })));
+    let messages = mock.rows("messages");
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["content"], "hello");
+    assert_eq!(messages[0]["user_id"], json!(user_id));
+
+    // Nobody gets to post as dave without dave's session.
+    let stranger = mock
+        .api()
+        .send_message(Uuid::new_v4(), user_id, "it's me, dave")
+        .await;
+    assert!(stranger.unwrap_err().contains("row-level security"));
+
+    // A resend after a lost response must not post twice.
+    let api = state.shared.api.clone();
+    let client_id: Uuid = serde_json::from_value(messages[0]["client_id"].clone()).unwrap();
+    api.send_message(client_id, user_id, "hello").await.unwrap();
+    assert_eq!(mock.rows("messages").len(), 1);
+
+    // Pinned messages neither count towards the cap nor get trimmed.
+    let pinned = messages[0]["id"].clone();
+    mock.insert(
+        "pins",
+        json!({ "message_id": pinned, "channel": "general" }),
+    );
+    for i in 0..MAX_MESSAGES + 5 {
+        api.send_message(Uuid::new_v4(), user_id, &format!("#{}", i))
+            .await
+            .unwrap();
+    }
+    let messages = mock.rows("messages");
+    assert_eq!(messages.len(), MAX_MESSAGES + 1);
+    assert!(messages.iter().any(|message| message["id"] == pinned));
+    assert!(messages.iter().all(|message| message["content"] != "#0"));
+
+    // At the cap, neither a retry nor a refused insert trims anything.
+    let last = messages.last().unwrap();
+    let client_id: Uuid = serde_json::from_value(last["client_id"].clone()).unwrap();
+    api.send_message(client_id, user_id, "again").await.unwrap();
+    let stranger = mock
+        .api()
+        .send_message(Uuid::new_v4(), user_id, "it's me")
+        .await;
+    assert!(stranger.is_err());
+    assert_eq!(mock.rows("messages"), messages);
+}
+
+#[tokio::test]
+async fn other_backends_leave_supabase_alone() {
+    let mock = MockPostgrest::start().await;
+    let backend = Arc::new(SqliteBackend::in_memory().unwrap());
+    let cache = Cache::in_memory(CacheConfig::default()).unwrap();
+    let mut state = AppState::with_backend(mock.api(), backend, cache, Outbox::load(None));
+    state.register_user("ivy", "hunter22").await.unwrap();
+
+    let content = "offline".to_string();
+    let events = handle_web_message(&mut state, WebMessage::Message { content }).await;
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, UiEvent::MessageDelivered { .. })));
+    assert!(mock.rows("messages").is_empty());
+    assert!(mock.rows("achievements").is_empty());
+    assert!(mock.rows("wallets").is_empty());
+    assert_eq!(state.wallet().balance, 0);
+    assert_eq!(
+        state.buy_item(Item::BanHalving).await,
+        Err("Only available with the Supabase backend".to_string())
+    );
+    assert!(state.wallet().inventory.is_empty());
+
+    assert!(state.list_pins().await.unwrap_err().contains("Supabase"));
+    assert!(state.shared.cleanup_messages().await.is_err());
+}
+
+#[tokio::test]
+async fn random_ban_events_run_on_every_backend() {
+    let mock = MockPostgrest::start().await;
+    let first_hour = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+    let event = (0..10_000)
+        .flat_map(|h| random_events_for_hour(first_hour + chrono::Duration::hours(h)))
+        .next()
+        .unwrap();
+    let clock = Arc::new(ManualClock::new(event.starts_at));
+    let backend = Arc::new(SqliteBackend::in_memory().unwrap());
+    let cache = Cache::in_memory(CacheConfig::default()).unwrap();
+    let mut state = AppState::with_backend(mock.api(), backend, cache, Outbox::load(None))
+        .with_chance(Chance::seeded(1, clock));
+
+    let events = tick(&mut state).await;
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, UiEvent::EventAnnouncement { .. })));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn polling_delivers_messages_in_order() {
+    let mock = MockPostgrest::start().await;
+    let (state, user_id) = registered(&mock, "erin").await;
+    let api = state.shared.api.clone();
+    for content in ["one", "two", "three"] {
+        api.send_message(Uuid::new_v4(), user_id, content)
+            .await
+            .unwrap();
+    }
+
+    let (feed, rx) = mpsc::channel();
+    state.shared.start_message_polling(feed);
+    let (rx, backlog) = next_messages(rx, 3).await;
+    let contents: Vec<&str> = backlog.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, ["one", "two", "three"]);
+    assert!(backlog.iter().all(|m| m.username == "erin"));
+    assert!(backlog.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+
+    api.send_message(Uuid::new_v4(), user_id, "four")
+        .await
+        .unwrap();
+    let (_, news) = next_messages(rx, 1).await;
+    assert_eq!(news[0].content, "four");
+}
+
+#[tokio::test]
+async fn counts_unread_messages_past_the_cursor() {
+    let mock = MockPostgrest::start().await;
+    let (mut state, user_id) = registered(&mock, "frank").await;
+    let api = state.shared.api.clone();
+    api.send_message(Uuid::new_v4(), user_id, "read")
+        .await
+        .unwrap();
+    let seq = mock.rows("messages")[0]["seq"].as_i64().unwrap();
+    assert!(state.mark_read(seq).await.unwrap());
+    assert_eq!(mock.rows("read_cursors").len(), 1);
+
+    for content in ["unread", "also unread"] {
+        api.send_message(Uuid::new_v4(), user_id, content)
+            .await
+            .unwrap();
+    }
+    assert_eq!(state.unread_count().await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn cleanup_spares_pinned_messages() {
+    let mock = MockPostgrest::start().await;
+    let (state, user_id) = registered(&mock, "grace").await;
+    let api = state.shared.api.clone();
+    for i in 0..5 {
+        api.send_message(Uuid::new_v4(), user_id, &format!("#{}", i))
+            .await
+            .unwrap();
+    }
+    let keep = mock.rows("messages")[2]["id"].clone();
+    mock.insert("pins", json!({ "message_id": keep, "channel": "general" }));
+
+    state.shared.cleanup_messages().await.unwrap();
+    let messages = mock.rows("messages");
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["id"], keep);
+    assert_eq!(mock.rows("pins").len(), 1);
+}
+
+#[tokio::test]
+async fn runs_registered_commands() {
+    let mock = MockPostgrest::start().await;
+    let (mut state, _) = registered(&mock, "plugged").await;
+    let command = || Command {
+        name: "whoami",
+        usage: "/whoami",
+        summary: "Say who you are",
+        handler: Box::new(|_, ctx| Ok(Outcome::Reply(ctx.username.to_string()))),
+    };
+    state.register_command(command()).unwrap();
+    assert!(state.register_command(command()).is_err());
+
+    let outcome = state.run_command("whoami", Args { raw: "" });
+    assert_eq!(outcome, Ok(Outcome::Reply("plugged".to_string())));
+}
+
+#[tokio::test]
+async fn bots_and_webhooks_need_valid_names() {
+    let mock = MockPostgrest::start().await;
+    let (mut state, user_id) = registered(&mock, "moddy").await;
+    let token = state.auth().session_token.clone().unwrap();
+    state.start_session(Session {
+        token,
+        user_id,
+        username: "moddy".to_string(),
+        is_moderator: true,
+    });
+
+    assert!(state
+        .create_bot("no spaces")
+        .await
+        .unwrap_err()
+        .contains("letters"));
+    assert!(state
+        .create_incoming_webhook("Admin")
+        .await
+        .unwrap_err()
+        .contains("reserved"));
+    assert_eq!(mock.rows("users").len(), 1);
+}
+
+#[tokio::test]
+async fn only_the_relay_bot_sees_outgoing_secrets() {
+    let mock = MockPostgrest::start().await;
+    let (mut state, user_id) = registered(&mock, "hookmod").await;
+    let token = state.auth().session_token.clone().unwrap();
+    state.start_session(Session {
+        token,
+        user_id,
+        username: "hookmod".to_string(),
+        is_moderator: true,
+    });
+    let relay_token = state.create_bot("relay").await.unwrap();
+    let other_token = state.create_bot("lurker").await.unwrap();
+
+    let events = ["ban".to_string()];
+    let url = "https://example.com/hook";
+    assert!(state
+        .create_outgoing_webhook(url, &events, "nobody")
+        .await
+        .is_err());
+    let secret = state
+        .create_outgoing_webhook(url, &events, "relay")
+        .await
+        .unwrap();
+
+    let hooks = |token: &str| {
+        let api = mock.api();
+        api.set_session(Some(token));
+        async move {
+            let request = api.rpc("gooncord_outgoing_webhooks").json(&json!({}));
+            let hooks: Vec<serde_json::Value> = api
+                .send(request, "hooks")
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            hooks
+        }
+    };
+    let relayed = hooks(&relay_token).await;
+    assert_eq!(relayed.len(), 1);
+    assert_eq!(relayed[0]["secret"], secret);
+    assert!(hooks(&other_token).await.is_empty());
+}
+
+#[tokio::test]
+async fn refuses_an_outdated_schema() {
+    let mock = MockPostgrest::start().await;
+    let api = mock.api();
+    api.check_schema().await.unwrap();
+    assert!(startup(&app(&mock)).await.is_empty());
+
+    mock.set_schema_version(Some(SCHEMA_VERSION - 1));
+    assert!(api.check_schema().await.unwrap_err().contains("migrate"));
+    mock.set_schema_version(None);
+    assert!(api.check_schema().await.unwrap_err().contains("migrate"));
+    let events = startup(&app(&mock)).await;
+    assert!(matches!(events[..], [UiEvent::SystemMessage { .. }]));
+}
+
+#[tokio::test]
+async fn active_effects_outlast_the_session_and_soften_bans() {
+    let mock = MockPostgrest::start().await;
+    let (_, mallory) = registered(&mock, "mallory").await;
+    let (mut state, user_id) = registered(&mock, "heidi").await;
+    for item in ["ban_redirect", "ban_halving"] {
+        mock.insert(
+            "inventory",
+            json!({ "user_id": user_id, "item": item, "quantity": 1 }),
+        );
+    }
+    state.load_wallet().await.unwrap();
+    state.use_item(Item::BanRedirect).await.unwrap();
+    state.use_item(Item::BanHalving).await.unwrap();
+    state.logout().await;
+
+    let clock = Arc::new(ManualClock::new(Utc::now()));
+    let mut state = app(&mock).with_chance(Chance::seeded(5, clock.clone()));
+    state.login_user("heidi", "hunter22").await.unwrap();
+    assert_eq!(state.wallet().effects.len(), 2);
+
+    // The first ban goes to mallory, the second is heidi's but halved.
+    let mut redirected = false;
+    for _ in 0..1000 {
+        match state.check_random_ban().await.unwrap() {
+            BanRoll::Redirected { username } => {
+                assert_eq!(username, "mallory");
+                redirected = true;
+            }
+            BanRoll::Banned => break,
+            BanRoll::Spared | BanRoll::Shielded => {}
+        }
+    }
+    assert!(redirected);
+    let bans = mock.rows("bans");
+    assert_eq!(bans.len(), 2);
+    assert_eq!(bans[0]["user_id"], json!(mallory));
+    let expires_at: DateTime<Utc> = serde_json::from_value(bans[1]["expires_at"].clone()).unwrap();
+    assert!((expires_at - clock.now()).num_seconds() < 15);
+    assert!(mock.rows("active_effects").is_empty());
+}
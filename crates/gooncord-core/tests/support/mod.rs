@@ -0,0 +1,1050 @@
+//! An in-process stand-in for Supabase's PostgREST, covering what
+//! `ApiClient` and `AppState` use: the tables from the migrations, `eq.`,
+//! `in.`, `is.` and the other filters (with `not.`), `users(...)` embeds,
+//! `order`/`limit`/`offset`, `on_conflict` upserts, `Prefer` (`return=`,
+//! `resolution=`, `count=exact`) and `Content-Range`. Foreign keys and
+//! unique constraints are checked the way Postgres would, and so is the
+//! ownership half of the row-level security: rows with a `user_id` are only
+//! written by that user, signed in with the `X-Gooncord-Session` header,
+//! and bans are bounded like `bans_create` bounds them.
+//! The `gooncord_*` database functions are under `/rest/v1/rpc/`.
+
+use axum::body::Bytes;
+use axum::extract::{Path, RawQuery, State};
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{any, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use gooncord_core::auth;
+use gooncord_core::migrations::{CREATE_VERSION_TABLE, MIGRATIONS};
+use gooncord_core::ApiClient;
+use serde_json::{json, Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// The only key the mock accepts, as both `apikey` and bearer token.
+pub const ANON_KEY: &str = "mock-anon-key";
+
+pub type Row = Map<String, Value>;
+
+#[derive(Default)]
+struct Database {
+    tables: HashMap<String, Vec<Row>>,
+    next_id: i64,
+}
+
+#[derive(Clone)]
+pub struct MockPostgrest {
+    pub url: String,
+    db: Arc<Mutex<Database>>,
+}
+
+/// Column sets no two rows may share, per table.
+fn unique_keys(table: &str) -> &'static [&'static [&'static str]] {
+    match table {
+        "users" => &[&["id"], &["username"]],
+        "sessions" | "bot_tokens" => &[&["token"]],
+        "messages" => &[&["id"], &["seq"], &["client_id"]],
+        "achievements" => &[&["user_id", "achievement_id"]],
+        "wallets" => &[&["user_id"]],
+        "inventory" | "active_effects" => &[&["user_id", "item"]],
+        "reactions" => &[&["message_id", "user_id", "emoji"]],
+        "pins" => &[&["message_id"]],
+        "bookmarks" => &[&["user_id", "message_id"]],
+        "read_cursors" => &[&["user_id", "channel"]],
+        "incoming_webhooks" => &[&["id"], &["secret"]],
+        "schema_migrations" => &[&["version"]],
+        _ => &[&["id"]],
+    }
+}
+
+/// `(column, referenced table)`; every reference is to the table's `id`.
+fn foreign_keys(table: &str) -> &'static [(&'static str, &'static str)] {
+    match table {
+        "sessions" | "messages" | "bans" | "achievements" | "wallets" | "inventory"
+        | "active_effects" | "bookmarks" | "read_cursors" | "bot_tokens" | "incoming_webhooks" => {
+            &[("user_id", "users")]
+        }
+        "reactions" => &[("message_id", "messages"), ("user_id", "users")],
+        "pins" => &[("message_id", "messages")],
+        "outgoing_webhooks" => &[("relay_id", "users")],
+        _ => &[],
+    }
+}
+
+/// Tables whose rows belong to their `user_id`, which alone may write them.
+fn owned(table: &str) -> bool {
+    [
+        "messages",
+        "achievements",
+        "wallets",
+        "inventory",
+        "active_effects",
+        "reactions",
+        "bookmarks",
+        "read_cursors",
+    ]
+    .contains(&table)
+}
+
+/// A PostgREST error response.
+type Rejection = (StatusCode, Json<Value>);
+
+fn error(status: StatusCode, code: &str, message: impl Into<String>) -> Rejection {
+    let body = json!({ "code": code, "message": message.into(), "details": null, "hint": null });
+    (status, Json(body))
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// How a stored value compares to a filter's literal, if they compare.
+fn compare_literal(value: &Value, literal: &str) -> Option<Ordering> {
+    match value {
+        Value::Number(n) => n.as_f64()?.partial_cmp(&literal.parse::<f64>().ok()?),
+        Value::Bool(b) => Some(b.cmp(&literal.parse::<bool>().ok()?)),
+        Value::String(s) => match (parse_time(s), parse_time(literal)) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => Some(s.as_str().cmp(literal)),
+        },
+        _ => None,
+    }
+}
+
+/// Sort order between two stored values; nulls last, like Postgres.
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        (_, Value::String(b)) => compare_literal(a, b).unwrap_or(Ordering::Equal),
+        _ => compare_literal(a, &b.to_string()).unwrap_or(Ordering::Equal),
+    }
+}
+
+struct Filter {
+    column: String,
+    negated: bool,
+    operator: String,
+    argument: String,
+}
+
+impl Filter {
+    fn parse(column: &str, value: &str) -> Result<Filter, String> {
+        if column.contains('.') {
+            return Err(format!(
+                "filters on embedded columns ({}) aren't mocked",
+                column
+            ));
+        }
+        let (negated, value) = match value.strip_prefix("not.") {
+            Some(rest) => (true, rest),
+            None => (false, value),
+        };
+        let (operator, argument) = value
+            .split_once('.')
+            .ok_or_else(|| format!("failed to parse filter {}={}", column, value))?;
+        if !["eq", "neq", "gt", "gte", "lt", "lte", "in", "is"].contains(&operator) {
+            return Err(format!("operator {} isn't mocked", operator));
+        }
+        Ok(Filter {
+            column: column.to_string(),
+            negated,
+            operator: operator.to_string(),
+            argument: argument.to_string(),
+        })
+    }
+
+    fn matches(&self, row: &Row) -> bool {
+        let value = row.get(&self.column).unwrap_or(&Value::Null);
+        let ordering = || compare_literal(value, &self.argument);
+        let matched = match self.operator.as_str() {
+            "eq" => ordering() == Some(Ordering::Equal),
+            "neq" => ordering().is_some_and(|o| o != Ordering::Equal),
+            "gt" => ordering() == Some(Ordering::Greater),
+            "gte" => ordering().is_some_and(|o| o != Ordering::Less),
+            "lt" => ordering() == Some(Ordering::Less),
+            "lte" => ordering().is_some_and(|o| o != Ordering::Greater),
+            "in" => self
+                .argument
+                .trim_start_matches('(')
+                .trim_end_matches(')')
+                .split(',')
+                .filter(|item| !item.is_empty())
+                .map(|item| compare_literal(value, item.trim_matches('"')))
+                .any(|ordering| ordering == Some(Ordering::Equal)),
+            "is" => match self.argument.as_str() {
+                "null" => value.is_null(),
+                "true" => value == &Value::Bool(true),
+                "false" => value == &Value::Bool(false),
+                _ => false,
+            },
+            _ => unreachable!("checked in parse"),
+        };
+        matched != self.negated
+    }
+}
+
+/// One item of a `select`: a column, `*`, or an embedded table like
+/// `users(username)` or `users!inner(username)`.
+enum Field {
+    All,
+    Column(String),
+    Embed {
+        table: String,
+        inner: bool,
+        fields: Vec<Field>,
+    },
+}
+
+fn parse_select(select: &str) -> Vec<Field> {
+    let mut items = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in select.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&select[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&select[start..]);
+
+    items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| match item.split_once('(') {
+            Some((name, rest)) => {
+                let (table, hint) = name.split_once('!').unwrap_or((name, ""));
+                Field::Embed {
+                    table: table.to_string(),
+                    inner: hint == "inner",
+                    fields: parse_select(rest.strip_suffix(')').unwrap_or(rest)),
+                }
+            }
+            None if item == "*" => Field::All,
+            None => Field::Column(item.to_string()),
+        })
+        .collect()
+}
+
+struct Request {
+    filters: Vec<Filter>,
+    select: Vec<Field>,
+    order: Vec<(String, bool)>,
+    limit: Option<usize>,
+    offset: usize,
+    on_conflict: Option<Vec<String>>,
+    prefer: Vec<String>,
+}
+
+impl Request {
+    fn parse(query: &str, headers: &HeaderMap) -> Result<Request, String> {
+        let mut request = Request {
+            filters: Vec::new(),
+            select: vec![Field::All],
+            order: Vec::new(),
+            limit: None,
+            offset: 0,
+            on_conflict: None,
+            prefer: headers
+                .get_all("Prefer")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .map(|item| item.trim().to_string())
+                .collect(),
+        };
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "select" => request.select = parse_select(&value),
+                "order" => {
+                    for item in value.split(',') {
+                        let mut parts = item.split('.');
+                        let column = parts.next().unwrap_or_default().to_string();
+                        let descending = parts.any(|part| part == "desc");
+                        request.order.push((column, descending));
+                    }
+                }
+                "limit" => request.limit = Some(value.parse().map_err(|_| "bad limit")?),
+                "offset" => request.offset = value.parse().map_err(|_| "bad offset")?,
+                "on_conflict" => {
+                    request.on_conflict = Some(value.split(',').map(str::to_string).collect())
+                }
+                "columns" => {}
+                _ => request.filters.push(Filter::parse(&key, &value)?),
+            }
+        }
+        Ok(request)
+    }
+
+    fn prefers(&self, preference: &str) -> bool {
+        self.prefer.iter().any(|p| p == preference)
+    }
+
+    fn matches(&self, row: &Row) -> bool {
+        self.filters.iter().all(|filter| filter.matches(row))
+    }
+}
+
+impl Database {
+    fn next_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Fills in what the migrations' column defaults would.
+    fn with_defaults(&mut self, table: &str, mut row: Row) -> Row {
+        let now = json!(Utc::now().to_rfc3339());
+        let defaults = match table {
+            "users" => vec![
+                ("is_moderator", json!(false)),
+                ("is_bot", json!(false)),
+                ("created_at", now),
+            ],
+            "messages" => {
+                // `seq` is generated always; whatever was sent is ignored.
+                row.insert("seq".to_string(), json!(self.next_id()));
+                vec![
+                    ("id", json!(Uuid::new_v4())),
+                    ("client_id", Value::Null),
+                    ("channel", json!("general")),
+                    ("created_at", now),
+                ]
+            }
+            "bans" => vec![
+                ("id", json!(self.next_id())),
+                ("created_at", now),
+                ("is_active", json!(true)),
+            ],
+            "reactions" => vec![("id", json!(self.next_id())), ("created_at", now)],
+            "pins" => vec![("pinned_at", now)],
+            "bookmarks" => vec![("note", Value::Null), ("bookmarked_at", now)],
+            "wallets" => vec![("balance", json!(0))],
+            "inventory" => vec![("quantity", json!(0))],
+            "active_effects" => vec![("expires_at", Value::Null)],
+            "achievements" => vec![("unlocked_at", now)],
+            "read_cursors" => vec![("updated_at", now)],
+            "schema_migrations" => vec![("applied_at", now)],
+            _ => vec![("created_at", now)],
+        };
+        for (column, value) in defaults {
+            row.entry(column.to_string()).or_insert(value);
+        }
+        row
+    }
+
+    fn find(&self, table: &str, id: &Value) -> Option<&Row> {
+        self.tables
+            .get(table)?
+            .iter()
+            .find(|row| row.get("id").is_some_and(|row_id| same(row_id, id)))
+    }
+
+    /// Checks `rows` (all of `table`, as it would be after the change)
+    /// against the table's constraints.
+    fn check(&self, table: &str, rows: &[Row]) -> Result<(), Rejection> {
+        for key in unique_keys(table) {
+            for (i, a) in rows.iter().enumerate() {
+                let clash = rows[i + 1..].iter().any(|b| conflicts(a, b, key));
+                if clash {
+                    return Err(error(
+                        StatusCode::CONFLICT,
+                        "23505",
+                        format!(
+                            "duplicate key value violates unique constraint on {:?}",
+                            key
+                        ),
+                    ));
+                }
+            }
+        }
+        for (column, target) in foreign_keys(table) {
+            for row in rows {
+                let value = row.get(*column).unwrap_or(&Value::Null);
+                if !value.is_null() && self.find(target, value).is_none() {
+                    return Err(error(
+                        StatusCode::CONFLICT,
+                        "23503",
+                        format!("{}.{} is not present in table {}", table, column, target),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes whatever references the deleted `rows` with `ON DELETE
+    /// CASCADE`, which every foreign key in the schema has.
+    fn cascade(&mut self, table: &str, rows: &[Row]) {
+        let tables: Vec<String> = self.tables.keys().cloned().collect();
+        for other in tables {
+            for (column, target) in foreign_keys(&other) {
+                if *target != table {
+                    continue;
+                }
+                let all = self.tables.get_mut(&other).expect("listed above");
+                let (gone, kept): (Vec<Row>, Vec<Row>) = all.drain(..).partition(|row| {
+                    rows.iter().any(|deleted| {
+                        let id = deleted.get("id").unwrap_or(&Value::Null);
+                        row.get(*column).is_some_and(|value| same(value, id))
+                    })
+                });
+                *all = kept;
+                self.cascade(&other, &gone);
+            }
+        }
+    }
+
+    /// `row` cut down to the `select`ed fields. `None` if an `!inner` embed
+    /// found nothing.
+    fn project(&self, table: &str, row: &Row, fields: &[Field]) -> Option<Row> {
+        let mut out = Row::new();
+        for field in fields {
+            match field {
+                Field::All => out.extend(row.clone()),
+                Field::Column(column) => {
+                    out.insert(column.clone(), row.get(column).cloned().unwrap_or_default());
+                }
+                Field::Embed {
+                    table: embedded,
+                    inner,
+                    fields,
+                } => {
+                    let (column, _) = foreign_keys(table)
+                        .iter()
+                        .find(|(_, target)| *target == embedded.as_str())
+                        .unwrap_or_else(|| panic!("no relationship {} -> {}", table, embedded));
+                    let target = row
+                        .get(*column)
+                        .and_then(|id| self.find(embedded, id))
+                        .and_then(|target| self.project(embedded, target, fields));
+                    match target {
+                        Some(target) => {
+                            out.insert(embedded.clone(), Value::Object(target));
+                        }
+                        None if *inner => return None,
+                        None => {
+                            out.insert(embedded.clone(), Value::Null);
+                        }
+                    }
+                }
+            }
+        }
+        Some(out)
+    }
+}
+
+/// Equal as stored values, ignoring how a UUID's case or a number's type
+/// happened to be written.
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.eq_ignore_ascii_case(b),
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => a == b,
+    }
+}
+
+/// Whether two rows share the `key` columns. Nulls never conflict.
+fn conflicts(a: &Row, b: &Row, key: &[&str]) -> bool {
+    key.iter()
+        .all(|column| match (a.get(*column), b.get(*column)) {
+            (Some(x), Some(y)) => !x.is_null() && same(x, y),
+            _ => false,
+        })
+}
+
+fn body_rows(body: &[u8]) -> Result<Vec<Row>, Rejection> {
+    let invalid = |e: String| error(StatusCode::BAD_REQUEST, "PGRST102", e);
+    match serde_json::from_slice::<Value>(body).map_err(|e| invalid(e.to_string()))? {
+        Value::Object(row) => Ok(vec![row]),
+        Value::Array(rows) => rows
+            .into_iter()
+            .map(|row| match row {
+                Value::Object(row) => Ok(row),
+                _ => Err(invalid("rows must be objects".to_string())),
+            })
+            .collect(),
+        _ => Err(invalid("body must be an object or an array".to_string())),
+    }
+}
+
+fn authorize(headers: &HeaderMap) -> Result<(), Rejection> {
+    let bearer = format!("Bearer {}", ANON_KEY);
+    let authorized = headers.get("apikey").is_some_and(|key| key == ANON_KEY)
+        && headers
+            .get(header::AUTHORIZATION)
+            .is_some_and(|value| value == bearer.as_str());
+    if authorized {
+        Ok(())
+    } else {
+        Err(error(
+            StatusCode::UNAUTHORIZED,
+            "PGRST301",
+            "No valid API key",
+        ))
+    }
+}
+
+async fn handle(
+    State(db): State<Arc<Mutex<Database>>>,
+    Path(table): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+    RawQuery(query): RawQuery,
+    body: Bytes,
+) -> Response {
+    if let Err(rejection) = authorize(&headers) {
+        return rejection.into_response();
+    }
+    let request = match Request::parse(query.as_deref().unwrap_or_default(), &headers) {
+        Ok(request) => request,
+        Err(e) => return error(StatusCode::BAD_REQUEST, "PGRST100", e).into_response(),
+    };
+
+    let mut db = db.lock().unwrap();
+    let Some(rows) = db.tables.get(&table).cloned() else {
+        return error(
+            StatusCode::NOT_FOUND,
+            "42P01",
+            format!("relation \"public.{}\" does not exist", table),
+        )
+        .into_response();
+    };
+
+    let user = db.current_user(&headers);
+    let result = match method {
+        Method::GET => return select(&db, &table, rows, &request),
+        Method::POST if table == "bans" => match db.check_bans(&body, user.as_ref()) {
+            Ok(()) => insert(&mut db, &table, rows, &request, &body),
+            Err(rejection) => Err(rejection),
+        },
+        Method::POST | Method::PATCH if owned(&table) => {
+            match check_owner(&table, &body, user.as_ref()) {
+                Ok(()) if method == Method::POST => insert(&mut db, &table, rows, &request, &body),
+                Ok(()) => update(&mut db, &table, rows, &request, &body),
+                Err(rejection) => Err(rejection),
+            }
+        }
+        Method::POST => insert(&mut db, &table, rows, &request, &body),
+        Method::PATCH => update(&mut db, &table, rows, &request, &body),
+        Method::DELETE => Ok(delete(&mut db, &table, rows, &request)),
+        _ => return StatusCode::METHOD_NOT_ALLOWED.into_response(),
+    };
+    let (status, affected) = match result {
+        Ok(result) => result,
+        Err(rejection) => return rejection.into_response(),
+    };
+    if request.prefers("return=representation") {
+        let affected: Vec<Row> = affected
+            .iter()
+            .filter_map(|row| db.project(&table, row, &request.select))
+            .collect();
+        let status = if status == StatusCode::NO_CONTENT {
+            StatusCode::OK
+        } else {
+            status
+        };
+        (status, Json(affected)).into_response()
+    } else {
+        status.into_response()
+    }
+}
+
+fn select(db: &Database, table: &str, rows: Vec<Row>, request: &Request) -> Response {
+    let mut rows: Vec<Row> = rows
+        .into_iter()
+        .filter(|row| request.matches(row))
+        .filter_map(|row| db.project(table, &row, &request.select).map(|_| row))
+        .collect();
+    rows.sort_by(|a, b| {
+        request
+            .order
+            .iter()
+            .map(|(column, descending)| {
+                let null = Value::Null;
+                let ordering = compare_values(
+                    a.get(column).unwrap_or(&null),
+                    b.get(column).unwrap_or(&null),
+                );
+                if *descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            })
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let total = rows.len();
+    let page: Vec<Row> = rows
+        .iter()
+        .skip(request.offset)
+        .take(request.limit.unwrap_or(usize::MAX))
+        .filter_map(|row| db.project(table, row, &request.select))
+        .collect();
+    let total = if request.prefers("count=exact") {
+        total.to_string()
+    } else {
+        "*".to_string()
+    };
+    let range = match page.len() {
+        0 => format!("*/{}", total),
+        n => format!("{}-{}/{}", request.offset, request.offset + n - 1, total),
+    };
+    (StatusCode::OK, [(header::CONTENT_RANGE, range)], Json(page)).into_response()
+}
+
+fn insert(
+    db: &mut Database,
+    table: &str,
+    mut rows: Vec<Row>,
+    request: &Request,
+    body: &[u8],
+) -> Result<(StatusCode, Vec<Row>), Rejection> {
+    let ignore = request.prefers("resolution=ignore-duplicates");
+    let merge = request.prefers("resolution=merge-duplicates");
+    let key: Vec<&str> = match &request.on_conflict {
+        Some(columns) => columns.iter().map(String::as_str).collect(),
+        None => unique_keys(table)[0].to_vec(),
+    };
+
+    let mut affected = Vec::new();
+    for new in body_rows(body)? {
+        match rows.iter().position(|row| conflicts(row, &new, &key)) {
+            Some(_) if ignore => {}
+            Some(existing) if merge => {
+                rows[existing].extend(new);
+                affected.push(rows[existing].clone());
+            }
+            _ => {
+                let row = db.with_defaults(table, new);
+                rows.push(row.clone());
+                affected.push(row);
+            }
+        }
+    }
+    db.check(table, &rows)?;
+    db.tables.insert(table.to_string(), rows);
+    Ok((StatusCode::CREATED, affected))
+}
+
+fn update(
+    db: &mut Database,
+    table: &str,
+    mut rows: Vec<Row>,
+    request: &Request,
+    body: &[u8],
+) -> Result<(StatusCode, Vec<Row>), Rejection> {
+    let changes = body_rows(body)?.pop().unwrap_or_default();
+    let mut affected = Vec::new();
+    for row in rows.iter_mut().filter(|row| request.matches(row)) {
+        row.extend(changes.clone());
+        affected.push(row.clone());
+    }
+    db.check(table, &rows)?;
+    db.tables.insert(table.to_string(), rows);
+    Ok((StatusCode::NO_CONTENT, affected))
+}
+
+fn delete(
+    db: &mut Database,
+    table: &str,
+    rows: Vec<Row>,
+    request: &Request,
+) -> (StatusCode, Vec<Row>) {
+    let (gone, kept): (Vec<Row>, Vec<Row>) = rows.into_iter().partition(|row| request.matches(row));
+    db.tables.insert(table.to_string(), kept);
+    db.cascade(table, &gone);
+    (StatusCode::NO_CONTENT, gone)
+}
+
+/// Rejects rows for `table` that aren't `user`'s, as the policies'
+/// `user_id = gooncord_current_user()` would.
+fn check_owner(table: &str, body: &[u8], user: Option<&Value>) -> Result<(), Rejection> {
+    for row in body_rows(body)? {
+        let owner = row.get("user_id");
+        let allowed = match (owner, user) {
+            (Some(owner), Some(user)) => same(owner, user),
+            // Updates that leave the owner alone.
+            (None, Some(_)) => true,
+            _ => false,
+        };
+        if !allowed {
+            return Err(policy_violation(table));
+        }
+    }
+    Ok(())
+}
+
+fn policy_violation(table: &str) -> Rejection {
+    error(
+        StatusCode::FORBIDDEN,
+        "42501",
+        format!(
+            "new row violates row-level security policy for table \"{}\"",
+            table
+        ),
+    )
+}
+
+/// Whether a ban expiring at `expires_at` for `reason` stays within what
+/// the database allows: up to an hour from now, with a short reason.
+fn ban_in_bounds(reason: &str, expires_at: Option<DateTime<Utc>>) -> bool {
+    let now = Utc::now();
+    (1..=200).contains(&reason.chars().count())
+        && expires_at.is_some_and(|e| e > now && e <= now + chrono::Duration::hours(1))
+}
+
+/// The `gooncord_*` functions from `0004_row_level_security.sql`, called
+/// as PostgREST calls them: arguments as a JSON object, results as JSON.
+async fn rpc(
+    State(db): State<Arc<Mutex<Database>>>,
+    Path(function): Path<String>,
+    headers: HeaderMap,
+    Json(args): Json<Value>,
+) -> Response {
+    if let Err(rejection) = authorize(&headers) {
+        return rejection.into_response();
+    }
+    let arg = |name: &str| args[name].as_str().unwrap_or_default().to_string();
+    let mut db = db.lock().unwrap();
+    let result = match function.as_str() {
+        "gooncord_register" => db.register(&arg("p_username"), &arg("p_password")),
+        "gooncord_login" => Ok(db.login(&arg("p_username"), &arg("p_password"))),
+        "gooncord_session" => Ok(db.session(&arg("p_token"))),
+        "gooncord_end_session" => {
+            let token = json!(arg("p_token"));
+            let sessions = db.tables.get_mut("sessions").expect("migrated");
+            sessions.retain(|row| row.get("token") != Some(&token));
+            return StatusCode::NO_CONTENT.into_response();
+        }
+        "gooncord_bot" => Ok(db.bot(&arg("p_token"))),
+        "gooncord_post_webhook" => db.post_webhook(&arg("p_secret"), &arg("p_content")),
+        "gooncord_outgoing_webhooks" => Ok(db.outgoing_webhooks(&headers)),
+        "gooncord_redirect_ban" => {
+            db.redirect_ban(&headers, &arg("p_reason"), &arg("p_expires_at"))
+        }
+        _ => Err(error(
+            StatusCode::NOT_FOUND,
+            "PGRST202",
+            format!("Could not find the function public.{}", function),
+        )),
+    };
+    match result {
+        Ok(value) => (StatusCode::OK, Json(value)).into_response(),
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+impl Database {
+    /// Who `X-Gooncord-Session` signs in as, like `gooncord_current_user()`.
+    fn current_user(&self, headers: &HeaderMap) -> Option<Value> {
+        let token = json!(headers.get("x-gooncord-session")?.to_str().ok()?);
+        let now = Utc::now();
+        let session = self.tables["sessions"].iter().find(|row| {
+            row.get("token") == Some(&token)
+                && row
+                    .get("expires_at")
+                    .and_then(Value::as_str)
+                    .and_then(parse_time)
+                    .is_some_and(|expires_at| expires_at > now)
+        });
+        let bot = || {
+            self.tables["bot_tokens"]
+                .iter()
+                .find(|row| row.get("token") == Some(&token))
+        };
+        session
+            .or_else(bot)
+            .and_then(|row| row.get("user_id").cloned())
+    }
+
+    fn start_session(&mut self, user_id: &Value) -> Value {
+        let token = Uuid::new_v4().to_string();
+        let expires_at = Utc::now() + chrono::Duration::days(30);
+        let row = json!({ "token": token, "user_id": user_id, "expires_at": expires_at });
+        let Value::Object(row) = row else {
+            unreachable!("built as an object")
+        };
+        self.tables.get_mut("sessions").expect("migrated").push(row);
+        self.session(&token)
+    }
+
+    fn session(&self, token: &str) -> Value {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-gooncord-session",
+            token.parse().expect("tokens are ASCII"),
+        );
+        let Some(user_id) = self.current_user(&headers) else {
+            return Value::Null;
+        };
+        match self.find("users", &user_id) {
+            Some(user) => json!({
+                "token": token,
+                "user_id": user_id,
+                "username": user["username"],
+                "is_moderator": user["is_moderator"],
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn register(&mut self, username: &str, password: &str) -> Result<Value, Rejection> {
+        auth::validate_username(username)
+            .map_err(|e| error(StatusCode::BAD_REQUEST, "22023", e))?;
+        let taken = self.tables["users"]
+            .iter()
+            .any(|row| row["username"] == username);
+        if taken {
+            return Err(error(
+                StatusCode::CONFLICT,
+                "23505",
+                "Username already exists",
+            ));
+        }
+        let id = json!(Uuid::new_v4());
+        let password_hash = bcrypt::hash(password, 4).expect("hashes");
+        let row = json!({ "id": id, "username": username, "password_hash": password_hash });
+        let row = self.with_defaults("users", row.as_object().expect("an object").clone());
+        self.tables.get_mut("users").expect("migrated").push(row);
+        Ok(self.start_session(&id))
+    }
+
+    fn login(&mut self, username: &str, password: &str) -> Value {
+        let user = self.tables["users"]
+            .iter()
+            .find(|row| row["username"] == username && row["is_bot"] != true);
+        let Some(user) = user else {
+            return Value::Null;
+        };
+        let hash = user["password_hash"].as_str().unwrap_or_default();
+        if !bcrypt::verify(password, hash).unwrap_or(false) {
+            return Value::Null;
+        }
+        let id = user["id"].clone();
+        self.start_session(&id)
+    }
+
+    fn bot(&self, token: &str) -> Value {
+        let token = json!(token);
+        self.tables["bot_tokens"]
+            .iter()
+            .find(|row| row.get("token") == Some(&token))
+            .and_then(|row| self.find("users", &row["user_id"]))
+            .map_or(
+                Value::Null,
+                |user| json!({ "user_id": user["id"], "username": user["username"] }),
+            )
+    }
+
+    fn post_webhook(&mut self, secret: &str, content: &str) -> Result<Value, Rejection> {
+        if !(1..=2000).contains(&content.chars().count()) {
+            let message = "Content must be 1 to 2000 characters";
+            return Err(error(StatusCode::BAD_REQUEST, "22023", message));
+        }
+        let hook = self.tables["incoming_webhooks"]
+            .iter()
+            .find(|row| row["secret"] == secret)
+            .cloned();
+        let Some(hook) = hook else {
+            return Ok(json!(false));
+        };
+        let message = json!({
+            "client_id": Uuid::new_v4(),
+            "channel": hook["channel"],
+            "content": content,
+            "user_id": hook["user_id"],
+        });
+        let message = message.as_object().expect("an object").clone();
+        let message = self.with_defaults("messages", message);
+        self.tables
+            .get_mut("messages")
+            .expect("migrated")
+            .push(message);
+        Ok(json!(true))
+    }
+
+    /// `bans_create`: people ban themselves, moderators and bots anyone, and
+    /// `created_at` isn't granted.
+    fn check_bans(&self, body: &[u8], user: Option<&Value>) -> Result<(), Rejection> {
+        let Some(user) = user else {
+            return Err(policy_violation("bans"));
+        };
+        let bans_anyone = self
+            .find("users", user)
+            .is_some_and(|caller| caller["is_moderator"] == true || caller["is_bot"] == true);
+        for row in body_rows(body)? {
+            if row.contains_key("created_at") {
+                let message = "permission denied for table bans";
+                return Err(error(StatusCode::FORBIDDEN, "42501", message));
+            }
+            let reason = row
+                .get("reason")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let expires_at = row
+                .get("expires_at")
+                .and_then(Value::as_str)
+                .and_then(parse_time);
+            let whose = row.get("user_id").is_some_and(|owner| same(owner, user)) || bans_anyone;
+            if !whose || !ban_in_bounds(reason, expires_at) {
+                return Err(policy_violation("bans"));
+            }
+        }
+        Ok(())
+    }
+
+    fn redirect_ban(
+        &mut self,
+        headers: &HeaderMap,
+        reason: &str,
+        expires_at: &str,
+    ) -> Result<Value, Rejection> {
+        if !ban_in_bounds(reason, parse_time(expires_at)) {
+            return Err(error(
+                StatusCode::BAD_REQUEST,
+                "22023",
+                "Bans last up to an hour",
+            ));
+        }
+        let user = self.current_user(headers).unwrap_or(Value::Null);
+        let is_redirect = |row: &Row| same(&row["user_id"], &user) && row["item"] == "ban_redirect";
+        if !self.tables["active_effects"].iter().any(is_redirect) {
+            return Err(error(
+                StatusCode::FORBIDDEN,
+                "42501",
+                "No ban redirect in effect",
+            ));
+        }
+        let target = self.tables["users"]
+            .iter()
+            .find(|row| !same(&row["id"], &user) && row["is_bot"] != true)
+            .cloned();
+        let Some(target) = target else {
+            let message = "Nobody else to redirect the ban to";
+            return Err(error(StatusCode::BAD_REQUEST, "P0002", message));
+        };
+
+        let effects = self.tables.get_mut("active_effects").expect("migrated");
+        effects.retain(|row| !is_redirect(row));
+        let ban = json!({ "user_id": target["id"], "reason": reason, "expires_at": expires_at });
+        let ban = self.with_defaults("bans", ban.as_object().expect("an object").clone());
+        self.tables.get_mut("bans").expect("migrated").push(ban);
+        Ok(target["username"].clone())
+    }
+
+    fn outgoing_webhooks(&self, headers: &HeaderMap) -> Value {
+        let Some(relay) = self.current_user(headers) else {
+            return json!([]);
+        };
+        let hooks: Vec<Value> = self.tables["outgoing_webhooks"]
+            .iter()
+            .filter(|row| same(&row["relay_id"], &relay))
+            .map(|row| {
+                json!({
+                    "id": row["id"],
+                    "url": row["url"],
+                    "secret": row["secret"],
+                    "events": row["events"],
+                })
+            })
+            .collect();
+        json!(hooks)
+    }
+}
+
+/// The tables the migrations create, by name.
+fn schema_tables() -> Vec<String> {
+    let marker = "CREATE TABLE IF NOT EXISTS ";
+    MIGRATIONS
+        .iter()
+        .map(|migration| migration.sql)
+        .chain([CREATE_VERSION_TABLE])
+        .flat_map(|sql| {
+            sql.match_indices(marker)
+                .map(move |(i, _)| &sql[i + marker.len()..])
+        })
+        .filter_map(|rest| rest.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
+}
+
+impl MockPostgrest {
+    /// Serves an empty, fully migrated database on a free local port.
+    pub async fn start() -> MockPostgrest {
+        let mut db = Database::default();
+        for table in schema_tables() {
+            db.tables.insert(table, Vec::new());
+        }
+        let mock = MockPostgrest {
+            url: String::new(),
+            db: Arc::new(Mutex::new(db)),
+        };
+        mock.set_schema_version(MIGRATIONS.last().map(|m| m.version));
+
+        let app = Router::new()
+            .route("/rest/v1/rpc/:function", post(rpc))
+            .route("/rest/v1/:table", any(handle))
+            .with_state(mock.db.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        MockPostgrest { url, ..mock }
+    }
+
+    pub fn api(&self) -> ApiClient {
+        ApiClient::new(self.url.clone(), ANON_KEY.to_string())
+    }
+
+    /// Every row of `table`, in insertion order.
+    pub fn rows(&self, table: &str) -> Vec<Row> {
+        self.db.lock().unwrap().tables[table].clone()
+    }
+
+    /// Inserts `row` into `table` directly, defaults and all, bypassing
+    /// the HTTP side.
+    pub fn insert(&self, table: &str, row: Value) -> Row {
+        let mut db = self.db.lock().unwrap();
+        let Value::Object(row) = row else {
+            panic!("rows are objects");
+        };
+        let row = db.with_defaults(table, row);
+        db.tables.get_mut(table).unwrap().push(row.clone());
+        row
+    }
+
+    /// Records the database as migrated up to `version`, or drops
+    /// `schema_migrations` altogether for `None`.
+    pub fn set_schema_version(&self, version: Option<i64>) {
+        let mut db = self.db.lock().unwrap();
+        match version {
+            Some(version) => {
+                let applied = MIGRATIONS
+                    .iter()
+                    .filter(|migration| migration.version <= version)
+                    .map(|migration| {
+                        let row = json!({ "version": migration.version, "name": migration.name });
+                        db.with_defaults("schema_migrations", row.as_object().unwrap().clone())
+                    })
+                    .collect();
+                db.tables.insert("schema_migrations".to_string(), applied);
+            }
+            None => {
+                db.tables.remove("schema_migrations");
+            }
+        }
+    }
+}
@@ -0,0 +1,420 @@
+use axum::extract::{Path, Query, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use gooncord_core::backend::MAX_MESSAGES;
+use gooncord_core::sequencer;
+use gooncord_core::{Ban, ReceivedMessage, Session};
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use uuid::Uuid;
+
+use crate::{Server, ServerEvent};
+
+/// Longest message anyone may post.
+pub const MAX_CONTENT_LEN: usize = 2000;
+/// Most messages one request can fetch.
+pub const MAX_PAGE: usize = 100;
+/// Longest ban reason anyone may give.
+pub const MAX_REASON_LEN: usize = 200;
+/// Longest ban anyone, moderators included, may hand out through the API.
+pub const MAX_BAN: chrono::Duration = chrono::Duration::hours(1);
+
+type Rejection = (StatusCode, String);
+
+#[derive(Deserialize)]
+struct Credentials {
+    username: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct MessagesParams {
+    latest: Option<usize>,
+    after: Option<i64>,
+    before: Option<i64>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct NewMessage {
+    client_id: Uuid,
+    /// Optional; if given it has to be the sender's own.
+    user_id: Option<Uuid>,
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct NewBan {
+    user_id: Uuid,
+    reason: String,
+    expires_at: DateTime<Utc>,
+}
+
+pub fn router(server: Arc<Server>) -> Router {
+    Router::new()
+        .route("/api/register", post(register))
+        .route("/api/login", post(login))
+        .route("/api/logout", post(logout))
+        .route("/api/messages", get(messages).post(send))
+        .route("/api/users/:id/bans", get(bans))
+        .route("/api/bans", post(ban))
+        .route("/api/events", get(events))
+        .with_state(server)
+}
+
+fn unavailable(e: String) -> Rejection {
+    (StatusCode::INTERNAL_SERVER_ERROR, e)
+}
+
+/// The session behind the request's bearer token, if it has a valid one.
+/// Anyone with a session is counted as online.
+async fn session(server: &Server, headers: &HeaderMap) -> Result<Option<Session>, Rejection> {
+    let Some(token) = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return Ok(None);
+    };
+    let session = server
+        .storage
+        .find_session(token)
+        .await
+        .map_err(unavailable)?;
+    if let Some(session) = &session {
+        server.seen(session);
+    }
+    Ok(session)
+}
+
+async fn require_session(server: &Server, headers: &HeaderMap) -> Result<Session, Rejection> {
+    session(server, headers)
+        .await?
+        .ok_or((StatusCode::UNAUTHORIZED, "Log in first".to_string()))
+}
+
+async fn register(
+    State(server): State<Arc<Server>>,
+    Json(credentials): Json<Credentials>,
+) -> Result<Json<Session>, Rejection> {
+    let session = server
+        .storage
+        .register(&credentials.username, &credentials.password)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    server.seen(&session);
+    Ok(Json(session))
+}
+
+async fn login(
+    State(server): State<Arc<Server>>,
+    Json(credentials): Json<Credentials>,
+) -> Result<Json<Session>, Rejection> {
+    let session = server
+        .storage
+        .login(&credentials.username, &credentials.password)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e))?;
+    server.seen(&session);
+    Ok(Json(session))
+}
+
+async fn logout(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+) -> Result<StatusCode, Rejection> {
+    if let Some(session) = session(&server, &headers).await? {
+        server
+            .storage
+            .end_session(&session.token)
+            .await
+            .map_err(unavailable)?;
+        server.gone(session.user_id);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Maps query parameters onto what the client's sequencer asked for.
+fn query(params: &MessagesParams) -> sequencer::Query {
+    let limit = |limit: Option<usize>| limit.unwrap_or(MAX_PAGE).min(MAX_PAGE);
+    match (params.latest, params.after, params.before) {
+        (_, Some(after), Some(before)) => sequencer::Query::Between { after, before },
+        (_, Some(seq), None) => sequencer::Query::After {
+            seq,
+            limit: limit(params.limit),
+        },
+        (latest, None, _) => sequencer::Query::Latest {
+            limit: limit(latest.or(Some(MAX_MESSAGES))),
+        },
+    }
+}
+
+async fn messages(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+    Query(params): Query<MessagesParams>,
+) -> Result<Json<Vec<ReceivedMessage>>, Rejection> {
+    session(&server, &headers).await?;
+    let messages = server
+        .storage
+        .get_messages(query(&params))
+        .await
+        .map_err(unavailable)?;
+    Ok(Json(messages))
+}
+
+async fn send(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+    Json(message): Json<NewMessage>,
+) -> Result<StatusCode, Rejection> {
+    let session = require_session(&server, &headers).await?;
+    if message.user_id.is_some_and(|id| id != session.user_id) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "You can only post as yourself".to_string(),
+        ));
+    }
+    let content = message.content.trim();
+    if content.is_empty() || content.len() > MAX_CONTENT_LEN {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("content must be 1 to {} bytes", MAX_CONTENT_LEN),
+        ));
+    }
+    if let Some(ban) = server
+        .storage
+        .current_ban(session.user_id, Utc::now())
+        .await
+        .map_err(unavailable)?
+    {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!(
+                "Banned until {}: {}",
+                ban.expires_at.to_rfc3339(),
+                ban.reason
+            ),
+        ));
+    }
+    server
+        .storage
+        .send_message(message.client_id, session.user_id, content)
+        .await
+        .map_err(unavailable)?;
+    Ok(StatusCode::CREATED)
+}
+
+async fn bans(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<Vec<Ban>>, Rejection> {
+    let session = require_session(&server, &headers).await?;
+    if user_id != session.user_id && !session.is_moderator {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only moderators can see other people's bans".to_string(),
+        ));
+    }
+    let bans = server
+        .storage
+        .ban_history(user_id)
+        .await
+        .map_err(unavailable)?;
+    Ok(Json(bans))
+}
+
+async fn ban(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+    Json(ban): Json<NewBan>,
+) -> Result<StatusCode, Rejection> {
+    let session = require_session(&server, &headers).await?;
+    if ban.user_id != session.user_id && !session.is_moderator {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only moderators can ban other people".to_string(),
+        ));
+    }
+    let reason = ban.reason.trim();
+    if reason.is_empty() || reason.len() > MAX_REASON_LEN {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("reason must be 1 to {} bytes", MAX_REASON_LEN),
+        ));
+    }
+    let now = Utc::now();
+    if ban.expires_at <= now || ban.expires_at > now + MAX_BAN {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!(
+                "expires_at must be within {} minutes",
+                MAX_BAN.num_minutes()
+            ),
+        ));
+    }
+    server
+        .ban(ban.user_id, reason, ban.expires_at)
+        .await
+        .map_err(unavailable)?;
+    Ok(StatusCode::CREATED)
+}
+
+async fn events(
+    State(server): State<Arc<Server>>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, Rejection> {
+    require_session(&server, &headers).await?;
+    // A subscriber too slow to keep up skips what it missed.
+    let stream = BroadcastStream::new(server.subscribe()).filter_map(|event| {
+        let event = event.ok()?;
+        let name = match event {
+            ServerEvent::Message(_) => "message",
+            ServerEvent::Ban { .. } => "ban",
+        };
+        Event::default().event(name).json_data(&event).ok().map(Ok)
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gooncord_core::backend::{ServerBackend, SqliteBackend};
+    use gooncord_core::Backend;
+
+    async fn serve() -> String {
+        let server = Server::new(Arc::new(SqliteBackend::in_memory().unwrap()));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(server)).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn turns_away_anonymous_requests() {
+        let url = serve().await;
+        let http = reqwest::Client::new();
+        for path in [
+            format!("/api/users/{}/bans", Uuid::new_v4()),
+            "/api/events".to_string(),
+        ] {
+            let response = http.get(format!("{}{}", url, path)).send().await.unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[test]
+    fn caps_page_sizes() {
+        let params = |latest, after, before, limit| MessagesParams {
+            latest,
+            after,
+            before,
+            limit,
+        };
+        assert!(matches!(
+            query(&params(None, None, None, None)),
+            sequencer::Query::Latest {
+                limit: MAX_MESSAGES
+            }
+        ));
+        assert!(matches!(
+            query(&params(Some(5000), None, None, None)),
+            sequencer::Query::Latest { limit: MAX_PAGE }
+        ));
+        assert!(matches!(
+            query(&params(None, Some(3), None, Some(10))),
+            sequencer::Query::After { seq: 3, limit: 10 }
+        ));
+        assert!(matches!(
+            query(&params(None, Some(3), Some(9), None)),
+            sequencer::Query::Between {
+                after: 3,
+                before: 9
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn chats_and_bans_through_the_server_backend() {
+        let url = serve().await;
+        let alice = ServerBackend::new(url.clone());
+        let session = alice.register("alice", "hunter22").await.unwrap();
+        assert!(alice.register("alice", "again").await.is_err());
+        assert!(ServerBackend::new(url.clone())
+            .login("alice", "wrong")
+            .await
+            .is_err());
+
+        alice
+            .send_message(Uuid::new_v4(), session.user_id, "hello")
+            .await
+            .unwrap();
+        let messages = alice
+            .get_messages(sequencer::Query::Latest { limit: 10 })
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].username, "alice");
+
+        // Nobody else gets to post as alice or ban her.
+        let bob = ServerBackend::new(url.clone());
+        bob.register("bob", "hunter22").await.unwrap();
+        assert!(bob
+            .send_message(Uuid::new_v4(), session.user_id, "I am alice")
+            .await
+            .is_err());
+        let expires_at = Utc::now() + chrono::Duration::minutes(5);
+        assert!(bob
+            .create_ban(session.user_id, "Framed", expires_at)
+            .await
+            .is_err());
+
+        alice
+            .create_ban(session.user_id, "Volunteered", expires_at)
+            .await
+            .unwrap();
+        assert_eq!(
+            alice
+                .current_ban(session.user_id, Utc::now())
+                .await
+                .unwrap()
+                .unwrap()
+                .reason,
+            "Volunteered"
+        );
+        assert!(alice
+            .send_message(Uuid::new_v4(), session.user_id, "still here?")
+            .await
+            .is_err());
+
+        // Bans stay short and to the point, whoever hands them out.
+        for (reason, expires_at) in [
+            ("", expires_at),
+            ("Rambling", Utc::now() + chrono::Duration::hours(2)),
+            ("Too late", Utc::now() - chrono::Duration::minutes(1)),
+        ] {
+            assert!(alice
+                .create_ban(session.user_id, reason, expires_at)
+                .await
+                .is_err());
+        }
+        assert!(bob.ban_history(session.user_id).await.is_err());
+        assert_eq!(alice.ban_history(session.user_id).await.unwrap().len(), 1);
+
+        alice.end_session(&session.token).await.unwrap();
+        alice.resume(&session);
+        assert!(alice
+            .send_message(Uuid::new_v4(), session.user_id, "after logout")
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! A self-hosted Gooncord server: accounts, sessions, messages and bans over
+//! a small JSON API, with ban rolls done here instead of on every client.
+//! Storage is SQLite or Postgres; Postgres gets its schema from [`migrate`],
+//! which `gooncord-server migrate` also runs against a Supabase database.
+//! Point the desktop app or `gooncord-cli` at the server with
+//! `GOONCORD_BACKEND=server` and `GOONCORD_SERVER_URL`.
+//!
+//! | Route                      |                                                 |
+//! |----------------------------|-------------------------------------------------|
+//! | `POST /api/register`       | `{username, password}`, answers with a session  |
+//! | `POST /api/login`          | same                                            |
+//! | `POST /api/logout`         | ends the bearer token's session                 |
+//! | `GET /api/messages`        | `?latest=n`, `?after=seq&limit=n` or `?after=a&before=b` |
+//! | `POST /api/messages`       | `{client_id, content}`, as the bearer           |
+//! | `GET /api/users/:id/bans`  | ban history, newest first; your own unless a moderator |
+//! | `POST /api/bans`           | `{user_id, reason, expires_at}`; ≤1h, anyone for moderators |
+//! | `GET /api/events`          | server-sent `message` and `ban` events, for the bearer |
+
+pub mod api;
+pub mod migrate;
+pub mod postgres;
+pub mod rolls;
+
+use chrono::{DateTime, Utc};
+use gooncord_core::backend::SqliteBackend;
+use gooncord_core::ban_events::BanPolicy;
+use gooncord_core::sequencer::Sequencer;
+use gooncord_core::{Backend, ReceivedMessage, Session};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use postgres::PostgresBackend;
+
+/// Someone who made an authenticated request this recently is online, and
+/// so in the running for a ban.
+const ONLINE_WINDOW: Duration = Duration::from_secs(30);
+/// How often storage is checked for new messages to announce.
+const FANOUT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What `/api/events` streams.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ServerEvent {
+    Message(ReceivedMessage),
+    Ban {
+        user_id: Uuid,
+        reason: String,
+        expires_at: DateTime<Utc>,
+    },
+}
+
+pub struct Server {
+    storage: Arc<dyn Backend>,
+    online: Mutex<HashMap<Uuid, (Session, Instant)>>,
+    events: broadcast::Sender<ServerEvent>,
+}
+
+/// Opens `GOONCORD_DATABASE_URL`-style storage: a `postgres://` URL, or a
+/// SQLite file path.
+pub async fn open_storage(database: &str) -> Result<Arc<dyn Backend>, String> {
+    if database.starts_with("postgres://") || database.starts_with("postgresql://") {
+        Ok(Arc::new(PostgresBackend::connect(database).await?))
+    } else {
+        let path = database.strip_prefix("sqlite://").unwrap_or(database);
+        Ok(Arc::new(SqliteBackend::open(Path::new(path))?))
+    }
+}
+
+impl Server {
+    pub fn new(storage: Arc<dyn Backend>) -> Arc<Self> {
+        let (events, _) = broadcast::channel(256);
+        Arc::new(Server {
+            storage,
+            online: Mutex::new(HashMap::new()),
+            events,
+        })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.events.subscribe()
+    }
+
+    fn announce(&self, event: ServerEvent) {
+        // Nobody listening is fine.
+        let _ = self.events.send(event);
+    }
+
+    fn seen(&self, session: &Session) {
+        self.online
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(session.user_id, (session.clone(), Instant::now()));
+    }
+
+    fn gone(&self, user_id: Uuid) {
+        self.online
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&user_id);
+    }
+
+    fn online(&self) -> Vec<Session> {
+        let mut online = self.online.lock().unwrap_or_else(|e| e.into_inner());
+        online.retain(|_, (_, seen)| seen.elapsed() < ONLINE_WINDOW);
+        online
+            .values()
+            .map(|(session, _)| session.clone())
+            .collect()
+    }
+
+    /// Bans `user_id` and tells everyone.
+    async fn ban(
+        &self,
+        user_id: Uuid,
+        reason: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        self.storage.create_ban(user_id, reason, expires_at).await?;
+        self.announce(ServerEvent::Ban {
+            user_id,
+            reason: reason.to_string(),
+            expires_at,
+        });
+        Ok(())
+    }
+
+    /// Rolls everyone online for a ban every few seconds, forever.
+    pub async fn roll_bans(self: Arc<Self>) {
+        let mut policy = BanPolicy::default();
+        let mut interval = tokio::time::interval(Duration::from_secs(rolls::ROLL_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let now = Utc::now();
+            policy.set_events(Vec::new(), now);
+
+            for session in self.online() {
+                match self.storage.current_ban(session.user_id, now).await {
+                    Ok(None) => {}
+                    Ok(Some(_)) => continue,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        continue;
+                    }
+                }
+                let chance = policy.chance_for(&session.username, now);
+                let Some(roll) = rolls::roll(&mut rand::thread_rng(), chance) else {
+                    continue;
+                };
+                let expires_at = now + chrono::Duration::seconds(roll.duration_secs);
+                if let Err(e) = self.ban(session.user_id, roll.reason, expires_at).await {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+    }
+
+    /// Announces new messages as storage sees them, forever.
+    pub async fn fan_out_messages(self: Arc<Self>) {
+        let mut sequencer = Sequencer::default();
+        let storage = self.storage.clone();
+        // The backlog is history, not news.
+        let mut backlog = true;
+        loop {
+            match sequencer
+                .poll(Utc::now(), |query| storage.get_messages(query))
+                .await
+            {
+                Ok(messages) if !backlog => {
+                    for message in messages {
+                        self.announce(ServerEvent::Message(message));
+                    }
+                }
+                Ok(_) => backlog = false,
+                Err(e) => eprintln!("{}", e),
+            }
+            tokio::time::sleep(FANOUT_INTERVAL).await;
+        }
+    }
+}
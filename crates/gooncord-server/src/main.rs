@@ -0,0 +1,46 @@
+use gooncord_core::migrations::SCHEMA_VERSION;
+use gooncord_server::{api, migrate, open_storage, postgres, Server};
+use std::env;
+
+/// Where the server listens unless `GOONCORD_SERVER_ADDR` says otherwise.
+const DEFAULT_ADDR: &str = "127.0.0.1:8080";
+/// Storage unless `GOONCORD_DATABASE_URL` says otherwise.
+const DEFAULT_DATABASE: &str = "gooncord-server.sqlite3";
+
+/// `gooncord-server migrate`: brings the Postgres (or Supabase) database at
+/// `GOONCORD_DATABASE_URL` up to date, then exits.
+async fn migrate() -> Result<(), String> {
+    let database = env::var("GOONCORD_DATABASE_URL")
+        .map_err(|_| "GOONCORD_DATABASE_URL is not set".to_string())?;
+    let mut client = postgres::connect(&database).await?;
+    let applied = migrate::migrate(&mut client).await?;
+    for migration in &applied {
+        println!("Applied {:04}_{}", migration.version, migration.name);
+    }
+    println!("Schema is at version {}", SCHEMA_VERSION);
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    match env::args().nth(1).as_deref() {
+        Some("migrate") => return migrate().await,
+        Some(other) => return Err(format!("Unknown command {:?}; try `migrate`", other)),
+        None => {}
+    }
+
+    let database =
+        env::var("GOONCORD_DATABASE_URL").unwrap_or_else(|_| DEFAULT_DATABASE.to_string());
+    let server = Server::new(open_storage(&database).await?);
+    tokio::spawn(server.clone().fan_out_messages());
+    tokio::spawn(server.clone().roll_bans());
+
+    let addr = env::var("GOONCORD_SERVER_ADDR").unwrap_or_else(|_| DEFAULT_ADDR.to_string());
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .map_err(|e| format!("Can't listen on {}: {}", addr, e))?;
+    println!("Gooncord server on http://{}", addr);
+    axum::serve(listener, api::router(server))
+        .await
+        .map_err(|e| e.to_string())
+}
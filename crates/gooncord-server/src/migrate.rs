@@ -0,0 +1,42 @@
+use gooncord_core::migrations::{self, Migration, CREATE_VERSION_TABLE};
+use tokio_postgres::Client;
+
+/// Applies every migration `client`'s database hasn't had yet, each in its
+/// own transaction, and answers with the ones it applied.
+pub async fn migrate(client: &mut Client) -> Result<Vec<&'static Migration>, String> {
+    client
+        .batch_execute(CREATE_VERSION_TABLE)
+        .await
+        .map_err(|e| format!("Failed to create schema_migrations: {}", e))?;
+    let applied: Vec<i64> = client
+        .query("SELECT version FROM schema_migrations", &[])
+        .await
+        .map_err(|e| format!("Failed to read schema_migrations: {}", e))?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    let pending = migrations::pending(&applied);
+    for migration in &pending {
+        let failed = |e: tokio_postgres::Error| {
+            format!(
+                "Migration {:04}_{} failed: {}",
+                migration.version, migration.name, e
+            )
+        };
+        let transaction = client.transaction().await.map_err(failed)?;
+        transaction
+            .batch_execute(migration.sql)
+            .await
+            .map_err(failed)?;
+        transaction
+            .execute(
+                "INSERT INTO schema_migrations (version, name) VALUES ($1, $2)",
+                &[&migration.version, &migration.name],
+            )
+            .await
+            .map_err(failed)?;
+        transaction.commit().await.map_err(failed)?;
+    }
+    Ok(pending)
+}
@@ -0,0 +1,246 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use gooncord_core::backend::{User, MAX_MESSAGES};
+use gooncord_core::sequencer::Query;
+use gooncord_core::{Backend, Ban, ReceivedMessage, Session, DEFAULT_CHANNEL};
+use tokio_postgres::{Client, NoTls, Row};
+use uuid::Uuid;
+
+use crate::migrate::migrate;
+
+/// Server storage in Postgres, for when one SQLite file won't do.
+pub struct PostgresBackend {
+    client: Client,
+}
+
+const MESSAGE_COLUMNS: &str = "m.id, m.seq, m.client_id, m.content, m.created_at, m.user_id, \
+                               u.username FROM messages m JOIN users u ON u.id = m.user_id";
+
+fn message(row: &Row) -> ReceivedMessage {
+    let id: Uuid = row.get(0);
+    let created_at: DateTime<Utc> = row.get(4);
+    let user_id: Uuid = row.get(5);
+    ReceivedMessage {
+        id: id.to_string(),
+        seq: row.get(1),
+        client_id: row.get(2),
+        content: row.get(3),
+        created_at: created_at.to_rfc3339(),
+        user_id: user_id.to_string(),
+        username: row.get(6),
+    }
+}
+
+/// Connects to the Postgres at `url`, driving the connection on a task of
+/// its own.
+pub async fn connect(url: &str) -> Result<Client, String> {
+    let (client, connection) = tokio_postgres::connect(url, NoTls)
+        .await
+        .map_err(|e| format!("Can't connect to Postgres: {}", e))?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("Lost the Postgres connection: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+impl PostgresBackend {
+    /// Connects to `url` and brings its schema up to date.
+    pub async fn connect(url: &str) -> Result<Self, String> {
+        let mut client = connect(url).await?;
+        migrate(&mut client).await?;
+        Ok(PostgresBackend { client })
+    }
+}
+
+#[async_trait]
+impl Backend for PostgresBackend {
+    async fn find_user(&self, username: &str) -> Result<Option<User>, String> {
+        let row = self
+            .client
+            .query_opt(
+                "SELECT id, username, password_hash, is_moderator, created_at
+                 FROM users WHERE username = $1",
+                &[&username],
+            )
+            .await
+            .map_err(|e| format!("Failed to look up user: {}", e))?;
+        Ok(row.map(|row| User {
+            id: row.get(0),
+            username: row.get(1),
+            password_hash: row.get(2),
+            is_moderator: row.get(3),
+            created_at: row.get(4),
+        }))
+    }
+
+    async fn create_user(&self, user: &User) -> Result<(), String> {
+        self.client
+            .execute(
+                "INSERT INTO users (id, username, password_hash, is_moderator, created_at)
+                 VALUES ($1, $2, $3, $4, $5)",
+                &[
+                    &user.id,
+                    &user.username,
+                    &user.password_hash,
+                    &user.is_moderator,
+                    &user.created_at,
+                ],
+            )
+            .await
+            .map_err(|e| format!("User creation failed: {}", e))?;
+        Ok(())
+    }
+
+    async fn create_session(
+        &self,
+        user_id: Uuid,
+        token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        self.client
+            .execute(
+                "INSERT INTO sessions (token, user_id, expires_at) VALUES ($1, $2, $3)",
+                &[&token, &user_id, &expires_at],
+            )
+            .await
+            .map_err(|e| format!("Session creation failed: {}", e))?;
+        Ok(())
+    }
+
+    async fn end_session(&self, token: &str) -> Result<(), String> {
+        self.client
+            .execute("DELETE FROM sessions WHERE token = $1", &[&token])
+            .await
+            .map_err(|e| format!("Failed to end session: {}", e))?;
+        Ok(())
+    }
+
+    async fn find_session(&self, token: &str) -> Result<Option<Session>, String> {
+        let row = self
+            .client
+            .query_opt(
+                "SELECT s.user_id, u.username, u.is_moderator
+                 FROM sessions s JOIN users u ON u.id = s.user_id
+                 WHERE s.token = $1 AND s.expires_at > now()",
+                &[&token],
+            )
+            .await
+            .map_err(|e| format!("Failed to look up session: {}", e))?;
+        Ok(row.map(|row| Session {
+            token: token.to_string(),
+            user_id: row.get(0),
+            username: row.get(1),
+            is_moderator: row.get(2),
+        }))
+    }
+
+    async fn get_messages(&self, query: Query) -> Result<Vec<ReceivedMessage>, String> {
+        let rows = match query {
+            Query::Latest { limit } => {
+                let sql = format!("SELECT {} ORDER BY m.seq DESC LIMIT $1", MESSAGE_COLUMNS);
+                let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+                self.client.query(&sql, &[&limit]).await
+            }
+            Query::After { seq, limit } => {
+                let sql = format!(
+                    "SELECT {} WHERE m.seq > $1 ORDER BY m.seq ASC LIMIT $2",
+                    MESSAGE_COLUMNS
+                );
+                let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+                self.client.query(&sql, &[&seq, &limit]).await
+            }
+            Query::Between { after, before } => {
+                let sql = format!(
+                    "SELECT {} WHERE m.seq > $1 AND m.seq < $2 ORDER BY m.seq ASC",
+                    MESSAGE_COLUMNS
+                );
+                self.client.query(&sql, &[&after, &before]).await
+            }
+        }
+        .map_err(|e| format!("Failed to fetch messages: {}", e))?;
+        let mut messages: Vec<ReceivedMessage> = rows.iter().map(message).collect();
+        messages.sort_by_key(|m| m.seq);
+        Ok(messages)
+    }
+
+    async fn send_message(
+        &self,
+        client_id: Uuid,
+        user_id: Uuid,
+        content: &str,
+    ) -> Result<(), String> {
+        let already_sent = self
+            .client
+            .query_opt("SELECT 1 FROM messages WHERE client_id = $1", &[&client_id])
+            .await
+            .map_err(|e| e.to_string())?
+            .is_some();
+        if already_sent {
+            return Ok(());
+        }
+
+        let keep = (MAX_MESSAGES - 1) as i64;
+        self.client
+            .execute(
+                "DELETE FROM messages WHERE seq IN
+                 (SELECT seq FROM messages ORDER BY seq DESC OFFSET $1)",
+                &[&keep],
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        self.client
+            .execute(
+                "INSERT INTO messages (id, client_id, channel, content, user_id)
+                 VALUES ($1, $2, $3, $4, $5) ON CONFLICT (client_id) DO NOTHING",
+                &[
+                    &Uuid::new_v4(),
+                    &client_id,
+                    &DEFAULT_CHANNEL,
+                    &content,
+                    &user_id,
+                ],
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    async fn ban_history(&self, user_id: Uuid) -> Result<Vec<Ban>, String> {
+        let rows = self
+            .client
+            .query(
+                "SELECT reason, created_at, expires_at, is_active FROM bans
+                 WHERE user_id = $1 ORDER BY id DESC",
+                &[&user_id],
+            )
+            .await
+            .map_err(|e| format!("Failed to fetch ban history: {}", e))?;
+        Ok(rows
+            .iter()
+            .map(|row| Ban {
+                reason: row.get(0),
+                created_at: row.get(1),
+                expires_at: row.get(2),
+                is_active: row.get(3),
+            })
+            .collect())
+    }
+
+    async fn create_ban(
+        &self,
+        user_id: Uuid,
+        reason: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), String> {
+        self.client
+            .execute(
+                "INSERT INTO bans (user_id, reason, expires_at) VALUES ($1, $2, $3)",
+                &[&user_id, &reason, &expires_at],
+            )
+            .await
+            .map_err(|e| format!("Failed to create ban: {}", e))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use rand::Rng;
+
+use gooncord_core::BAN_REASONS;
+
+/// How often everyone online is rolled for a ban.
+pub const ROLL_INTERVAL_SECS: u64 = 5;
+
+/// A ban that a roll came up with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    pub reason: &'static str,
+    pub duration_secs: i64,
+}
+
+/// One roll at `chance`, the same odds, reasons and durations the desktop
+/// client uses when it rolls for itself.
+pub fn roll(rng: &mut impl Rng, chance: f64) -> Option<Roll> {
+    if rng.gen_range(0.0..1.0) >= chance {
+        return None;
+    }
+    Some(Roll {
+        reason: BAN_REASONS[rng.gen_range(0..BAN_REASONS.len())],
+        duration_secs: rng.gen_range(10..30),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn odds_decide_and_bans_last_10_to_30_seconds() {
+        let mut rng = StdRng::seed_from_u64(7);
+        assert!((0..1000).all(|_| roll(&mut rng, 0.0).is_none()));
+        for _ in 0..1000 {
+            let roll = roll(&mut rng, 1.0).unwrap();
+            assert!((10..30).contains(&roll.duration_secs));
+            assert!(BAN_REASONS.contains(&roll.reason));
+        }
+        let hits = (0..10_000)
+            .filter(|_| roll(&mut rng, 0.05).is_some())
+            .count();
+        assert!((400..600).contains(&hits), "{}", hits);
+    }
+}
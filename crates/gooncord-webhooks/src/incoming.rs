@@ -0,0 +1,154 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use gooncord_core::ApiClient;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Longest message an integration may post.
+pub const MAX_CONTENT_LEN: usize = 2000;
+
+/// What integrations POST to `/hooks/<secret>`.
+#[derive(Deserialize, Debug)]
+pub struct IncomingMessage {
+    pub content: String,
+}
+
+/// Serves incoming webhooks. Each one posts as its own bot user into its
+/// channel; the secret in the URL is all the authentication there is.
+pub fn router(api: ApiClient) -> Router {
+    Router::new()
+        .route("/hooks/:secret", post(receive))
+        .with_state(Arc::new(api))
+}
+
+async fn receive(
+    State(api): State<Arc<ApiClient>>,
+    Path(secret): Path<String>,
+    Json(message): Json<IncomingMessage>,
+) -> (StatusCode, String) {
+    match post_message(&api, &secret, message.content.trim()).await {
+        Ok(()) => (StatusCode::NO_CONTENT, String::new()),
+        Err(rejection) => rejection,
+    }
+}
+
+async fn post_message(
+    api: &ApiClient,
+    secret: &str,
+    content: &str,
+) -> Result<(), (StatusCode, String)> {
+    if content.is_empty() || content.len() > MAX_CONTENT_LEN {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("content must be 1 to {} bytes", MAX_CONTENT_LEN),
+        ));
+    }
+    // Secrets are URL-safe base64; anything else can't match and mustn't
+    // reach the filter.
+    if !secret
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err((StatusCode::NOT_FOUND, "Unknown webhook".to_string()));
+    }
+
+    // The secret is checked, and the message posted as the webhook's bot,
+    // inside the database: secrets aren't readable over the anon key.
+    let unavailable = |e: String| (StatusCode::BAD_GATEWAY, e);
+    let request = api
+        .rpc("gooncord_post_webhook")
+        .json(&json!({ "p_secret": secret, "p_content": content }));
+    let posted: bool = api
+        .send(request, "Failed to post message")
+        .await
+        .map_err(unavailable)?
+        .json()
+        .await
+        .map_err(|e| unavailable(format!("Failed to parse webhook result: {}", e)))?;
+    if !posted {
+        return Err((StatusCode::NOT_FOUND, "Unknown webhook".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    type Inserted = Arc<Mutex<Vec<serde_json::Value>>>;
+
+    /// Stands in for PostgREST: one incoming webhook with secret `abc`,
+    /// whose posts are remembered.
+    async fn postgrest(bot: Uuid) -> (String, Inserted) {
+        let inserted = Inserted::default();
+        let app = Router::new()
+            .route("/rest/v1/rpc/gooncord_post_webhook", post(post_webhook))
+            .with_state((bot, inserted.clone()));
+        (serve(app).await, inserted)
+    }
+
+    async fn post_webhook(
+        State((bot, inserted)): State<(Uuid, Inserted)>,
+        Json(args): Json<serde_json::Value>,
+    ) -> Json<bool> {
+        if args["p_secret"] != "abc" {
+            return Json(false);
+        }
+        inserted.lock().unwrap().push(json!({
+            "user_id": bot,
+            "channel": "ci",
+            "content": args["p_content"],
+        }));
+        Json(true)
+    }
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn posts_as_the_integration() {
+        let bot = Uuid::new_v4();
+        let (backend, inserted) = postgrest(bot).await;
+        let relay = serve(router(ApiClient::new(backend, "key".to_string()))).await;
+        let http = reqwest::Client::new();
+
+        let response = http
+            .post(format!("{}/hooks/abc", relay))
+            .json(&json!({ "content": " ✅ build 42 passed " }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), StatusCode::NO_CONTENT.as_u16());
+        {
+            let inserted = inserted.lock().unwrap();
+            assert_eq!(inserted.len(), 1);
+            assert_eq!(inserted[0]["user_id"], json!(bot));
+            assert_eq!(inserted[0]["channel"], "ci");
+            assert_eq!(inserted[0]["content"], "✅ build 42 passed");
+        }
+
+        for (secret, content, status) in [
+            ("nope", "hi", StatusCode::NOT_FOUND),
+            ("abc&id=gt.0", "hi", StatusCode::NOT_FOUND),
+            ("abc", "   ", StatusCode::UNPROCESSABLE_ENTITY),
+        ] {
+            let response = http
+                .post(format!("{}/hooks/{}", relay, secret))
+                .json(&json!({ "content": content }))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status().as_u16(), status.as_u16(), "{}", secret);
+        }
+        assert_eq!(inserted.lock().unwrap().len(), 1);
+    }
+}
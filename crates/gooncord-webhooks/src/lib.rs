@@ -0,0 +1,57 @@
+//! Webhooks for Gooncord, run as a relay next to the backend.
+//!
+//! Incoming webhooks let other tools post into a channel: each has a secret
+//! URL (`POST /hooks/<secret>` with `{"content": "..."}`) and posts as its
+//! own bot user. Outgoing webhooks get a signed `POST` for every new message
+//! or ban they subscribed to. Both are set up by moderators in the app.
+
+pub mod incoming;
+pub mod outgoing;
+
+use gooncord_bot::Bot;
+use std::time::{Duration, Instant};
+
+use outgoing::{OutgoingWebhook, Payload};
+
+/// How often the list of outgoing webhooks is reloaded.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Forwards the bot's events to outgoing webhooks until the process ends.
+/// Deliveries run in the background, so a slow endpoint holds nobody up.
+pub async fn forward_events(mut bot: Bot) {
+    let http = reqwest::Client::new();
+    let mut hooks: Vec<OutgoingWebhook> = Vec::new();
+    let mut last_refresh: Option<Instant> = None;
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        interval.tick().await;
+
+        if last_refresh.is_none_or(|t| t.elapsed() >= REFRESH_INTERVAL) {
+            match outgoing::load(bot.api()).await {
+                Ok(loaded) => hooks = loaded,
+                Err(e) => eprintln!("{}", e),
+            }
+            last_refresh = Some(Instant::now());
+        }
+
+        let events = match bot.poll().await {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+        for event in events {
+            let payload = Payload::from_event(event);
+            for hook in hooks.iter().filter(|hook| hook.wants(&payload)) {
+                let (http, hook, payload) = (http.clone(), hook.clone(), payload.clone());
+                tokio::spawn(async move {
+                    if let Err(e) = outgoing::deliver(&http, &hook, &payload).await {
+                        eprintln!("{}", e);
+                    }
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use gooncord_bot::Bot;
+use gooncord_webhooks::{forward_events, incoming};
+use std::env;
+
+/// Where incoming webhooks are served unless `GOONCORD_WEBHOOK_ADDR` says
+/// otherwise.
+const DEFAULT_ADDR: &str = "127.0.0.1:8787";
+
+fn var(name: &str) -> Result<String, String> {
+    env::var(name).map_err(|_| format!("{} is not set", name))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    let bot = Bot::connect(
+        &var("GOONCORD_URL")?,
+        &var("GOONCORD_KEY")?,
+        &var("GOONCORD_BOT_TOKEN")?,
+    )
+    .await?;
+    let app = incoming::router(bot.api().clone());
+    tokio::spawn(forward_events(bot));
+
+    let addr = env::var("GOONCORD_WEBHOOK_ADDR").unwrap_or_else(|_| DEFAULT_ADDR.to_string());
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .map_err(|e| format!("Can't listen on {}: {}", addr, e))?;
+    println!("Incoming webhooks on http://{}/hooks/<secret>", addr);
+    axum::serve(listener, app).await.map_err(|e| e.to_string())
+}
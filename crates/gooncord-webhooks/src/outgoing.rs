@@ -0,0 +1,209 @@
+use chrono::{DateTime, Utc};
+use gooncord_bot::Event;
+use gooncord_core::ApiClient;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// `sha256=` followed by the hex HMAC-SHA256 of the body, keyed with the
+/// webhook's secret.
+pub const SIGNATURE_HEADER: &str = "X-Gooncord-Signature";
+/// `message` or `ban`, same as the payload's `event` field.
+pub const EVENT_HEADER: &str = "X-Gooncord-Event";
+
+/// An endpoint to tell about events, as configured in the app.
+#[derive(Deserialize, Debug, Clone)]
+pub struct OutgoingWebhook {
+    pub id: Uuid,
+    pub url: String,
+    pub secret: String,
+    /// Which payload kinds it wants; see [`Payload::kind`].
+    pub events: Vec<String>,
+}
+
+impl OutgoingWebhook {
+    pub fn wants(&self, payload: &Payload) -> bool {
+        self.events.iter().any(|e| e == payload.kind())
+    }
+}
+
+/// The JSON body of an outgoing webhook.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Payload {
+    Message {
+        id: String,
+        seq: i64,
+        username: String,
+        content: String,
+        created_at: String,
+    },
+    Ban {
+        username: String,
+        reason: String,
+        created_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    },
+}
+
+impl Payload {
+    pub fn from_event(event: Event) -> Payload {
+        match event {
+            Event::MessageCreated(message) => Payload::Message {
+                id: message.id,
+                seq: message.seq,
+                username: message.username,
+                content: message.content,
+                created_at: message.created_at,
+            },
+            Event::UserBanned(ban) => Payload::Ban {
+                username: ban.username,
+                reason: ban.reason,
+                created_at: ban.created_at,
+                expires_at: ban.expires_at,
+            },
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Payload::Message { .. } => "message",
+            Payload::Ban { .. } => "ban",
+        }
+    }
+}
+
+fn mac(secret: &str) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size")
+}
+
+/// The [`SIGNATURE_HEADER`] value for `body`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = mac(secret);
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Checks a [`SIGNATURE_HEADER`] value in constant time. For receivers.
+pub fn verify(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(digest) = signature
+        .strip_prefix("sha256=")
+        .and_then(|hex| hex::decode(hex).ok())
+    else {
+        return false;
+    };
+    let mut mac = mac(secret);
+    mac.update(body);
+    mac.verify_slice(&digest).is_ok()
+}
+
+/// The outgoing webhooks relayed by the bot `api` is signed in as, secrets
+/// included. Other bots' webhooks stay out of sight.
+pub async fn load(api: &ApiClient) -> Result<Vec<OutgoingWebhook>, String> {
+    api.send(
+        api.rpc("gooncord_outgoing_webhooks").json(&json!({})),
+        "Failed to fetch outgoing webhooks",
+    )
+    .await?
+    .json()
+    .await
+    .map_err(|e| format!("Failed to parse outgoing webhooks: {}", e))
+}
+
+pub async fn deliver(
+    http: &reqwest::Client,
+    hook: &OutgoingWebhook,
+    payload: &Payload,
+) -> Result<(), String> {
+    let body = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+    let response = http
+        .post(&hook.url)
+        .header("Content-Type", "application/json")
+        .header(EVENT_HEADER, payload.kind())
+        .header(SIGNATURE_HEADER, sign(&hook.secret, &body))
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| format!("Webhook {} failed: {}", hook.id, e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Webhook {} failed: {}", hook.id, response.status()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use axum::Router;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn signs_like_hmac_sha256() {
+        let body = b"The quick brown fox jumps over the lazy dog";
+        let signature = sign("key", body);
+        assert_eq!(
+            signature,
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        assert!(verify("key", body, &signature));
+        assert!(!verify("other key", body, &signature));
+        assert!(!verify("key", body, "f7bc83f4"));
+    }
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, Bytes)>>>;
+
+    /// Stands in for whatever the webhook points at, remembering each
+    /// request.
+    async fn stand_in() -> (String, Received) {
+        let received = Received::default();
+        let app = Router::new()
+            .route("/hook", post(remember))
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, received)
+    }
+
+    async fn remember(State(received): State<Received>, headers: HeaderMap, body: Bytes) {
+        received.lock().unwrap().push((headers, body));
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payloads() {
+        let (url, received) = stand_in().await;
+        let hook = OutgoingWebhook {
+            id: Uuid::new_v4(),
+            url,
+            secret: "s3cret".to_string(),
+            events: vec!["ban".to_string()],
+        };
+        let payload = Payload::Ban {
+            username: "alice".to_string(),
+            reason: "illegal emoji usage".to_string(),
+            created_at: Utc::now(),
+            expires_at: Utc::now(),
+        };
+        assert!(hook.wants(&payload));
+
+        deliver(&reqwest::Client::new(), &hook, &payload)
+            .await
+            .unwrap();
+
+        let received = received.lock().unwrap();
+        let (headers, body) = &received[0];
+        assert_eq!(headers[EVENT_HEADER], "ban");
+        let signature = headers[SIGNATURE_HEADER].to_str().unwrap();
+        assert!(verify("s3cret", body, signature));
+        let json: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(json["event"], "ban");
+        assert_eq!(json["username"], "alice");
+    }
+}
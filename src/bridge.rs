@@ -0,0 +1,7 @@
+use gooncord_core::events::{to_script, UiEvent};
+use web_view::{Error, WVResult, WebView};
+
+pub fn dispatch<T>(webview: &mut WebView<T>, event: UiEvent) -> WVResult {
+    let script = to_script(&event).map_err(Error::custom)?;
+    webview.eval(&script)
+}